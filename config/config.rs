@@ -7,12 +7,15 @@ use std::ops::Deref;
 use wtftw::window_system::*;
 use wtftw::window_manager::*;
 use wtftw::handlers::default::*;
+use wtftw::handlers::rules::{ manage_hook, class, window_type, Action };
 use wtftw::config::*;
+//use wtftw::status::{ Bar, StatusLogger };
 use wtftw::util::*;
 use wtftw::layout::Direction;
 use wtftw::layout::LayoutMessage;
 use wtftw_contrib::layout::{ AvoidStrutsLayout, LayoutCollection, BinarySpacePartition, GapLayout, MirrorLayout, NoBordersLayout, FullLayout };
 
+export_abi_version!();
 
 #[no_mangle]
 pub extern fn configure(_: &mut WindowManager, w: &dyn WindowSystem, config: &mut Config) {
@@ -33,6 +36,16 @@ pub extern fn configure(_: &mut WindowManager, w: &dyn WindowSystem, config: &mu
                                 "七: 音楽", "八: im", "九: 残り"))
         .into_iter().map(String::from).collect();
 
+    // Window rules
+    config.set_manage_hook(manage_hook(vec!(
+            class().is("Steam").then(Action::Shift(String::from("五: スチーム"))),
+            (class().is("Gimp") | class().is("Pinentry")).then(Action::CenterFloat),
+            window_type(WindowType::Notification).then(Action::Ignore))));
+
+    // Status bar, uncomment along with the status import if xmobar is installed
+    //let xmobar = spawn_pipe(config, "xmobar", Vec::new());
+    //config.set_log_hook(StatusLogger::new(Bar::Xmobar).log_hook(xmobar));
+
     // Register key handlers
 
     // Some standard key handlers for starting, restarting, etc.
@@ -49,6 +62,8 @@ pub extern fn configure(_: &mut WindowManager, w: &dyn WindowSystem, config: &mu
     add_key_handler_str!(config, w, "Return", modm,        |m, w, c| m.windows(w.deref(), c, &|x| x.swap_master()));
     add_key_handler_str!(config, w, "c", modm, |m, w, c| m.kill_window(w.deref()).windows(w.deref(), c, &|x| x.clone()));
 
+    add_key_handler_str!(config, w, "f", modm, toggle_fullscreen);
+
     add_key_handler_str!(config, w, "t", modm, |m, w, c| {
         match m.workspaces.peek() {
             Some(window) => m.windows(w.deref(), c, &|x| x.sink(window)),
@@ -78,6 +93,12 @@ pub extern fn configure(_: &mut WindowManager, w: &dyn WindowSystem, config: &mu
     add_key_handler_str!(config, w, "i",      modm | KeyModifiers::CONTROLMASK, send_layout_message!(LayoutMessage::TreeShrinkFrom(Direction::Down)));
     add_key_handler_str!(config, w, "o",      modm | KeyModifiers::CONTROLMASK, send_layout_message!(LayoutMessage::TreeShrinkFrom(Direction::Up)));
 
+    // Resize mode, h and l resize until Escape is pressed
+    add_key_handler_str!(config, w, "r", modm | KeyModifiers::SHIFTMASK, |m, w, _| m.enter_mode(w.deref(), "resize"));
+    config.add_mode(w, "resize");
+    config.add_mode_key_binding(w, "resize", "h", Box::new(send_layout_message!(LayoutMessage::Decrease))).unwrap();
+    config.add_mode_key_binding(w, "resize", "l", Box::new(send_layout_message!(LayoutMessage::Increase))).unwrap();
+
 
     // Workspace switching and moving
     for i in 1usize..10 {
@@ -92,11 +113,14 @@ pub extern fn configure(_: &mut WindowManager, w: &dyn WindowSystem, config: &mu
     add_key_handler_str!(config, w, "j", modm | KeyModifiers::CONTROLMASK, run!("amixer", "-q set Master 5%-"));
     add_key_handler_str!(config, w, "k", modm | KeyModifiers::CONTROLMASK, run!("amixer", "-q set Master 5%+"));
 
-    add_key_handler_code!(config, 0x1008ff11, KeyModifiers::NONEMASK, run!("amixer", "-q set Master 5%-"));
-    add_key_handler_code!(config, 0x1008ff13, KeyModifiers::NONEMASK, run!("amixer", "-q set Master 5%+"));
+    config.add_key_binding(w, "<XF86AudioLowerVolume>", Box::new(run!("amixer", "-q set Master 5%-"))).unwrap();
+    config.add_key_binding(w, "<XF86AudioRaiseVolume>", Box::new(run!("amixer", "-q set Master 5%+"))).unwrap();
+
+    config.add_key_binding(w, "<XF86MonBrightnessUp>",   Box::new(run!("xbacklight", "+10"))).unwrap();
+    config.add_key_binding(w, "<XF86MonBrightnessDown>", Box::new(run!("xbacklight", "-10"))).unwrap();
 
-    add_key_handler_code!(config, 0x1008ff02, KeyModifiers::NONEMASK, run!("xbacklight", "+10"));
-    add_key_handler_code!(config, 0x1008ff03, KeyModifiers::NONEMASK, run!("xbacklight", "-10"));
+    // Chords, e.g. mod-x then m
+    config.add_key_binding(w, "M-x m", Box::new(run!("amixer", "-q set Master toggle"))).unwrap();
 
     add_mouse_handler!(config, BUTTON1, modm,
                        |m, w, c, s| {
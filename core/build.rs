@@ -0,0 +1,50 @@
+//! Identify the compiler and the sources wtftw is built from, so
+//! configuration modules built from anything else are refused.
+//! Shared by the `wtftw` package, whose library is the same source.
+use std::collections::hash_map::DefaultHasher;
+use std::env;
+use std::fs::{read, read_dir};
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+fn hash_dir(hasher: &mut DefaultHasher, root: &Path, dir: &Path) -> io::Result<()> {
+    let mut entries: Vec<PathBuf> = read_dir(dir)?
+        .filter_map(|x| x.ok().map(|x| x.path()))
+        .collect();
+    entries.sort();
+    for path in entries {
+        if path.is_dir() {
+            hash_dir(hasher, root, &path)?;
+        } else {
+            path.strip_prefix(root).unwrap().hash(hasher);
+            read(&path)?.hash(hasher);
+        }
+    }
+    Ok(())
+}
+
+fn main() -> io::Result<()> {
+    let manifest_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    let src = ["src", "core/src"]
+        .iter()
+        .map(|x| manifest_dir.join(x))
+        .find(|x| x.join("lib.rs").exists())
+        .expect("no library sources to identify");
+
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_owned());
+    let output = Command::new(rustc).arg("--version").output()?;
+    let rustc_version = String::from_utf8_lossy(&output.stdout).trim().to_owned();
+
+    let mut hasher = DefaultHasher::new();
+    hash_dir(&mut hasher, &src, &src)?;
+
+    println!(
+        "cargo:rustc-env=WTFTW_ABI={}, source {:016x}",
+        rustc_version,
+        hasher.finish()
+    );
+    println!("cargo:rerun-if-changed={}", src.display());
+    Ok(())
+}
@@ -1,28 +1,37 @@
 use crate::core::workspaces::Workspaces;
 use crate::handlers::default::{exit, restart, start_terminal};
 use crate::handlers::{KeyHandler, LogHook, ManageHook, MouseHandler, StartupHook};
-use crate::layout::{Layout, TallLayout};
+use crate::layout::{Layout, LayoutConstructor, LayoutRegistry, TallLayout};
 use crate::window_manager::WindowManager;
 use crate::window_system::{
     KeyCommand, KeyModifiers, MouseButton, MouseCommand, Window, WindowSystem,
 };
 use std::borrow::ToOwned;
+use std::collections::hash_map::DefaultHasher;
 use std::collections::BTreeMap;
+use std::ffi::CStr;
+use std::hash::{Hash, Hasher};
+use std::os::raw::c_char;
 
-use anyhow::Result;
+use anyhow::{anyhow, bail, Result};
 use dylib::DynamicLibrary;
-use std::fs::metadata;
 use std::fs::File;
-use std::fs::{create_dir_all, read_dir};
+use std::fs::{create_dir_all, read, read_dir, read_to_string, remove_file, write};
 use std::io::Write;
 use std::mem;
+use std::ops::Deref;
 use std::path::Path;
 use std::path::PathBuf;
 use std::process::Child;
 use std::process::Command;
 use std::rc::Rc;
 use std::sync::RwLock;
-use std::thread::spawn;
+
+/// The compiler and a hash of the sources wtftw is built from, see
+/// `build.rs`. Configuration modules share `Config`, `WindowManager`,
+/// `WindowSystem` and everything they contain, so they have to be
+/// built from the very same sources with the very same compiler.
+pub const ABI_VERSION: &str = concat!(env!("WTFTW_ABI"), "\0");
 
 pub struct GeneralConfig {
     /// Whether focus follows mouse movements or
@@ -66,14 +75,40 @@ impl Clone for GeneralConfig {
     }
 }
 
+/// Key bindings that replace all others while their mode is active
+pub struct KeyMode {
+    pub key_handlers: BTreeMap<KeyCommand, KeyHandler>,
+    /// Whether the mode stays active until left explicitly, or is
+    /// left after the next key like the modes of a chord
+    pub sticky: bool,
+    /// The mode a chord was bound in, which is returned to once it ends
+    pub parent: Option<String>,
+}
+
+impl KeyMode {
+    pub fn new(sticky: bool) -> KeyMode {
+        KeyMode {
+            key_handlers: BTreeMap::new(),
+            sticky,
+            parent: None,
+        }
+    }
+}
+
 pub struct InternalConfig {
     pub library: Option<DynamicLibrary>,
     pub key_handlers: BTreeMap<KeyCommand, KeyHandler>,
+    /// Key modes by name, see `Config::add_mode`
+    pub modes: BTreeMap<String, KeyMode>,
     pub mouse_handlers: BTreeMap<MouseCommand, MouseHandler>,
     pub manage_hook: ManageHook,
     pub startup_hook: StartupHook,
     pub loghook: Option<LogHook>,
+    pub layouts: LayoutRegistry,
     pub wtftw_dir: String,
+    /// Whether to update the dependencies of the config module
+    /// before building it, instead of using the ones in Cargo.lock
+    pub update_dependencies: bool,
 }
 
 impl InternalConfig {
@@ -81,11 +116,14 @@ impl InternalConfig {
         InternalConfig {
             library: None,
             key_handlers: BTreeMap::new(),
+            modes: BTreeMap::new(),
             mouse_handlers: BTreeMap::new(),
             manage_hook,
             startup_hook,
             loghook: None,
+            layouts: LayoutRegistry::new(),
             wtftw_dir: format!("{}/.wtftw", home),
+            update_dependencies: false,
         }
     }
 }
@@ -154,23 +192,31 @@ impl Config {
         m
     }
 
+    /// Bind the default keys, unless they are bound already
     pub fn default_configuration(&mut self, w: &dyn WindowSystem) {
         let mod_mask = self.general.mod_mask;
-        self.add_key_handler(
-            w.get_keycode_from_string("Return"),
-            mod_mask | KeyModifiers::SHIFTMASK,
-            Box::new(|m, ws, c| start_terminal(m, ws, c)),
-        );
-        self.add_key_handler(
-            w.get_keycode_from_string("q"),
-            mod_mask,
-            Box::new(|m, ws, c| restart(m, ws, c).expect("error while restarting wtftw")),
-        );
-        self.add_key_handler(
-            w.get_keycode_from_string("q"),
-            mod_mask | KeyModifiers::SHIFTMASK,
-            Box::new(|m, ws, c| exit(m, ws, c)),
-        );
+        let defaults: Vec<(&str, KeyModifiers, KeyHandler)> = vec![
+            (
+                "Return",
+                mod_mask | KeyModifiers::SHIFTMASK,
+                Box::new(|m, ws, c| start_terminal(m, ws, c)),
+            ),
+            (
+                "q",
+                mod_mask,
+                Box::new(|m, ws, c| restart(m, ws, c).expect("error while restarting wtftw")),
+            ),
+            (
+                "q",
+                mod_mask | KeyModifiers::SHIFTMASK,
+                Box::new(|m, ws, c| exit(m, ws, c)),
+            ),
+        ];
+
+        for (key, mask, handler) in defaults {
+            let command = KeyCommand::new(w.get_keycode_from_string(key), mask);
+            self.internal.key_handlers.entry(command).or_insert(handler);
+        }
     }
 
     pub fn get_mod_mask(&self) -> KeyModifiers {
@@ -183,6 +229,144 @@ impl Config {
             .insert(KeyCommand::new(key, mask), keyhandler);
     }
 
+    /// Bind a handler to a key given like `M-S-Return`, see
+    /// `KeyCommand::parse`, or to keys pressed one after another like
+    /// `M-x w`. All but the last key of such a chord enter a mode
+    /// named after the keys so far, which is left after the next key.
+    /// Chords bound in a mode are named after it too, e.g. `resize: g`,
+    /// and return to it when they end.
+    pub fn add_key_binding(
+        &mut self,
+        window_system: &dyn WindowSystem,
+        binding: &str,
+        keyhandler: KeyHandler,
+    ) -> Result<()> {
+        let keys = KeyCommand::parse_sequence(binding, self.general.mod_mask, window_system)?;
+        self.add_key_sequence(None, binding, &keys, keyhandler)
+    }
+
+    /// Add a mode whose bindings replace all others once entered with
+    /// `WindowManager::enter_mode`, until it is left with `Escape`
+    /// or `WindowManager::exit_mode`. The keyboard is grabbed meanwhile.
+    pub fn add_mode(&mut self, window_system: &dyn WindowSystem, name: &str) {
+        let escape = KeyCommand::new(
+            window_system.get_keycode_from_string("Escape"),
+            KeyModifiers::NONEMASK,
+        );
+        let mode = self
+            .internal
+            .modes
+            .entry(name.to_owned())
+            .or_insert_with(|| KeyMode::new(true));
+        mode.sticky = true;
+        mode.key_handlers
+            .entry(escape)
+            .or_insert_with(|| Box::new(|m, w, _| m.exit_mode(w.deref())));
+    }
+
+    /// Bind a handler to a key, or a chord, in the given mode.
+    /// See `add_key_binding` and `add_mode`.
+    pub fn add_mode_key_binding(
+        &mut self,
+        window_system: &dyn WindowSystem,
+        mode: &str,
+        binding: &str,
+        keyhandler: KeyHandler,
+    ) -> Result<()> {
+        let keys = KeyCommand::parse_sequence(binding, self.general.mod_mask, window_system)?;
+        self.add_key_sequence(Some(mode), binding, &keys, keyhandler)
+    }
+
+    /// Bind a handler to the given keys, parsed from `binding`, in the
+    /// given mode or outside of all modes if `None`
+    pub fn add_key_sequence(
+        &mut self,
+        mode: Option<&str>,
+        binding: &str,
+        keys: &[KeyCommand],
+        keyhandler: KeyHandler,
+    ) -> Result<()> {
+        if let Some(name) = mode.filter(|m| !self.internal.modes.contains_key(*m)) {
+            bail!("unknown mode {:?}", name);
+        }
+        let (last, prefix) = match keys.split_last() {
+            Some(x) => x,
+            None => bail!("no key given"),
+        };
+
+        let words: Vec<&str> = binding.split_whitespace().collect();
+        let mut current = mode.map(|m| m.to_owned());
+        for (i, &key) in prefix.iter().enumerate() {
+            let chord = match mode {
+                Some(parent) => format!("{}: {}", parent, words[..=i].join(" ")),
+                None => words[..=i].join(" "),
+            };
+            self.internal
+                .modes
+                .entry(chord.clone())
+                .or_insert_with(|| KeyMode::new(false))
+                .parent = mode.map(|m| m.to_owned());
+            let target = chord.clone();
+            self.key_handlers_mut(current.as_deref()).insert(
+                key,
+                Box::new(move |m, w, _| m.enter_mode(w.deref(), &target)),
+            );
+            current = Some(chord);
+        }
+
+        self.key_handlers_mut(current.as_deref())
+            .insert(*last, keyhandler);
+        Ok(())
+    }
+
+    fn key_handlers_mut(&mut self, mode: Option<&str>) -> &mut BTreeMap<KeyCommand, KeyHandler> {
+        match mode {
+            Some(name) => {
+                &mut self
+                    .internal
+                    .modes
+                    .entry(name.to_owned())
+                    .or_insert_with(|| KeyMode::new(false))
+                    .key_handlers
+            }
+            None => &mut self.internal.key_handlers,
+        }
+    }
+
+    /// Run the handler bound to the given key in the current mode.
+    /// Chord modes are left for the mode they were bound in on any key,
+    /// keys unbound in a mode are dropped.
+    pub fn handle_key(
+        &self,
+        window_manager: WindowManager,
+        window_system: Rc<dyn WindowSystem>,
+        key: KeyCommand,
+    ) -> WindowManager {
+        let mode = window_manager.mode.clone();
+        let (window_manager, handlers) = match mode {
+            None => (window_manager, &self.internal.key_handlers),
+            Some(ref name) => match self.internal.modes.get(name) {
+                Some(mode) if mode.sticky => (window_manager, &mode.key_handlers),
+                Some(mode) => match mode.parent {
+                    Some(ref parent) => (
+                        window_manager.enter_mode(window_system.deref(), parent),
+                        &mode.key_handlers,
+                    ),
+                    None => (
+                        window_manager.exit_mode(window_system.deref()),
+                        &mode.key_handlers,
+                    ),
+                },
+                None => return window_manager.exit_mode(window_system.deref()),
+            },
+        };
+
+        match handlers.get(&key) {
+            Some(handler) => handler(window_manager, window_system.clone(), &self.general),
+            None => window_manager,
+        }
+    }
+
     pub fn add_mouse_handler(
         &mut self,
         button: MouseButton,
@@ -194,6 +378,16 @@ impl Config {
             .insert(MouseCommand::new(button, mask), mousehandler);
     }
 
+    /// Grab the bound keys and mouse buttons, releasing all others
+    pub fn grab_bindings(&self, window_system: &dyn WindowSystem) {
+        window_system.ungrab_keys();
+        window_system.ungrab_buttons();
+        window_system.grab_keys(self.internal.key_handlers.keys().copied().collect());
+        for &command in self.internal.mouse_handlers.keys() {
+            window_system.grab_button(command);
+        }
+    }
+
     pub fn set_manage_hook(&mut self, hook: ManageHook) {
         self.internal.manage_hook = hook;
     }
@@ -202,133 +396,324 @@ impl Config {
         self.internal.loghook = Some(hook);
     }
 
+    /// Run the log hook after every event. Without one, notices
+    /// like failed config modules are shown in a window instead.
+    pub fn log(&mut self, window_manager: &WindowManager, window_system: Rc<dyn WindowSystem>) {
+        match self.internal.loghook {
+            Some(ref mut loghook) => loghook(window_manager.clone(), window_system),
+            None => window_system.show_notice(window_manager.notice.as_deref()),
+        }
+    }
+
+    /// Make a layout known under its description, so its
+    /// parameters survive restarts
+    pub fn register_layout(&mut self, description: &str, constructor: LayoutConstructor) {
+        self.internal.layouts.register(description, constructor);
+    }
+
+    /// Build the config module in `wtftw_dir`, if there is one, and let it
+    /// configure wtftw. If it can't be built or loaded, the error is logged
+    /// and returned for the session to show, and the default configuration
+    /// is used.
     pub fn compile_and_call(&mut self, m: &mut WindowManager, w: &dyn WindowSystem) -> Result<()> {
-        let toml = format!("{}/Cargo.toml", self.internal.wtftw_dir.clone());
-
-        if !path_exists(&self.internal.wtftw_dir.clone()) {
-            match create_dir_all(Path::new(&self.internal.wtftw_dir.clone())) {
-                Ok(()) => (),
-                Err(e) => panic!(format!(
-                    "mkdir: {} failed with error {}",
-                    self.internal.wtftw_dir.clone(),
-                    e
-                )),
-            }
+        let result = match self.has_module() {
+            Ok(true) => self.compile().and_then(|()| self.call(m, w)),
+            other => other.map(|_| ()),
+        };
+
+        if let Err(ref e) = result {
+            error!("{:#}, using the default configuration instead", e);
         }
+        if self.internal.library.is_none() {
+            self.default_configuration(w);
+        }
+        result
+    }
 
-        if !path_exists(&toml) {
-            let file = File::create(Path::new(&toml).as_os_str());
-            file?.write_all(
-                "[project]\n\
-                                     name = \"config\"\n\
-                                     version = \"0.0.0\"\n\
-                                     authors = [\"wtftw\"]\n\n\
-                                     [dependencies.wtftw_contrib]
-                                     git = \"https://github.com/Kintaro/wtftw-contrib.git\"\n
-                                     [dependencies.wtftw]\n\
-                                     git = \"https://github.com/Kintaro/wtftw.git\"\n\n\
-                                     [lib]\n\
-                                     name = \"config\"\n\
-                                     crate-type = [\"dylib\"]"
-                    .as_bytes(),
-            )?;
+    /// Build and load the config module, without calling it.
+    /// Returns whether there is a config module at all.
+    pub fn check(&self) -> Result<bool> {
+        if !self.has_module()? {
+            return Ok(false);
         }
+        self.compile()?;
+        self.open()?;
+        Ok(true)
+    }
 
-        let config_source = format!("{}/src/lib.rs", self.internal.wtftw_dir.clone());
-        if path_exists(&config_source) && self.compile()? {
-            self.call(m, w)?;
-        } else {
-            self.default_configuration(w);
+    /// Whether there is a config module in `wtftw_dir`. Its manifest is
+    /// kept in step with this wtftw, unless the user took it over by
+    /// removing the marker.
+    fn has_module(&self) -> Result<bool> {
+        let dir = Path::new(&self.internal.wtftw_dir);
+        create_dir_all(dir)
+            .map_err(|e| anyhow!("mkdir: {} failed with error {}", dir.display(), e))?;
+
+        let toml = dir.join("Cargo.toml");
+        let manifest = config_manifest();
+        let current = read_to_string(&toml).ok();
+        let generated = current.as_deref().map_or(true, is_generated_manifest);
+        if generated && current.as_ref() != Some(&manifest) {
+            File::create(&toml)?.write_all(manifest.as_bytes())?;
         }
-        Ok(())
+
+        Ok(dir.join("src").join("lib.rs").exists())
+    }
+
+    /// Whether the config module was built from its current sources,
+    /// so there is no need to run cargo at all
+    pub fn is_compiled(&self) -> bool {
+        let dir = Path::new(&self.internal.wtftw_dir);
+        let built = read_to_string(dir.join(HASH_FILE)).ok();
+        dir.join(LIBRARY_DIR).join("libconfig.so").exists()
+            && source_hash(dir).ok().map(|x| format!("{:x}", x)) == built
     }
 
-    pub fn compile(&self) -> Result<bool> {
-        info!("updating dependencies");
-        Command::new("cargo")
-            .current_dir(&Path::new(&self.internal.wtftw_dir.clone()))
-            .arg("update")
-            .env("RUST_LOG", "none")
-            .output()?;
+    /// Build the config module in release mode. Its dependencies are
+    /// only updated if `update_dependencies` is set, otherwise the ones
+    /// in Cargo.lock are built offline, falling back to fetching them
+    /// if some are missing. The errors of failed builds are kept in
+    /// `ERROR_LOG` in `wtftw_dir`.
+    pub fn compile(&self) -> Result<()> {
+        let dir = Path::new(&self.internal.wtftw_dir);
+        let log = dir.join(ERROR_LOG);
+        if !self.internal.update_dependencies && self.is_compiled() {
+            info!("config module is up to date");
+            return Ok(());
+        }
+
+        if self.internal.update_dependencies {
+            info!("updating dependencies");
+            Command::new("cargo")
+                .current_dir(dir)
+                .arg("update")
+                .env("RUST_LOG", "none")
+                .output()?;
+        }
+
         info!("compiling config module");
-        let output = Command::new("cargo")
-            .current_dir(&Path::new(&self.internal.wtftw_dir.clone()))
-            .arg("build") //.arg("--release")
-            .env("RUST_LOG", "none")
-            .output();
-
-        Ok(match output {
-            Ok(o) => {
-                if o.status.success() {
-                    info!("config module compiled");
-                    true
-                } else {
-                    error!("error compiling config module");
-
-                    spawn(move || {
-                        Command::new("xmessage").arg("\"error compiling config module. run 'cargo build' in ~/.wtftw to get more info.\"").spawn().unwrap();
-                    });
-                    false
-                }
+        let build = |offline: bool| {
+            let mut command = Command::new("cargo");
+            command
+                .current_dir(dir)
+                .arg("build")
+                .arg("--release")
+                .env("RUST_LOG", "none");
+            if offline {
+                command.arg("--offline");
             }
-            Err(err) => {
-                error!("error compiling config module");
-                spawn(move || {
-                    Command::new("xmessage")
-                        .arg(format!("{}", err))
-                        .spawn()
-                        .unwrap();
-                });
-                false
+            command.output()
+        };
+        let offline = dir.join("Cargo.lock").exists();
+        let output = match build(offline) {
+            Ok(ref o)
+                if offline
+                    && !o.status.success()
+                    && String::from_utf8_lossy(&o.stderr).contains("offline") =>
+            {
+                info!("dependencies of the config module are missing, fetching them");
+                build(false)
             }
-        })
+            output => output,
+        }
+        .map_err(|e| anyhow!("unable to run cargo to compile the config module: {}", e))?;
+
+        if !output.status.success() {
+            let errors = String::from_utf8_lossy(&output.stderr);
+            write(&log, errors.as_bytes())?;
+            bail!(
+                "error compiling config module, see {} for details:\n{}",
+                log.display(),
+                summarize_errors(&errors)
+            );
+        }
+
+        info!("config module compiled");
+        if log.exists() {
+            remove_file(&log)?;
+        }
+        write(dir.join(HASH_FILE), format!("{:x}", source_hash(dir)?))?;
+        Ok(())
     }
 
+    /// Load the compiled configuration module and let it configure wtftw.
+    /// Modules built against a different version of wtftw are rejected
+    /// before anything is called, leaving the configuration untouched.
     pub fn call(&mut self, m: &mut WindowManager, w: &dyn WindowSystem) -> Result<()> {
+        let (lib, configure) = self.open()?;
+        self.internal.library = Some(lib);
+        configure(m, w, self);
+        Ok(())
+    }
+
+    /// Load the compiled configuration module and look up its
+    /// `configure` function, after checking its wtftw version
+    fn open(&self) -> Result<(DynamicLibrary, ConfigureFn)> {
         debug!("looking for config module");
-        let mut contents = read_dir(&Path::new(&format!(
-            "{}/target/debug",
-            self.internal.wtftw_dir.clone()
-        )))?;
-        let libname = contents.find(|x| match *x {
-            Ok(ref y) => y
-                .path()
-                .into_os_string()
-                .as_os_str()
-                .to_str()
-                .map(|x| x.contains("libconfig.so"))
-                .unwrap_or(false),
-            Err(_) => false,
-        });
-
-        if let Ok(lib) = DynamicLibrary::open(Some(&Path::new(
-            &libname
-                .unwrap()
-                .unwrap()
-                .path()
-                .as_os_str()
-                .to_str()
-                .unwrap(),
-        ))) {
-            unsafe {
-                if let Ok(symbol) = lib.symbol("configure") {
-                    let result = mem::transmute::<
-                        *mut u8,
-                        extern "C" fn(&mut WindowManager, &dyn WindowSystem, &mut Config),
-                    >(symbol);
-
-                    self.internal.library = Some(lib);
-                    result(m, w, self);
-                } else {
-                    error!("Error loading config module")
-                }
-            }
+        let dir = format!("{}/{}", self.internal.wtftw_dir, LIBRARY_DIR);
+        let path = read_dir(&dir)?
+            .filter_map(|x| x.ok().map(|x| x.path()))
+            .find(|x| x.to_str().is_some_and(|x| x.contains("libconfig.so")))
+            .ok_or_else(|| anyhow!("no config module in {}", dir))?;
+
+        let lib = DynamicLibrary::open(Some(&path))
+            .map_err(|e| anyhow!("unable to load {}: {}", path.display(), e))?;
+
+        unsafe {
+            let version = lib.symbol("wtftw_abi_version").map_err(|_| {
+                anyhow!(
+                    "the config module doesn't tell its wtftw version, add export_abi_version!()"
+                )
+            })?;
+            let version = mem::transmute::<*mut u8, extern "C" fn() -> *const c_char>(version);
+            check_abi_version(&CStr::from_ptr(version()).to_string_lossy())?;
+
+            let symbol = lib
+                .symbol("configure")
+                .map_err(|e| anyhow!("the config module has no configure function: {}", e))?;
+            let configure = mem::transmute::<*mut u8, ConfigureFn>(symbol);
+
+            Ok((lib, configure))
+        }
+    }
+}
+
+/// Check the version a configuration module was built against, as
+/// exported by `export_abi_version!`. `Config` and the types it contains
+/// are passed to the module as they are, so they have to match exactly.
+pub fn check_abi_version(version: &str) -> Result<()> {
+    let expected = ABI_VERSION.trim_end_matches('\0');
+    if version != expected {
+        bail!(
+            "the config module was built with {}, but wtftw with {}. \
+             Rebuild it in the wtftw directory with cargo build --release",
+            version,
+            expected
+        );
+    }
+    Ok(())
+}
+
+/// The first line of generated config module manifests
+const MANIFEST_MARKER: &str = "# Generated by wtftw, remove this line to keep your changes";
+
+/// The upstream repository of wtftw, which wtftw_contrib depends on
+const WTFTW_REPOSITORY: &str = "https://github.com/Kintaro/wtftw.git";
+
+/// Where cargo puts the config module
+const LIBRARY_DIR: &str = "target/release";
+
+/// Where the errors of the last failed build of the config module are kept
+pub const ERROR_LOG: &str = "compile-errors.log";
+
+/// The function config modules export to configure wtftw
+#[allow(improper_ctypes_definitions)]
+type ConfigureFn = extern "C" fn(&mut WindowManager, &dyn WindowSystem, &mut Config);
+
+/// Where the hash of the sources the config module was built from is kept
+const HASH_FILE: &str = "target/config.hash";
+
+/// The manifest of the config module, building it against this very
+/// wtftw: the `wtftw` package in its sources if they are still around,
+/// or the upstream repository otherwise. wtftw_contrib, which depends
+/// on the upstream repository, is patched to use the same sources.
+pub fn config_manifest() -> String {
+    // The library is built both as wtftw and as wtftw_core in core/
+    let source = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .ancestors()
+        .find(|x| x.join("Cargo.toml").exists() && x.join("core").join("Cargo.toml").exists());
+    // Cargo refuses to patch the upstream repository with itself
+    let (wtftw, patch) = match source {
+        Some(source) => {
+            let wtftw = format!(
+                "{{ path = {} }}",
+                toml::Value::String(source.to_string_lossy().into_owned())
+            );
+            let patch = format!("[patch.\"{}\"]\nwtftw = {}\n\n", WTFTW_REPOSITORY, wtftw);
+            (wtftw, patch)
         }
+        None => (
+            format!("{{ git = \"{}\" }}", WTFTW_REPOSITORY),
+            String::new(),
+        ),
+    };
+
+    format!(
+        "{}\n\
+         [package]\n\
+         name = \"config\"\n\
+         version = \"0.0.0\"\n\
+         authors = [\"wtftw\"]\n\n\
+         [dependencies]\n\
+         wtftw = {}\n\
+         wtftw_contrib = {{ git = \"https://github.com/Kintaro/wtftw-contrib.git\" }}\n\n\
+         {}\
+         [lib]\n\
+         name = \"config\"\n\
+         crate-type = [\"dylib\"]\n",
+        MANIFEST_MARKER, wtftw, patch
+    )
+}
 
+/// Whether the given manifest was generated by wtftw, now or by the
+/// versions before the marker, which built against the git master
+fn is_generated_manifest(manifest: &str) -> bool {
+    let legacy = "[project]\n\
+                  name = \"config\"\n\
+                  version = \"0.0.0\"\n\
+                  authors = [\"wtftw\"]\n\n\
+                  [dependencies.wtftw_contrib]\n\
+                  git = \"https://github.com/Kintaro/wtftw-contrib.git\"\n\n\
+                  [dependencies.wtftw]\n\
+                  git = \"https://github.com/Kintaro/wtftw.git\"\n\n\
+                  [lib]\n\
+                  name = \"config\"\n\
+                  crate-type = [\"dylib\"]";
+    manifest.starts_with(MANIFEST_MARKER)
+        || manifest.split_whitespace().eq(legacy.split_whitespace())
+}
+
+/// Hash the manifest and sources of the config module in the given
+/// directory, together with the wtftw version it is built against
+pub fn source_hash(dir: &Path) -> Result<u64> {
+    fn hash_dir(hasher: &mut DefaultHasher, root: &Path, dir: &Path) -> Result<()> {
+        let mut entries: Vec<PathBuf> = read_dir(dir)?
+            .filter_map(|x| x.ok().map(|x| x.path()))
+            .collect();
+        entries.sort();
+        for path in entries {
+            if path.is_dir() {
+                hash_dir(hasher, root, &path)?;
+            } else {
+                path.strip_prefix(root)?.hash(hasher);
+                read(&path)?.hash(hasher);
+            }
+        }
         Ok(())
     }
+
+    let mut hasher = DefaultHasher::new();
+    ABI_VERSION.hash(&mut hasher);
+    read(dir.join("Cargo.toml"))?.hash(&mut hasher);
+    hash_dir(&mut hasher, dir, &dir.join("src"))?;
+    Ok(hasher.finish())
 }
 
-fn path_exists(path: &str) -> bool {
-    metadata(path).is_ok()
+/// The first errors reported by cargo, short enough for a message window
+fn summarize_errors(errors: &str) -> String {
+    let errors: Vec<&str> = errors
+        .lines()
+        .filter(|x| x.starts_with("error"))
+        .filter(|x| !x.starts_with("error: aborting") && !x.starts_with("error: could not compile"))
+        .collect();
+    let mut summary = errors
+        .iter()
+        .take(5)
+        .cloned()
+        .collect::<Vec<_>>()
+        .join("\n");
+    if errors.len() > 5 {
+        summary.push_str(&format!("\n... and {} more", errors.len() - 5));
+    }
+    summary
 }
@@ -0,0 +1,559 @@
+//! A declarative configuration file, read without compiling anything.
+//!
+//! `~/.config/wtftw/config.toml` sets the general options, the layout,
+//! key and mouse bindings to built-in actions and window rules. It is
+//! loaded before the compiled configuration, which may override it:
+//!
+//! ```toml
+//! mod_mask = "mod4"
+//! terminal = "urxvt -e tmux"
+//! focus_border_color = "#00b6ff"
+//! tags = ["1: term", "2: web", "3: code"]
+//! layout = [{ name = "Tall", ratio = 0.6 }, "Grid"]
+//!
+//! [[keys]]
+//! key = "M-S-Return"
+//! action = "spawn-terminal"
+//!
+//! [[keys]]
+//! key = "2"
+//! modifiers = ["mod"]
+//! action = "view 2: web"
+//!
+//! [[keys]]
+//! key = "<XF86AudioMute>"
+//! action = "spawn amixer -q set Master toggle"
+//!
+//! [[keys]]
+//! key = "M-x t"
+//! action = "spawn-terminal"
+//!
+//! [[keys]]
+//! key = "M-r"
+//! action = "mode resize"
+//!
+//! [[keys]]
+//! key = "h"
+//! mode = "resize"
+//! action = "layout decrease"
+//!
+//! [[mouse]]
+//! button = 1
+//! modifiers = ["mod"]
+//! action = "move"
+//!
+//! [[rules]]
+//! class = "Gimp"
+//! action = "float"
+//!
+//! [[rules]]
+//! title = { matches = "^Steam" }
+//! actions = ["shift 5", "border 0"]
+//! ```
+//!
+//! Keys are given like `M-S-Return`, see `KeyCommand::parse`, and
+//! `modifiers` may add more. Keys separated by spaces form a chord.
+//! Keys with a `mode` are only bound in that mode, which `mode <name>`
+//! enters and `Escape` or `exit-mode` leaves again, see `Config::add_mode`.
+//! Other key actions are `spawn-terminal`, `spawn-launcher`,
+//! `spawn <command>`, `focus-up`, `focus-down`, `swap-up`, `swap-down`,
+//! `swap-master`, `toggle-fullscreen`, `restart`, `exit`, and every
+//! command understood by `wtftwctl`, e.g. `move 3` or `layout increase-master`.
+//! Commands are split into arguments like a shell would, so quotes
+//! keep spaces, e.g. `spawn notify-send 'two words'`. A list of
+//! layouts is cycled with `layout next` and `layout prev`.
+use crate::config::{Config, GeneralConfig};
+use crate::core::workspaces::Workspaces;
+use crate::handlers::default::{exit, restart, start_launcher, start_terminal, toggle_fullscreen};
+use crate::handlers::rules::{always, Action, Property, Query, Regex, Rule};
+use crate::handlers::{rules, KeyHandler, MouseHandler};
+use crate::ipc::Command;
+use crate::layout::{ChooseLayout, Layout, LayoutRegistry};
+use crate::window_manager::WindowManager;
+use crate::window_system::{KeyCommand, KeyModifiers, MouseButton, WindowSystem, WindowType};
+use anyhow::{anyhow, bail, Context, Result};
+use std::collections::BTreeSet;
+use std::fs;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::rc::Rc;
+use toml::value::{Table, Value};
+
+/// Where the configuration file is looked for
+pub fn config_file_path() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(|| PathBuf::from("./"))
+        .join("wtftw")
+        .join("config.toml")
+}
+
+/// Apply the configuration file at the given path. Returns
+/// `false` if there is none.
+pub fn load_file(
+    config: &mut Config,
+    window_system: &dyn WindowSystem,
+    path: &Path,
+) -> Result<bool> {
+    if !path.exists() {
+        return Ok(false);
+    }
+
+    let source = fs::read_to_string(path)?;
+    load(config, window_system, &source).with_context(|| format!("in {}", path.display()))?;
+    Ok(true)
+}
+
+/// Apply the given configuration. Nothing is changed
+/// unless the whole configuration is valid.
+pub fn load(config: &mut Config, window_system: &dyn WindowSystem, source: &str) -> Result<()> {
+    let table = match source.parse::<Value>()? {
+        Value::Table(table) => table,
+        _ => bail!("expected a table"),
+    };
+
+    let mut general = config.general.clone();
+    let mut keys = Vec::new();
+    let mut modes = BTreeSet::new();
+    let mut targets = Vec::new();
+    let mut buttons = Vec::new();
+    let mut rules = None;
+
+    for (name, value) in table.iter() {
+        let context = || format!("invalid {}", name);
+        match name.as_str() {
+            "focus_follows_mouse" => {
+                general.focus_follows_mouse = boolean(value).with_context(context)?
+            }
+            "focus_border_color" => {
+                general.focus_border_color = colour(value).with_context(context)?
+            }
+            "border_color" => general.border_color = colour(value).with_context(context)?,
+            "border_width" => general.border_width = integer(value).with_context(context)? as u32,
+            "terminal" => {
+                let terminal = string(value).with_context(context)?;
+                general.terminal = match terminal.find(' ') {
+                    Some(i) => (
+                        terminal[..i].to_owned(),
+                        terminal[i + 1..].trim().to_owned(),
+                    ),
+                    None => (terminal.to_owned(), String::new()),
+                };
+            }
+            "launcher" => general.launcher = string(value).with_context(context)?.to_owned(),
+            "logfile" => general.logfile = string(value).with_context(context)?.to_owned(),
+            "tags" => {
+                general.tags = strings(value).with_context(context)?;
+                if general.tags.is_empty() {
+                    bail!("at least one tag is needed");
+                }
+            }
+            "mod_mask" => {
+                general.mod_mask =
+                    modifier(string(value)?, KeyModifiers::NONEMASK).with_context(context)?
+            }
+            "layout" => {
+                general.layout = layout(value, &config.internal.layouts).with_context(context)?
+            }
+            "keys" | "mouse" | "rules" => (),
+            _ => bail!("unknown option {:?}", name),
+        }
+    }
+
+    // Bindings come last, as "mod" refers to the configured mod_mask
+    for (i, key) in tables(table.get("keys"))?.iter().enumerate() {
+        let binding = || format!("invalid key binding {}", i + 1);
+        let name = string(field(key, "key")?).with_context(binding)?;
+        let mut commands = KeyCommand::parse_sequence(name, general.mod_mask, window_system)
+            .with_context(binding)?;
+        let mask = modifiers(key.get("modifiers"), general.mod_mask).with_context(binding)?;
+        if mask != KeyModifiers::NONEMASK {
+            if commands.len() > 1 {
+                bail!("{}: modifiers can't be added to a chord", binding());
+            }
+            commands[0].mask |= mask;
+        }
+        let mode = key
+            .get("mode")
+            .map(|m| string(m).map(String::from))
+            .transpose()
+            .with_context(binding)?;
+        let action = string(field(key, "action")?).with_context(binding)?;
+        if let Some(target) = action.trim().strip_prefix("mode ") {
+            targets.push(target.trim().to_owned());
+        }
+        modes.extend(mode.clone());
+        keys.push((
+            mode,
+            name.to_owned(),
+            commands,
+            key_action(action).with_context(binding)?,
+        ));
+    }
+
+    // Modes are made up by the keys bound in them
+    if let Some(target) = targets
+        .iter()
+        .find(|m| !modes.contains(*m) && !config.internal.modes.contains_key(*m))
+    {
+        bail!("no keys are bound in mode {:?}", target);
+    }
+
+    for (i, button) in tables(table.get("mouse"))?.iter().enumerate() {
+        let binding = || format!("invalid mouse binding {}", i + 1);
+        let number = integer(field(button, "button")?).with_context(binding)?;
+        if !(1..=5).contains(&number) {
+            bail!("unknown mouse button {}", number);
+        }
+        let mask = modifiers(button.get("modifiers"), general.mod_mask).with_context(binding)?;
+        let action = string(field(button, "action")?).with_context(binding)?;
+        buttons.push((
+            number as MouseButton,
+            mask,
+            mouse_action(action).with_context(binding)?,
+        ));
+    }
+
+    if table.contains_key("rules") {
+        let all = tables(table.get("rules"))?
+            .iter()
+            .enumerate()
+            .map(|(i, r)| rule(r).with_context(|| format!("invalid rule {}", i + 1)))
+            .collect::<Result<Vec<_>>>()?;
+        rules = Some(all);
+    }
+
+    config.general = general;
+    for mode in modes.iter() {
+        config.add_mode(window_system, mode);
+    }
+    for (mode, name, commands, handler) in keys {
+        config.add_key_sequence(mode.as_deref(), &name, &commands, handler)?;
+    }
+    for (button, mask, handler) in buttons {
+        config.add_mouse_handler(button, mask, handler);
+    }
+    if let Some(rules) = rules {
+        config.set_manage_hook(rules::manage_hook(rules));
+    }
+
+    Ok(())
+}
+
+fn boolean(value: &Value) -> Result<bool> {
+    value
+        .as_bool()
+        .ok_or_else(|| anyhow!("expected a boolean, found {}", value.type_str()))
+}
+
+fn integer(value: &Value) -> Result<i64> {
+    match value.as_integer() {
+        Some(x) if x >= 0 => Ok(x),
+        Some(x) => bail!("expected a positive integer, found {}", x),
+        None => bail!("expected an integer, found {}", value.type_str()),
+    }
+}
+
+fn string(value: &Value) -> Result<&str> {
+    value
+        .as_str()
+        .ok_or_else(|| anyhow!("expected a string, found {}", value.type_str()))
+}
+
+fn strings(value: &Value) -> Result<Vec<String>> {
+    value
+        .as_array()
+        .ok_or_else(|| anyhow!("expected an array, found {}", value.type_str()))?
+        .iter()
+        .map(|x| string(x).map(|x| x.to_owned()))
+        .collect()
+}
+
+fn tables(value: Option<&Value>) -> Result<Vec<&Table>> {
+    match value {
+        None => Ok(Vec::new()),
+        Some(Value::Array(values)) => values
+            .iter()
+            .map(|x| {
+                x.as_table()
+                    .ok_or_else(|| anyhow!("expected a table, found {}", x.type_str()))
+            })
+            .collect(),
+        Some(x) => bail!("expected an array of tables, found {}", x.type_str()),
+    }
+}
+
+fn field<'a>(table: &'a Table, name: &str) -> Result<&'a Value> {
+    table
+        .get(name)
+        .ok_or_else(|| anyhow!("{} is missing", name))
+}
+
+/// Colours are given as integers or as `#rrggbb`
+fn colour(value: &Value) -> Result<u32> {
+    match *value {
+        Value::Integer(x) if (0..=0xffffff).contains(&x) => Ok(x as u32),
+        Value::String(ref s) if s.starts_with('#') && s.len() == 7 => {
+            Ok(u32::from_str_radix(&s[1..], 16)?)
+        }
+        _ => bail!("expected a colour like \"#rrggbb\", found {}", value),
+    }
+}
+
+/// Parse a modifier, where `mod` stands for the given mod mask
+fn modifier(name: &str, mod_mask: KeyModifiers) -> Result<KeyModifiers> {
+    Ok(match name.to_lowercase().as_str() {
+        "mod" if mod_mask != KeyModifiers::NONEMASK => mod_mask,
+        "shift" => KeyModifiers::SHIFTMASK,
+        "lock" => KeyModifiers::LOCKMASK,
+        "control" | "ctrl" => KeyModifiers::CONTROLMASK,
+        "mod1" | "alt" => KeyModifiers::MOD1MASK,
+        "mod2" => KeyModifiers::MOD2MASK,
+        "mod3" => KeyModifiers::MOD3MASK,
+        "mod4" | "super" => KeyModifiers::MOD4MASK,
+        "mod5" => KeyModifiers::MOD5MASK,
+        _ => bail!("unknown modifier {:?}", name),
+    })
+}
+
+fn modifiers(value: Option<&Value>, mod_mask: KeyModifiers) -> Result<KeyModifiers> {
+    match value {
+        Some(value) => strings(value)?
+            .iter()
+            .try_fold(KeyModifiers::NONEMASK, |mask, name| {
+                Ok(mask | modifier(name, mod_mask)?)
+            }),
+        None => Ok(KeyModifiers::NONEMASK),
+    }
+}
+
+/// Create a layout given either by name, or as a table with its
+/// name and the parameters differing from the defaults. A list of
+/// layouts is switched with `layout next` and `layout prev`.
+fn layout(value: &Value, registry: &LayoutRegistry) -> Result<Box<dyn Layout>> {
+    let (name, parameters) = match *value {
+        Value::Array(ref layouts) if !layouts.is_empty() => {
+            let layouts = layouts
+                .iter()
+                .map(|x| layout(x, registry))
+                .collect::<Result<Vec<_>>>()?;
+            return Ok(ChooseLayout::boxed_new(layouts));
+        }
+        Value::String(ref name) => (name.as_str(), Table::new()),
+        Value::Table(ref table) => {
+            let mut parameters = table.clone();
+            parameters.remove("name");
+            (string(field(table, "name")?)?, parameters)
+        }
+        _ => bail!(
+            "expected a layout name, table or list, found {}",
+            value.type_str()
+        ),
+    };
+
+    let mut layout = registry
+        .create(name)
+        .ok_or_else(|| anyhow!("unknown layout {:?}", name))?;
+    if parameters.is_empty() {
+        return Ok(layout);
+    }
+
+    let mut state = layout.serialize().unwrap_or_else(|| json!({}));
+    for (key, value) in parameters.iter() {
+        if state.get(key).is_none() {
+            bail!("layout {} has no parameter {:?}", name, key);
+        }
+        state[key] = serde_json::to_value(value)?;
+    }
+
+    if layout.deserialize(&state, registry) {
+        Ok(layout)
+    } else {
+        bail!("invalid parameters for layout {}", name)
+    }
+}
+
+/// Split a command into its arguments like a shell would, honouring
+/// single and double quotes and backslashes, but nothing else
+pub fn command_line(command: &str) -> Result<Vec<String>> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = command.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => words.extend(word.take()),
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => bail!("unterminated quote in {:?}", command),
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ '"') | Some(c @ '\\') => word.push(c),
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => bail!("unterminated quote in {:?}", command),
+                        },
+                        Some(c) => word.push(c),
+                        None => bail!("unterminated quote in {:?}", command),
+                    }
+                }
+            }
+            '\\' => match chars.next() {
+                Some(c) => word.get_or_insert_with(String::new).push(c),
+                None => bail!("trailing backslash in {:?}", command),
+            },
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+
+    words.extend(word);
+    Ok(words)
+}
+
+fn key_action(action: &str) -> Result<KeyHandler> {
+    let action = action.trim();
+    let (name, argument) = match action.find(char::is_whitespace) {
+        Some(i) => (&action[..i], action[i..].trim()),
+        None => (action, ""),
+    };
+
+    let modify: fn(&Workspaces) -> Workspaces = match name {
+        "focus-up" => |x| x.focus_up(),
+        "focus-down" => |x| x.focus_down(),
+        "swap-up" => |x| x.swap_up(),
+        "swap-down" => |x| x.swap_down(),
+        "swap-master" => |x| x.swap_master(),
+        "spawn-terminal" => return Ok(Box::new(start_terminal)),
+        "spawn-launcher" => return Ok(Box::new(start_launcher)),
+        "toggle-fullscreen" => return Ok(Box::new(toggle_fullscreen)),
+        "exit" => return Ok(Box::new(exit)),
+        "mode" => {
+            if argument.is_empty() {
+                bail!("mode needs a name");
+            }
+            let mode = argument.to_owned();
+            return Ok(Box::new(move |m, ws, _| m.enter_mode(ws.deref(), &mode)));
+        }
+        "exit-mode" => return Ok(Box::new(|m, ws, _| m.exit_mode(ws.deref()))),
+        "restart" => {
+            return Ok(Box::new(|m, ws, c| {
+                restart(m, ws, c).expect("error while restarting wtftw")
+            }))
+        }
+        "spawn" => {
+            let words = command_line(argument)?;
+            if words.is_empty() {
+                bail!("spawn needs a command");
+            }
+            return Ok(Box::new(move |m, _, _| {
+                if let Err(e) = process::Command::new(&words[0]).args(&words[1..]).spawn() {
+                    error!("unable to spawn {}: {}", words[0], e);
+                }
+                m
+            }));
+        }
+        _ => {
+            let command = Command::parse(action)?;
+            if let Command::GetTree | Command::Subscribe(_) = command {
+                bail!("{} can't be bound to a key", name);
+            }
+            let action = action.to_owned();
+            return Ok(Box::new(move |m: WindowManager, ws, c| {
+                match command.execute(m.clone(), ws, c) {
+                    Ok(m) => m,
+                    Err(e) => {
+                        error!("{}: {}", action, e);
+                        m
+                    }
+                }
+            }));
+        }
+    };
+
+    Ok(Box::new(
+        move |m: WindowManager, ws: Rc<dyn WindowSystem>, c: &GeneralConfig| {
+            m.windows(ws.deref(), c, &modify)
+        },
+    ))
+}
+
+fn mouse_action(action: &str) -> Result<MouseHandler> {
+    Ok(match action.trim() {
+        "focus" => Box::new(|m, ws, c, window| m.focus(window, ws.deref(), c)),
+        "move" => Box::new(|m, ws, c, window| {
+            m.focus(window, ws.deref(), c)
+                .mouse_move_window(ws.deref(), c, window)
+        }),
+        "resize" => Box::new(|m, ws, c, window| {
+            m.focus(window, ws.deref(), c)
+                .mouse_resize_window(ws.deref(), c, window)
+        }),
+        _ => bail!("unknown mouse action {:?}", action),
+    })
+}
+
+/// A query on a window property, given either as the exact value
+/// or as a table with `contains` or `matches`
+fn property(property: Property, value: &Value) -> Result<Query> {
+    match *value {
+        Value::String(ref s) => Ok(property.is(s)),
+        Value::Table(ref table) => match (table.get("contains"), table.get("matches")) {
+            (Some(s), None) if table.len() == 1 => Ok(property.contains(string(s)?)),
+            (None, Some(s)) if table.len() == 1 => Ok(property.matches(Regex::new(string(s)?)?)),
+            _ => bail!("expected either contains or matches"),
+        },
+        _ => bail!("expected a string or table, found {}", value.type_str()),
+    }
+}
+
+fn rule(table: &Table) -> Result<Rule> {
+    let mut query: Option<Query> = None;
+    let mut actions = Vec::new();
+
+    for (name, value) in table.iter() {
+        let condition = match name.as_str() {
+            "class" => property(Property::Class, value)?,
+            "instance" => property(Property::Instance, value)?,
+            "title" => property(Property::Title, value)?,
+            "role" => property(Property::Role, value)?,
+            "type" => rules::window_type(string(value)?.parse::<WindowType>()?),
+            "action" => {
+                actions.push(string(value)?.parse::<Action>()?);
+                continue;
+            }
+            "actions" => {
+                for action in strings(value)? {
+                    actions.push(action.parse::<Action>()?);
+                }
+                continue;
+            }
+            _ => bail!("unknown condition {:?}", name),
+        };
+        query = Some(match query {
+            Some(q) => q & condition,
+            None => condition,
+        });
+    }
+
+    if actions.is_empty() {
+        bail!("no action given");
+    }
+
+    Ok(Rule {
+        query: query.unwrap_or_else(always),
+        actions,
+    })
+}
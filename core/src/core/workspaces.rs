@@ -4,7 +4,7 @@ use crate::core::screen::Screen;
 use crate::core::stack::Stack;
 use crate::core::workspace::Workspace;
 use crate::layout::{Layout, LayoutMessage};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::iter::repeat;
 use crate::window_manager::ScreenDetail;
 use crate::window_system::{Window, WindowSystem};
@@ -18,6 +18,10 @@ pub struct Workspaces {
     pub hidden: Vec<Workspace>,
     /// A list of all floating windows
     pub floating: BTreeMap<Window, RationalRect>,
+    /// A list of all fullscreen windows
+    pub fullscreen: BTreeSet<Window>,
+    /// Border widths of windows that don't use the configured one
+    pub borders: BTreeMap<Window, u32>,
 }
 
 impl Clone for Workspaces {
@@ -27,6 +31,8 @@ impl Clone for Workspaces {
             visible: self.visible.clone(),
             hidden: self.hidden.clone(),
             floating: self.floating.clone(),
+            fullscreen: self.fullscreen.clone(),
+            borders: self.borders.clone(),
         }
     }
 }
@@ -72,6 +78,8 @@ impl Workspaces {
             visible: current.iter().skip(1).cloned().collect(),
             hidden: unseen,
             floating: BTreeMap::new(),
+            fullscreen: BTreeSet::new(),
+            borders: BTreeMap::new(),
         }
     }
 
@@ -81,6 +89,8 @@ impl Workspaces {
             visible: self.visible.clone(),
             hidden: self.hidden.clone(),
             floating: self.floating.clone(),
+            fullscreen: self.fullscreen.clone(),
+            borders: self.borders.clone(),
         }
     }
 
@@ -90,6 +100,8 @@ impl Workspaces {
             visible,
             hidden: self.hidden.clone(),
             floating: self.floating.clone(),
+            fullscreen: self.fullscreen.clone(),
+            borders: self.borders.clone(),
         }
     }
 
@@ -99,9 +111,21 @@ impl Workspaces {
             visible: self.visible.clone(),
             hidden,
             floating: self.floating.clone(),
+            fullscreen: self.fullscreen.clone(),
+            borders: self.borders.clone(),
         }
     }
 
+    /// Apply the given function to every workspace, shown or hidden
+    pub fn map_workspaces<F>(&self, f: F) -> Workspaces
+    where
+        F: Fn(Workspace) -> Workspace,
+    {
+        self.from_current(self.current.map_workspace(&f))
+            .from_visible(self.visible.iter().map(|s| s.map_workspace(&f)).collect())
+            .from_hidden(self.hidden.iter().map(|w| f(w.clone())).collect())
+    }
+
     /// Set focus to the workspace with index \'i\'.
     /// If the index is out of range, return the original 'StackSet'.
     ///
@@ -203,8 +227,47 @@ impl Workspaces {
         w
     }
 
+    /// Show the given window over its whole screen
+    pub fn fullscreen(&self, window: Window) -> Workspaces {
+        let mut w = self.clone();
+        w.fullscreen.insert(window);
+        w
+    }
+
+    /// Return the given window from fullscreen to its
+    /// previous, floating or tiled state
+    pub fn unfullscreen(&self, window: Window) -> Workspaces {
+        let mut w = self.clone();
+        w.fullscreen.remove(&window);
+        w
+    }
+
+    pub fn is_fullscreen(&self, window: Window) -> bool {
+        self.fullscreen.contains(&window)
+    }
+
+    /// Draw the given window with its own border width
+    /// instead of the configured one
+    pub fn set_border_width(&self, window: Window, width: u32) -> Workspaces {
+        let mut w = self.clone();
+        w.borders.insert(window, width);
+        w
+    }
+
+    /// The border width of the given window, falling back to the
+    /// given default. Fullscreen windows never have a border.
+    pub fn border_width(&self, window: Window, default: u32) -> u32 {
+        if self.is_fullscreen(window) {
+            0
+        } else {
+            self.borders.get(&window).copied().unwrap_or(default)
+        }
+    }
+
     pub fn delete(&self, window: Window) -> Workspaces {
-        self.delete_p(window).sink(window)
+        let mut w = self.delete_p(window).sink(window).unfullscreen(window);
+        w.borders.remove(&window);
+        w
     }
 
     pub fn delete_p(&self, window: Window) -> Workspaces {
@@ -482,6 +545,18 @@ impl Workspaces {
             .collect()
     }
 
+    /// Order all windows from bottom to top, given the window system's
+    /// stacking order with the topmost window first. Windows that were
+    /// never restacked (i.e. never shown) end up at the bottom.
+    pub fn stacking_order(&self, restacked: &[Window]) -> Vec<Window> {
+        let windows = self.all_windows();
+        let (stacked, unstacked): (Vec<Window>, Vec<Window>) =
+            windows.iter().partition(|w| restacked.contains(w));
+        let ordered = restacked.iter().rev().filter(|w| stacked.contains(w));
+
+        unstacked.iter().chain(ordered).copied().collect()
+    }
+
     /// Returns a list of all windows as tuples together with their
     /// respective workspace IDs
     pub fn all_windows_with_workspaces(&self) -> Vec<(Window, u32)> {
@@ -544,6 +619,8 @@ impl Workspaces {
             visible: self.visible.clone(),
             hidden: self.hidden.clone(),
             floating: map,
+            fullscreen: self.fullscreen.clone(),
+            borders: self.borders.clone(),
         }
     }
 }
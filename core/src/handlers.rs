@@ -8,6 +8,8 @@ use crate::window_system::Window;
 use crate::window_system::WindowSystem;
 use std::rc::Rc;
 
+pub mod rules;
+
 pub type KeyHandler =
     Box<dyn Fn(WindowManager, Rc<dyn WindowSystem>, &GeneralConfig) -> WindowManager>;
 pub type MouseHandler =
@@ -25,6 +27,7 @@ pub mod default {
     use crate::config::GeneralConfig;
     use crate::core::workspaces::Workspaces;
     use crate::handlers::libc::execvp;
+    use crate::state::{self, state_path};
     use crate::window_manager::WindowManager;
     use crate::window_system::Window;
     use crate::window_system::WindowSystem;
@@ -103,8 +106,9 @@ pub mod default {
 
     /// Restart the window manager by calling execvp and replacing the current binary
     /// with the new one in memory.
-    /// Pass a list of all windows to it via command line arguments
-    /// so it may resume work as usual.
+    /// Save the complete state to a file and pass its path via command line
+    /// arguments so it may resume work as usual. If the state can't be saved,
+    /// pass a list of all windows instead.
     pub fn restart(
         window_manager: WindowManager,
         _: Rc<dyn WindowSystem>,
@@ -112,27 +116,33 @@ pub mod default {
     ) -> Result<WindowManager> {
         // Get absolute path to binary
         let filename = env::current_dir()?.join(&env::current_exe()?);
-        // Collect all managed windows
-        let window_ids: String =
-            json!(&window_manager.workspaces.all_windows_with_workspaces()).to_string();
+        // Save the state, or at least all managed windows
+        let path = state_path();
+        let (option, argument) = match state::write(&path, &window_manager.workspaces) {
+            Ok(()) => ("--state", path.into_os_string().into_string().unwrap()),
+            Err(e) => {
+                error!("unable to save state to {}: {}", path.display(), e);
+                (
+                    "--resume",
+                    json!(&window_manager.workspaces.all_windows_with_workspaces()).to_string(),
+                )
+            }
+        };
 
-        // Create arguments
-        let resume = &"--resume";
-        let windows = window_ids;
         let filename_c = CString::new(filename.into_os_string().into_string().unwrap().as_bytes())?;
 
         for p in c.pipes.iter() {
             p.write().unwrap().wait()?;
         }
 
-        let resume_str = CString::new(resume.as_bytes())?;
-        let windows_str = CString::new(windows.as_bytes())?;
+        let option_str = CString::new(option.as_bytes())?;
+        let argument_str = CString::new(argument.as_bytes())?;
 
         unsafe {
             let slice: &mut [*const i8; 4] = &mut [
                 filename_c.as_ptr(),
-                resume_str.as_ptr(),
-                windows_str.as_ptr(),
+                option_str.as_ptr(),
+                argument_str.as_ptr(),
                 null(),
             ];
             execvp(filename_c.as_ptr(), slice.as_mut_ptr());
@@ -141,13 +151,39 @@ pub mod default {
         Ok(window_manager)
     }
 
+    /// Toggle fullscreen for the currently focused window
+    pub fn toggle_fullscreen(
+        window_manager: WindowManager,
+        window_system: Rc<dyn WindowSystem>,
+        config: &GeneralConfig,
+    ) -> WindowManager {
+        match window_manager.workspaces.peek() {
+            Some(window) => window_manager.toggle_fullscreen(window_system.deref(), config, window),
+            None => window_manager,
+        }
+    }
+
     /// Stop the window manager
     pub fn exit(w: WindowManager, _: Rc<dyn WindowSystem>, _: &GeneralConfig) -> WindowManager {
         WindowManager {
             running: false,
+            reload_requested: w.reload_requested,
             dragging: None,
             workspaces: w.workspaces,
             waiting_unmap: w.waiting_unmap,
+            mode: w.mode,
+            notice: w.notice,
+            urgent: w.urgent,
+            events: w.events,
+        }
+    }
+
+    /// Read the configuration again without restarting,
+    /// see `WindowManager::reconfigure`
+    pub fn reload(w: WindowManager, _: Rc<dyn WindowSystem>, _: &GeneralConfig) -> WindowManager {
+        WindowManager {
+            reload_requested: true,
+            ..w
         }
     }
 
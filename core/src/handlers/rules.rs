@@ -0,0 +1,313 @@
+//! Declarative window rules, compiled into a `ManageHook`.
+//!
+//! A rule consists of a query, deciding which windows it applies to,
+//! and a list of actions to perform on every matching window:
+//!
+//! ```ignore
+//! config.set_manage_hook(manage_hook(vec![
+//!     class().is("Gimp").then(Action::Float),
+//!     (class().is("Firefox") & !role().is("browser")).then(Action::CenterFloat),
+//!     title().matches(Regex::new("^Steam").unwrap()).then(Action::Shift("5".to_owned())),
+//!     window_type(WindowType::Notification).then(Action::Ignore),
+//! ]));
+//! ```
+pub use regex::Regex;
+
+use crate::core::workspaces::Workspaces;
+use crate::handlers::ManageHook;
+use crate::window_manager::WindowManager;
+use crate::window_system::{Window, WindowSystem, WindowType};
+use anyhow::{anyhow, Error};
+use std::ops::{BitAnd, BitOr, Not};
+use std::rc::Rc;
+use std::str::FromStr;
+
+/// A textual property of a window a query can inspect
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Property {
+    /// The class part of WM_CLASS
+    Class,
+    /// The instance part of WM_CLASS
+    Instance,
+    /// The window's title
+    Title,
+    /// The window's WM_WINDOW_ROLE
+    Role,
+}
+
+impl Property {
+    pub fn get(self, window_system: &dyn WindowSystem, window: Window) -> String {
+        match self {
+            Property::Class => window_system.get_class_name(window),
+            Property::Instance => window_system.get_role_name(window),
+            Property::Title => window_system.get_window_name(window),
+            Property::Role => window_system.get_window_role(window),
+        }
+    }
+
+    /// Match windows whose property is exactly the given value
+    pub fn is(self, value: &str) -> Query {
+        Query::Equals(self, value.to_owned())
+    }
+
+    /// Match windows whose property contains the given value
+    pub fn contains(self, value: &str) -> Query {
+        Query::Contains(self, value.to_owned())
+    }
+
+    /// Match windows whose property matches the given regular expression
+    pub fn matches(self, regex: Regex) -> Query {
+        Query::Matches(self, regex)
+    }
+}
+
+pub fn class() -> Property {
+    Property::Class
+}
+
+pub fn instance() -> Property {
+    Property::Instance
+}
+
+pub fn title() -> Property {
+    Property::Title
+}
+
+pub fn role() -> Property {
+    Property::Role
+}
+
+/// Match windows announcing the given type
+pub fn window_type(window_type: WindowType) -> Query {
+    Query::WindowType(window_type)
+}
+
+/// Match every window
+pub fn always() -> Query {
+    Query::Always
+}
+
+/// Decides which windows a rule applies to. Queries can be combined
+/// with `&`, `|` and `!`, or with `and` and `or`.
+#[derive(Clone, Debug)]
+pub enum Query {
+    Always,
+    Equals(Property, String),
+    Contains(Property, String),
+    Matches(Property, Regex),
+    WindowType(WindowType),
+    And(Box<Query>, Box<Query>),
+    Or(Box<Query>, Box<Query>),
+    Not(Box<Query>),
+}
+
+impl Query {
+    pub fn and(self, other: Query) -> Query {
+        Query::And(Box::new(self), Box::new(other))
+    }
+
+    pub fn or(self, other: Query) -> Query {
+        Query::Or(Box::new(self), Box::new(other))
+    }
+
+    /// Create a rule performing the given action on all matching windows
+    pub fn then(self, action: Action) -> Rule {
+        Rule {
+            query: self,
+            actions: vec![action],
+        }
+    }
+
+    /// Check whether the given window matches the query
+    pub fn test(&self, window_system: &dyn WindowSystem, window: Window) -> bool {
+        match *self {
+            Query::Always => true,
+            Query::Equals(property, ref value) => property.get(window_system, window) == *value,
+            Query::Contains(property, ref value) => {
+                property.get(window_system, window).contains(value.as_str())
+            }
+            Query::Matches(property, ref regex) => {
+                regex.is_match(&property.get(window_system, window))
+            }
+            Query::WindowType(window_type) => window_system
+                .get_window_types(window)
+                .contains(&window_type),
+            Query::And(ref a, ref b) => {
+                a.test(window_system, window) && b.test(window_system, window)
+            }
+            Query::Or(ref a, ref b) => {
+                a.test(window_system, window) || b.test(window_system, window)
+            }
+            Query::Not(ref a) => !a.test(window_system, window),
+        }
+    }
+}
+
+impl BitAnd for Query {
+    type Output = Query;
+
+    fn bitand(self, other: Query) -> Query {
+        self.and(other)
+    }
+}
+
+impl BitOr for Query {
+    type Output = Query;
+
+    fn bitor(self, other: Query) -> Query {
+        self.or(other)
+    }
+}
+
+impl Not for Query {
+    type Output = Query;
+
+    fn not(self) -> Query {
+        Query::Not(Box::new(self))
+    }
+}
+
+/// What to do with a window matching a rule
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Action {
+    /// Move the window to the workspace with the given tag
+    Shift(String),
+    /// Float the window at its requested position
+    Float,
+    /// Float the window in the centre of the current screen
+    CenterFloat,
+    /// Show the window over its whole screen
+    Fullscreen,
+    /// Don't manage the window, but show it as it is
+    Ignore,
+    /// Draw the window with the given border width
+    Border(u32),
+}
+
+impl FromStr for Action {
+    type Err = Error;
+
+    /// Parse an action from its kebab-case name, e.g. `center-float`,
+    /// `shift 2: web` or `border 0`
+    fn from_str(s: &str) -> Result<Action, Error> {
+        let s = s.trim();
+        let (name, argument) = match s.find(char::is_whitespace) {
+            Some(i) => (&s[..i], s[i..].trim()),
+            None => (s, ""),
+        };
+        let argument = || -> Result<&str, Error> {
+            if argument.is_empty() {
+                Err(anyhow!("{} needs an argument", name))
+            } else {
+                Ok(argument)
+            }
+        };
+
+        Ok(match name {
+            "shift" => Action::Shift(argument()?.to_owned()),
+            "float" => Action::Float,
+            "center-float" => Action::CenterFloat,
+            "fullscreen" => Action::Fullscreen,
+            "ignore" => Action::Ignore,
+            "border" => Action::Border(argument()?.parse()?),
+            _ => return Err(anyhow!("unknown action {:?}", s)),
+        })
+    }
+}
+
+impl Action {
+    pub fn apply(
+        &self,
+        workspaces: Workspaces,
+        window_system: &dyn WindowSystem,
+        window: Window,
+    ) -> Workspaces {
+        match *self {
+            Action::Shift(ref tag) => {
+                match workspaces.workspaces().iter().find(|w| w.tag == *tag) {
+                    Some(workspace) => {
+                        // Shifting sinks the window, so keep what
+                        // earlier actions decided about it
+                        let mut w = workspaces.shift_window(workspace.id, window);
+                        if let Some(&rect) = workspaces.floating.get(&window) {
+                            w = w.float(window, rect);
+                        }
+                        if workspaces.is_fullscreen(window) {
+                            w = w.fullscreen(window);
+                        }
+                        if let Some(&width) = workspaces.borders.get(&window) {
+                            w = w.set_border_width(window, width);
+                        }
+                        w
+                    }
+                    None => {
+                        debug!("no workspace tagged {}", tag);
+                        workspaces
+                    }
+                }
+            }
+            Action::Float => {
+                let rect = WindowManager::relative_location(
+                    window_system.get_geometry(window),
+                    workspaces.current.screen_detail,
+                );
+                workspaces.float(window, rect)
+            }
+            Action::CenterFloat => {
+                let rect = WindowManager::center_location(
+                    window_system.get_geometry(window),
+                    workspaces.current.screen_detail,
+                );
+                workspaces.float(window, rect)
+            }
+            Action::Fullscreen => workspaces.fullscreen(window),
+            Action::Ignore => {
+                window_system.show_window(window);
+                workspaces.delete(window)
+            }
+            Action::Border(width) => workspaces.set_border_width(window, width),
+        }
+    }
+}
+
+/// A query together with the actions to perform on matching windows
+#[derive(Clone, Debug)]
+pub struct Rule {
+    pub query: Query,
+    pub actions: Vec<Action>,
+}
+
+impl Rule {
+    /// Perform another action on all matching windows
+    pub fn then(mut self, action: Action) -> Rule {
+        self.actions.push(action);
+        self
+    }
+
+    pub fn apply(
+        &self,
+        workspaces: Workspaces,
+        window_system: &dyn WindowSystem,
+        window: Window,
+    ) -> Workspaces {
+        if !self.query.test(window_system, window) {
+            return workspaces;
+        }
+
+        self.actions.iter().fold(workspaces, |w, action| {
+            action.apply(w, window_system, window)
+        })
+    }
+}
+
+/// Compile the given rules into a manage hook. The actions
+/// of every matching rule are performed in order.
+pub fn manage_hook(rules: Vec<Rule>) -> ManageHook {
+    Box::new(
+        move |workspaces: Workspaces, window_system: Rc<dyn WindowSystem>, window: Window| {
+            rules.iter().fold(workspaces, |w, rule| {
+                rule.apply(w, window_system.as_ref(), window)
+            })
+        },
+    )
+}
@@ -0,0 +1,482 @@
+//! A Unix domain socket that lets scripts drive the window manager.
+//!
+//! Every connection carries a single command line, e.g. `view 2`,
+//! `move 3 0x1a00007`, `layout increase-master` or `quit`, or the same
+//! as a JSON array such as `["view","2: web"]`, and receives
+//! a single JSON reply: `{"success":true}` or
+//! `{"success":false,"error":"..."}`. The reply to `get-tree`
+//! carries the window manager's state in `tree`.
+//!
+//! After `subscribe [EVENT...]` the connection stays open and receives
+//! one JSON object per line for every event of the given kinds, or of
+//! all kinds if none are given. Subscribers that can't keep up are dropped.
+use crate::config::GeneralConfig;
+use crate::core::rational_rect::RationalRect;
+use crate::handlers::default::{exit, reload, restart};
+use crate::layout::LayoutMessage;
+use crate::window_manager::{WindowManager, WindowManagerEvent};
+use crate::window_system::{ClientMessage, Rectangle, Window, WindowSystem};
+use anyhow::{anyhow, bail, Error, Result};
+use serde_json::Value;
+use std::cell::RefCell;
+use std::env;
+use std::fs;
+use std::io::{ErrorKind, Read, Write};
+use std::ops::Deref;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::str;
+
+/// The path of the command socket. Can be overridden with `WTFTW_SOCKET`.
+pub fn socket_path() -> PathBuf {
+    if let Some(path) = env::var_os("WTFTW_SOCKET") {
+        return PathBuf::from(path);
+    }
+
+    match dirs::runtime_dir() {
+        Some(dir) => dir.join("wtftw.sock"),
+        None => env::temp_dir().join(format!("wtftw-{}.sock", unsafe { libc::getuid() })),
+    }
+}
+
+/// A command received over the socket. Windows default to
+/// the focused one, workspaces are given by index or tag.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Command {
+    View(String),
+    Move(String, Option<Window>),
+    Layout(LayoutMessage),
+    Focus(Window),
+    Float(Option<Window>),
+    Sink(Option<Window>),
+    Close(Option<Window>),
+    GetTree,
+    Subscribe(Vec<String>),
+    Reload,
+    Restart,
+    Quit,
+}
+
+impl Command {
+    /// Parse a command line, or a JSON array of its arguments
+    /// so they can contain any whitespace
+    pub fn parse(line: &str) -> Result<Command> {
+        let arguments: Vec<String> = if line.trim_start().starts_with('[') {
+            serde_json::from_str(line)?
+        } else {
+            line.split_whitespace().map(|x| x.to_owned()).collect()
+        };
+        let words: Vec<&str> = arguments.iter().map(|x| x.as_str()).collect();
+        let window = |index: usize| -> Result<Option<Window>> {
+            words.get(index).map(|x| parse_window(x)).transpose()
+        };
+        let argument = |index: usize| -> Result<&str> {
+            words
+                .get(index)
+                .copied()
+                .ok_or_else(|| anyhow!("{} needs an argument", words[0]))
+        };
+
+        if words.is_empty() {
+            bail!("empty command");
+        }
+
+        Ok(match words[0] {
+            "view" => Command::View(argument(1).map(|_| words[1..].join(" "))?),
+            "move" => {
+                argument(1)?;
+                // Tags may contain spaces, a trailing window id ends them
+                match words.last().map(|x| parse_window(x)) {
+                    Some(Ok(window)) if words.len() > 2 => {
+                        Command::Move(words[1..words.len() - 1].join(" "), Some(window))
+                    }
+                    _ => Command::Move(words[1..].join(" "), None),
+                }
+            }
+            "layout" => Command::Layout(words[1..].join(" ").parse()?),
+            "focus" => Command::Focus(parse_window(argument(1)?)?),
+            "float" => Command::Float(window(1)?),
+            "sink" => Command::Sink(window(1)?),
+            "close" => Command::Close(window(1)?),
+            "get-tree" => Command::GetTree,
+            "subscribe" => {
+                if let Some(name) = words[1..].iter().find(|x| !EVENTS.contains(x)) {
+                    bail!("unknown event {:?}", name);
+                }
+                Command::Subscribe(words[1..].iter().map(|x| (*x).to_owned()).collect())
+            }
+            "reload" => Command::Reload,
+            "restart" => Command::Restart,
+            "quit" => Command::Quit,
+            _ => bail!("unknown command {:?}", words[0]),
+        })
+    }
+
+    /// Apply the command to the window manager
+    pub fn execute(
+        &self,
+        window_manager: WindowManager,
+        window_system: Rc<dyn WindowSystem>,
+        config: &GeneralConfig,
+    ) -> Result<WindowManager> {
+        let ws = window_system.deref();
+        let target = |window: Option<Window>| -> Result<Window> {
+            match window.or_else(|| window_manager.workspaces.peek()) {
+                Some(w) if window_manager.is_window_managed(w) => Ok(w),
+                Some(w) => bail!("window {} is not managed", w),
+                None => bail!("no window is focused"),
+            }
+        };
+
+        Ok(match *self {
+            Command::View(ref workspace) => {
+                let index = find_workspace(&window_manager, workspace)?;
+                window_manager.view(ws, index, config)
+            }
+            Command::Move(ref workspace, window) => {
+                let index = find_workspace(&window_manager, workspace)?;
+                let window = target(window)?;
+                window_manager.windows(ws, config, &|w| w.shift_window(index, window))
+            }
+            Command::Layout(message) => window_manager
+                .send_layout_message(message, ws, config)
+                .windows(ws, config, &|x| x.clone()),
+            Command::Focus(window) => {
+                let window = target(Some(window))?;
+                window_manager.handle_client_message(
+                    ws,
+                    config,
+                    ClientMessage::ActivateWindow(window),
+                )
+            }
+            Command::Float(window) => {
+                let window = target(window)?;
+                window_manager.float(ws, config, window)
+            }
+            Command::Sink(window) => {
+                let window = target(window)?;
+                window_manager.windows(ws, config, &|w| w.sink(window))
+            }
+            Command::Close(window) => {
+                window_system.kill_client(target(window)?);
+                window_manager
+            }
+            Command::GetTree | Command::Subscribe(_) => window_manager,
+            Command::Reload => reload(window_manager, window_system.clone(), config),
+            Command::Restart => restart(window_manager, window_system.clone(), config)?,
+            Command::Quit => exit(window_manager, window_system.clone(), config),
+        })
+    }
+}
+
+/// Describe the screens, workspaces, layouts, stacks
+/// and floating windows of the window manager
+pub fn tree(window_manager: &WindowManager) -> Value {
+    let workspaces = &window_manager.workspaces;
+    let screens = workspaces.screens();
+    let rect = |Rectangle(x, y, width, height): Rectangle| json!({ "x": x, "y": y, "width": width, "height": height });
+
+    let mut all = workspaces.workspaces();
+    all.sort_by_key(|w| w.id);
+
+    json!({
+        "focused": workspaces.peek(),
+        "screens": screens.iter().map(|s| json!({
+            "id": s.screen_id,
+            "rect": rect(s.screen_detail),
+            "workspace": s.workspace.id,
+            "current": s.screen_id == workspaces.current.screen_id,
+        })).collect::<Vec<_>>(),
+        "workspaces": all.iter().map(|w| json!({
+            "id": w.id,
+            "tag": w.tag,
+            "layout": w.layout.description(),
+            "screen": screens.iter().find(|s| s.workspace.id == w.id).map(|s| s.screen_id),
+            "stack": w.stack.as_ref().map(|s| json!({
+                "focus": s.focus,
+                "up": s.up,
+                "down": s.down,
+            })),
+        })).collect::<Vec<_>>(),
+        "floating": workspaces.floating.iter().map(|(window, &RationalRect(x, y, width, height))| json!({
+            "window": window,
+            "rect": { "x": x, "y": y, "width": width, "height": height },
+        })).collect::<Vec<_>>(),
+        "fullscreen": workspaces.fullscreen,
+        "mode": window_manager.mode,
+    })
+}
+
+/// The kinds of events clients can subscribe to
+pub const EVENTS: [&str; 9] = [
+    "workspace",
+    "managed",
+    "unmanaged",
+    "focus",
+    "title",
+    "layout",
+    "screens",
+    "urgency",
+    "mode",
+];
+
+/// The kind of the given event, as listed in `EVENTS`
+pub fn event_name(event: &WindowManagerEvent) -> &'static str {
+    match *event {
+        WindowManagerEvent::WorkspaceSwitched(..) => "workspace",
+        WindowManagerEvent::WindowManaged(_) => "managed",
+        WindowManagerEvent::WindowUnmanaged(_) => "unmanaged",
+        WindowManagerEvent::FocusChanged(_) => "focus",
+        WindowManagerEvent::TitleChanged(..) => "title",
+        WindowManagerEvent::LayoutChanged(..) => "layout",
+        WindowManagerEvent::ScreensChanged(_) => "screens",
+        WindowManagerEvent::UrgencySet(_) => "urgency",
+        WindowManagerEvent::ModeChanged(_) => "mode",
+    }
+}
+
+pub fn event_json(event: &WindowManagerEvent) -> Value {
+    let name = event_name(event);
+    match *event {
+        WindowManagerEvent::WorkspaceSwitched(id, ref tag) => {
+            json!({ "event": name, "workspace": id, "tag": tag })
+        }
+        WindowManagerEvent::WindowManaged(window)
+        | WindowManagerEvent::WindowUnmanaged(window)
+        | WindowManagerEvent::UrgencySet(window) => json!({ "event": name, "window": window }),
+        WindowManagerEvent::FocusChanged(window) => json!({ "event": name, "window": window }),
+        WindowManagerEvent::TitleChanged(window, ref title) => {
+            json!({ "event": name, "window": window, "title": title })
+        }
+        WindowManagerEvent::LayoutChanged(id, ref layout) => {
+            json!({ "event": name, "workspace": id, "layout": layout })
+        }
+        WindowManagerEvent::ScreensChanged(ref screens) => json!({
+            "event": name,
+            "screens": screens.iter().map(|&Rectangle(x, y, width, height)| {
+                json!({ "x": x, "y": y, "width": width, "height": height })
+            }).collect::<Vec<_>>(),
+        }),
+        WindowManagerEvent::ModeChanged(ref mode) => json!({ "event": name, "mode": mode }),
+    }
+}
+
+/// Parse a window id, either decimal or hexadecimal as printed by `xprop`
+pub fn parse_window(s: &str) -> Result<Window> {
+    let window = match s.strip_prefix("0x") {
+        Some(hex) => Window::from_str_radix(hex, 16),
+        None => s.parse(),
+    };
+    window.map_err(|_| anyhow!("invalid window id {:?}", s))
+}
+
+/// Find a workspace by its index or its tag
+fn find_workspace(window_manager: &WindowManager, workspace: &str) -> Result<u32> {
+    let workspaces = window_manager.workspaces.workspaces();
+    workspace
+        .parse::<u32>()
+        .ok()
+        .filter(|&i| i < workspaces.len() as u32)
+        .or_else(|| workspaces.iter().find(|w| w.tag == workspace).map(|w| w.id))
+        .ok_or_else(|| anyhow!("unknown workspace {:?}", workspace))
+}
+
+pub struct IpcServer {
+    listener: UnixListener,
+    path: PathBuf,
+    /// Connections whose command hasn't fully arrived, with what has
+    pending: RefCell<Vec<(UnixStream, Vec<u8>)>>,
+    /// Open connections waiting for events, with the kinds they want
+    subscribers: RefCell<Vec<(UnixStream, Vec<String>)>>,
+}
+
+impl IpcServer {
+    /// Listen on the given path, replacing a stale socket. Fails
+    /// if another window manager is still listening on it. Only
+    /// the user may connect, wherever the socket is.
+    pub fn new(path: PathBuf) -> Result<IpcServer> {
+        if path.exists() {
+            if UnixStream::connect(&path).is_ok() {
+                bail!("{} is in use by another instance", path.display());
+            }
+            fs::remove_file(&path)?;
+        }
+        // Create the socket with mode 0600, whatever the umask
+        let umask = unsafe { libc::umask(0o177) };
+        let listener = UnixListener::bind(&path);
+        unsafe { libc::umask(umask) };
+        let listener = listener?;
+        listener.set_nonblocking(true)?;
+        debug!("listening for commands on {}", path.display());
+
+        Ok(IpcServer {
+            listener,
+            path,
+            pending: RefCell::new(Vec::new()),
+            subscribers: RefCell::new(Vec::new()),
+        })
+    }
+
+    /// Block until a client connects or sends more of its command, or
+    /// the given file descriptor becomes readable. Without one, wake
+    /// up regularly instead.
+    pub fn wait(&self, fd: Option<RawFd>) {
+        let mut fds = vec![self.listener.as_raw_fd()];
+        fds.extend(self.pending.borrow().iter().map(|x| x.0.as_raw_fd()));
+        fds.extend(fd);
+        let mut fds: Vec<libc::pollfd> = fds
+            .into_iter()
+            .map(|fd| libc::pollfd {
+                fd,
+                events: libc::POLLIN,
+                revents: 0,
+            })
+            .collect();
+        let timeout = if fd.is_some() { -1 } else { 100 };
+
+        unsafe {
+            libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, timeout);
+        }
+    }
+
+    /// Accept new connections and read what clients sent without
+    /// blocking. Returns the connections whose command is complete.
+    fn receive(&self) -> Vec<(UnixStream, Result<Command>)> {
+        let mut pending = self.pending.borrow_mut();
+
+        loop {
+            match self.listener.accept() {
+                Ok((stream, _)) => match stream.set_nonblocking(true) {
+                    Ok(()) => pending.push((stream, Vec::new())),
+                    Err(e) => error!("unable to read from connection: {}", e),
+                },
+                Err(ref e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    error!("unable to accept connection: {}", e);
+                    break;
+                }
+            }
+        }
+
+        let mut received = Vec::new();
+        for (stream, mut buffer) in pending.drain(..).collect::<Vec<_>>() {
+            match read_command(&stream, &mut buffer) {
+                Some(command) => received.push((stream, command)),
+                None => pending.push((stream, buffer)),
+            }
+        }
+        received
+    }
+
+    /// Execute the commands of all connections that sent one
+    pub fn process(
+        &self,
+        window_manager: WindowManager,
+        window_system: Rc<dyn WindowSystem>,
+        config: &GeneralConfig,
+    ) -> WindowManager {
+        let mut window_manager = window_manager;
+
+        for (stream, command) in self.receive() {
+            // Replies are written as before, waiting for the client
+            if let Err(e) = stream.set_nonblocking(false) {
+                debug!("unable to reply: {}", e);
+                continue;
+            }
+            let command = match command {
+                Ok(command) => command,
+                Err(e) => {
+                    reply(&stream, &failure(e));
+                    continue;
+                }
+            };
+            debug!("received command {:?}", command);
+
+            // A successful restart never returns, so reply beforehand
+            if command == Command::Restart {
+                reply(&stream, &json!({ "success": true }));
+            }
+
+            match command.execute(window_manager.clone(), window_system.clone(), config) {
+                Ok(w) => {
+                    window_manager = w;
+                    match command {
+                        Command::Restart => (),
+                        Command::GetTree => reply(
+                            &stream,
+                            &json!({ "success": true, "tree": tree(&window_manager) }),
+                        ),
+                        Command::Subscribe(ref events) => {
+                            reply(&stream, &json!({ "success": true }));
+                            match stream.set_nonblocking(true) {
+                                Ok(()) => {
+                                    self.subscribers.borrow_mut().push((stream, events.clone()))
+                                }
+                                Err(e) => debug!("unable to subscribe: {}", e),
+                            }
+                        }
+                        _ => reply(&stream, &json!({ "success": true })),
+                    }
+                }
+                Err(e) => reply(&stream, &failure(e)),
+            }
+        }
+
+        window_manager
+    }
+
+    /// Send the given events to all interested subscribers
+    pub fn broadcast(&self, events: &[WindowManagerEvent]) {
+        if events.is_empty() {
+            return;
+        }
+
+        self.subscribers.borrow_mut().retain(|(stream, filter)| {
+            let mut stream = stream;
+            events
+                .iter()
+                .filter(|e| filter.is_empty() || filter.iter().any(|x| x == event_name(e)))
+                .all(|e| writeln!(stream, "{}", event_json(e)).is_ok())
+        });
+    }
+}
+
+impl Drop for IpcServer {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Add what the client sent since the last call to `buffer`. Returns
+/// `None` until the command line is complete or the client stops sending.
+fn read_command(mut stream: &UnixStream, buffer: &mut Vec<u8>) -> Option<Result<Command>> {
+    let mut chunk = [0; 1024];
+    while !buffer.contains(&b'\n') {
+        match stream.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(n) => buffer.extend_from_slice(&chunk[..n]),
+            Err(ref e) if e.kind() == ErrorKind::WouldBlock => return None,
+            Err(ref e) if e.kind() == ErrorKind::Interrupted => (),
+            Err(e) => return Some(Err(e.into())),
+        }
+    }
+
+    let line = buffer.split(|&x| x == b'\n').next().unwrap_or(&[]);
+    Some(
+        str::from_utf8(line)
+            .map_err(Error::from)
+            .and_then(Command::parse),
+    )
+}
+
+fn failure(error: Error) -> Value {
+    json!({ "success": false, "error": error.to_string() })
+}
+
+fn reply(mut stream: &UnixStream, value: &Value) {
+    if let Err(e) = writeln!(stream, "{}", value) {
+        debug!("unable to reply: {}", e);
+    }
+}
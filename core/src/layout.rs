@@ -5,8 +5,12 @@ use crate::window_manager::ScreenDetail;
 use crate::window_system::Rectangle;
 use crate::window_system::Window;
 use crate::window_system::WindowSystem;
+use anyhow::{anyhow, Error};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::str::FromStr;
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum LayoutMessage {
     Increase,
     Decrease,
@@ -27,6 +31,44 @@ pub enum LayoutMessage {
     TreeShrinkFrom(Direction),
 }
 
+impl FromStr for LayoutMessage {
+    type Err = Error;
+
+    /// Parse a message from its kebab-case name, e.g. `increase-master`
+    /// or `tree-expand-towards left`
+    fn from_str(s: &str) -> Result<LayoutMessage, Error> {
+        let mut words = s.split_whitespace();
+        let name = words.next().unwrap_or_default();
+        let mut direction = || -> Result<Direction, Error> {
+            words
+                .next()
+                .ok_or_else(|| anyhow!("{} needs a direction", name))?
+                .parse()
+        };
+
+        Ok(match name {
+            "increase" => LayoutMessage::Increase,
+            "decrease" => LayoutMessage::Decrease,
+            "increase-master" => LayoutMessage::IncreaseMaster,
+            "decrease-master" => LayoutMessage::DecreaseMaster,
+            "increase-slave" => LayoutMessage::IncreaseSlave,
+            "decrease-slave" => LayoutMessage::DecreaseSlave,
+            "increase-gap" => LayoutMessage::IncreaseGap,
+            "decrease-gap" => LayoutMessage::DecreaseGap,
+            "next" => LayoutMessage::Next,
+            "prev" => LayoutMessage::Prev,
+            "horizontal-split" => LayoutMessage::HorizontalSplit,
+            "vertical-split" => LayoutMessage::VerticalSplit,
+            "hide" => LayoutMessage::Hide,
+            "tree-rotate" => LayoutMessage::TreeRotate,
+            "tree-swap" => LayoutMessage::TreeSwap,
+            "tree-expand-towards" => LayoutMessage::TreeExpandTowards(direction()?),
+            "tree-shrink-from" => LayoutMessage::TreeShrinkFrom(direction()?),
+            _ => return Err(anyhow!("unknown layout message {:?}", s)),
+        })
+    }
+}
+
 pub fn mirror_rect(&Rectangle(x, y, w, h): &Rectangle) -> Rectangle {
     Rectangle(y, x, h, w)
 }
@@ -56,6 +98,46 @@ pub fn split_vertically(num: u32, screen: ScreenDetail) -> Vec<Rectangle> {
         .collect()
 }
 
+pub fn split_horizontally(num: u32, screen: ScreenDetail) -> Vec<Rectangle> {
+    split_vertically(num, mirror_rect(&screen))
+        .iter()
+        .map(mirror_rect)
+        .collect()
+}
+
+/// Arrange `num_windows` cells in columns, as many as make the cells
+/// closest to `aspect` (their width over their height), changed by
+/// `column_bias`. Columns on the right take the leftover windows.
+pub fn grid(
+    aspect: f32,
+    column_bias: i32,
+    screen: ScreenDetail,
+    num_windows: u32,
+) -> Vec<Rectangle> {
+    if num_windows == 0 {
+        return Vec::new();
+    }
+
+    let Rectangle(_, _, sw, sh) = screen;
+    let ideal = (num_windows as f32 * sw as f32 / (sh.max(1) as f32 * aspect))
+        .sqrt()
+        .round();
+    let num_columns = (ideal as i32 + column_bias).max(1).min(num_windows as i32) as u32;
+
+    split_horizontally(num_columns, screen)
+        .iter()
+        .enumerate()
+        .flat_map(|(i, &column)| {
+            let extra = if (i as u32) >= num_columns - num_windows % num_columns {
+                1
+            } else {
+                0
+            };
+            split_vertically(num_windows / num_columns + extra, column)
+        })
+        .collect()
+}
+
 pub fn split_horizontally_by(ratio: f32, screen: ScreenDetail) -> (Rectangle, Rectangle) {
     let Rectangle(sx, sy, sw, sh) = screen;
     let leftw = (sw as f32 * ratio).floor() as u32;
@@ -66,6 +148,172 @@ pub fn split_horizontally_by(ratio: f32, screen: ScreenDetail) -> (Rectangle, Re
     )
 }
 
+/// Split the screen into rows with heights proportional to the given weights
+pub fn split_vertically_by_weights(weights: &[f32], screen: ScreenDetail) -> Vec<Rectangle> {
+    let Rectangle(sx, sy, sw, sh) = screen;
+    let total: f32 = weights.iter().sum();
+    let mut sum = 0.0;
+    let mut top = 0;
+
+    weights
+        .iter()
+        .enumerate()
+        .map(|(i, weight)| {
+            sum += weight;
+            let bottom = if i + 1 == weights.len() {
+                sh
+            } else {
+                ((sh as f32 * sum / total).round() as u32).clamp(top, sh)
+            };
+            let row = Rectangle(sx, sy + top as i32, sw, bottom - top);
+            top = bottom;
+            row
+        })
+        .collect()
+}
+
+/// Like `tile`, but with the heights of the windows
+/// outside the master column following their weights
+pub fn resizable_tile(
+    ratio: f32,
+    screen: ScreenDetail,
+    num_master: u32,
+    num_windows: u32,
+    weights: &[f32],
+) -> Vec<Rectangle> {
+    let num_slaves = num_windows.saturating_sub(num_master) as usize;
+    let slave_weights: Vec<f32> = (0..num_slaves)
+        .map(|i| weights.get(i).copied().unwrap_or(1.0))
+        .collect();
+
+    if num_slaves == 0 {
+        split_vertically(num_windows, screen)
+    } else if num_master == 0 {
+        split_vertically_by_weights(&slave_weights, screen)
+    } else {
+        let (r1, r2) = split_horizontally_by(ratio, screen);
+        split_vertically(num_master, r1)
+            .into_iter()
+            .chain(split_vertically_by_weights(&slave_weights, r2))
+            .collect()
+    }
+}
+
+/// Put `num_master` windows in a column taking `ratio` of the screen's
+/// width and the others in two columns beside it, or around it if
+/// `middle` is set. The others fill the column next to the master one
+/// first, or alternate between both if `alternate` is set. With a single
+/// other window there are only two columns, like in `tile`.
+pub fn three_columns(
+    ratio: f32,
+    num_master: u32,
+    middle: bool,
+    alternate: bool,
+    screen: ScreenDetail,
+    num_windows: u32,
+) -> Vec<Rectangle> {
+    if num_windows <= num_master + 1 || num_master == 0 {
+        return tile(ratio, screen, num_master, num_windows);
+    }
+
+    let Rectangle(sx, sy, sw, sh) = screen;
+    let master_width = ((sw as f32 * ratio).floor() as u32).min(sw);
+    let left_width = (sw - master_width) / 2;
+    let right_width = sw - master_width - left_width;
+    let (master, first, second) = if middle {
+        (
+            Rectangle(sx + left_width as i32, sy, master_width, sh),
+            Rectangle(sx + (left_width + master_width) as i32, sy, right_width, sh),
+            Rectangle(sx, sy, left_width, sh),
+        )
+    } else {
+        (
+            Rectangle(sx, sy, master_width, sh),
+            Rectangle(sx + master_width as i32, sy, left_width, sh),
+            Rectangle(sx + (master_width + left_width) as i32, sy, right_width, sh),
+        )
+    };
+
+    let num_slaves = num_windows - num_master;
+    let firsts = split_vertically(num_slaves.div_ceil(2), first);
+    let seconds = split_vertically(num_slaves / 2, second);
+    let slaves: Vec<Rectangle> = if alternate {
+        (0..num_slaves as usize)
+            .map(|i| {
+                if i % 2 == 0 {
+                    firsts[i / 2]
+                } else {
+                    seconds[i / 2]
+                }
+            })
+            .collect()
+    } else {
+        firsts.into_iter().chain(seconds).collect()
+    };
+
+    split_vertically(num_master, master)
+        .into_iter()
+        .chain(slaves)
+        .collect()
+}
+
+/// Parts of fibonacci layouts are never made smaller than this
+const FIBONACCI_MIN_SIZE: u32 = 32;
+
+/// Give each window but the last `ratio` of the space left by the ones
+/// before, splitting it alternately into left and right, and top and
+/// bottom. A `spiral` goes round clockwise, taking the right and bottom
+/// parts every other turn, instead of dwindling towards the bottom right.
+/// Windows left once the space gets too small share it side by side.
+pub fn fibonacci(
+    ratio: f32,
+    spiral: bool,
+    screen: ScreenDetail,
+    num_windows: u32,
+) -> Vec<Rectangle> {
+    let mut rects = Vec::new();
+    let mut rest = screen;
+
+    for i in 1..num_windows {
+        let turn = if spiral { (i - 1) % 4 } else { (i - 1) % 2 };
+        let (window, remaining) = match turn {
+            0 => split_horizontally_by(ratio, rest),
+            1 => {
+                let (top, bottom) = split_horizontally_by(ratio, mirror_rect(&rest));
+                (mirror_rect(&top), mirror_rect(&bottom))
+            }
+            2 => {
+                let (left, right) = split_horizontally_by(1.0 - ratio, rest);
+                (right, left)
+            }
+            _ => {
+                let (top, bottom) = split_horizontally_by(1.0 - ratio, mirror_rect(&rest));
+                (mirror_rect(&bottom), mirror_rect(&top))
+            }
+        };
+
+        let too_small = |&Rectangle(_, _, w, h): &Rectangle| w.min(h) < FIBONACCI_MIN_SIZE;
+        if too_small(&window) || too_small(&remaining) {
+            let Rectangle(_, _, w, h) = rest;
+            let shared = if w > h {
+                split_horizontally(num_windows - i + 1, rest)
+            } else {
+                split_vertically(num_windows - i + 1, rest)
+            };
+            rects.extend(shared);
+            return rects;
+        }
+
+        rects.push(window);
+        rest = remaining;
+    }
+
+    if num_windows > 0 {
+        rects.push(rest);
+    }
+    rects
+}
+
 pub trait Layout {
     fn apply_layout(
         &mut self,
@@ -84,10 +332,98 @@ pub trait Layout {
         true
     }
     fn description(&self) -> String;
+    /// The name the layout is registered under in a `LayoutRegistry`.
+    /// Only layouts describing themselves by their state differ from
+    /// their description.
+    fn name(&self) -> String {
+        self.description()
+    }
     fn copy(&self) -> Box<dyn Layout> {
         panic!("")
     }
     fn unhook(&self, _: &dyn WindowSystem, _: &Option<Stack<Window>>, _: &GeneralConfig) {}
+    /// Save the layout's parameters, or `None` if it has none
+    fn serialize(&self) -> Option<Value> {
+        None
+    }
+    /// Restore parameters saved by `serialize`, returning whether they
+    /// were understood. Layouts wrapping others can rebuild them with
+    /// the given registry.
+    fn deserialize(&mut self, _: &Value, _: &LayoutRegistry) -> bool {
+        false
+    }
+}
+
+/// Describe the given layout and its parameters as JSON
+pub fn save_layout(layout: &dyn Layout) -> Value {
+    json!({
+        "description": layout.name(),
+        "state": layout.serialize(),
+    })
+}
+
+/// Creates a layout with its default parameters
+pub type LayoutConstructor = Box<dyn Fn() -> Box<dyn Layout>>;
+
+/// Maps layout descriptions to constructors, so layouts
+/// saved by `save_layout` can be rebuilt
+pub struct LayoutRegistry {
+    constructors: BTreeMap<String, LayoutConstructor>,
+}
+
+impl LayoutRegistry {
+    /// Create a registry knowing the built-in layouts
+    pub fn new() -> LayoutRegistry {
+        let mut registry = LayoutRegistry {
+            constructors: BTreeMap::new(),
+        };
+        registry.register("Tall", Box::new(TallLayout::boxed_new));
+        registry.register("Grid", Box::new(GridLayout::boxed_new));
+        registry.register("Spiral", Box::new(SpiralLayout::boxed_new));
+        registry.register("Dwindle", Box::new(DwindleLayout::boxed_new));
+        registry.register("ThreeColumn", Box::new(ThreeColumnLayout::boxed_new));
+        registry.register("CenteredMaster", Box::new(CenteredMasterLayout::boxed_new));
+        registry.register("ResizableTall", Box::new(ResizableTallLayout::boxed_new));
+        registry.register(
+            "Choose",
+            Box::new(|| ChooseLayout::boxed_new(vec![TallLayout::boxed_new()])),
+        );
+        registry
+    }
+
+    pub fn register(&mut self, description: &str, constructor: LayoutConstructor) {
+        self.constructors
+            .insert(description.to_owned(), constructor);
+    }
+
+    pub fn contains(&self, description: &str) -> bool {
+        self.constructors.contains_key(description)
+    }
+
+    /// Create the layout with the given description and default parameters
+    pub fn create(&self, description: &str) -> Option<Box<dyn Layout>> {
+        self.constructors
+            .get(description)
+            .map(|constructor| constructor())
+    }
+
+    /// Rebuild a layout saved by `save_layout`. Returns `None` if the
+    /// layout is unknown or doesn't understand the saved parameters.
+    pub fn build(&self, value: &Value) -> Option<Box<dyn Layout>> {
+        let mut layout = self.create(value["description"].as_str()?)?;
+
+        if value["state"].is_null() || layout.deserialize(&value["state"], self) {
+            Some(layout)
+        } else {
+            None
+        }
+    }
+}
+
+impl Default for LayoutRegistry {
+    fn default() -> LayoutRegistry {
+        LayoutRegistry::new()
+    }
 }
 
 #[derive(Clone, Copy)]
@@ -165,10 +501,777 @@ impl Layout for TallLayout {
     fn copy(&self) -> Box<dyn Layout> {
         Box::new(*self)
     }
+
+    fn serialize(&self) -> Option<Value> {
+        Some(json!({
+            "num_master": self.num_master,
+            "increment_ratio": self.increment_ratio,
+            "ratio": self.ratio,
+        }))
+    }
+
+    fn deserialize(&mut self, state: &Value, _: &LayoutRegistry) -> bool {
+        match (
+            state["num_master"].as_u64(),
+            state["increment_ratio"].as_f64(),
+            state["ratio"].as_f64(),
+        ) {
+            (Some(num_master), Some(increment_ratio), Some(ratio))
+                if ratio > 0.0 && ratio < 1.0 =>
+            {
+                self.num_master = num_master as u32;
+                self.increment_ratio = increment_ratio as f32;
+                self.ratio = ratio as f32;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Arranges the windows in a grid of cells close to a given shape,
+/// optionally beside a master window
+#[derive(Clone, Copy)]
+pub struct GridLayout {
+    /// The preferred width of cells over their height
+    pub aspect: f32,
+    /// Columns added to, or taken from, the ones the aspect asks for.
+    /// Changed by `Increase` and `Decrease`. Laying out never uses
+    /// less than one column or more than one per window, but keeps
+    /// the bias for when the number of windows changes.
+    pub column_bias: i32,
+    /// Whether the first window gets a column of its own
+    pub master: bool,
+    /// The share of the screen's width taken by the master window
+    pub ratio: f32,
+}
+
+impl GridLayout {
+    pub fn boxed_new() -> Box<dyn Layout> {
+        Box::new(GridLayout {
+            aspect: 1.0,
+            column_bias: 0,
+            master: false,
+            ratio: 0.5,
+        })
+    }
+}
+
+impl Layout for GridLayout {
+    fn apply_layout(
+        &mut self,
+        _: &dyn WindowSystem,
+        screen: Rectangle,
+        _: &GeneralConfig,
+        stack: &Option<Stack<Window>>,
+    ) -> Vec<(Window, Rectangle)> {
+        match *stack {
+            Some(ref s) => {
+                let ws = s.integrate();
+                let (master, area, num_cells) = if self.master && ws.len() > 1 {
+                    let (master, rest) = split_horizontally_by(self.ratio, screen);
+                    (Some(master), rest, ws.len() as u32 - 1)
+                } else {
+                    (None, screen, ws.len() as u32)
+                };
+
+                let cells = grid(self.aspect, self.column_bias, area, num_cells);
+                ws.into_iter()
+                    .zip(master.into_iter().chain(cells))
+                    .collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    fn apply_message(
+        &mut self,
+        message: LayoutMessage,
+        _: &dyn WindowSystem,
+        _: &Option<Stack<Window>>,
+        _: &GeneralConfig,
+    ) -> bool {
+        match message {
+            LayoutMessage::Increase => {
+                self.column_bias += 1;
+                true
+            }
+            LayoutMessage::Decrease => {
+                self.column_bias -= 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn description(&self) -> String {
+        "Grid".to_owned()
+    }
+
+    fn copy(&self) -> Box<dyn Layout> {
+        Box::new(*self)
+    }
+
+    fn serialize(&self) -> Option<Value> {
+        Some(json!({
+            "aspect": self.aspect,
+            "column_bias": self.column_bias,
+            "master": self.master,
+            "ratio": self.ratio,
+        }))
+    }
+
+    fn deserialize(&mut self, state: &Value, _: &LayoutRegistry) -> bool {
+        match (
+            state["aspect"].as_f64(),
+            state["column_bias"].as_i64(),
+            state["master"].as_bool(),
+            state["ratio"].as_f64(),
+        ) {
+            (Some(aspect), Some(column_bias), Some(master), Some(ratio))
+                if aspect > 0.0 && ratio > 0.0 && ratio < 1.0 =>
+            {
+                self.aspect = aspect as f32;
+                self.column_bias = column_bias as i32;
+                self.master = master;
+                self.ratio = ratio as f32;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Gives each window a share of the space left by the ones before,
+/// going round clockwise like dwm's fibonacci spiral, or dwindling
+/// towards the bottom right corner. Shared by `SpiralLayout` and
+/// `DwindleLayout`.
+#[derive(Clone, Copy)]
+pub struct FibonacciLayout {
+    /// Whether to go round, or down to the right
+    pub spiral: bool,
+    /// The share of the remaining space each window takes
+    pub ratio: f32,
+    pub increment_ratio: f32,
+}
+
+impl Layout for FibonacciLayout {
+    fn apply_layout(
+        &mut self,
+        _: &dyn WindowSystem,
+        screen: Rectangle,
+        _: &GeneralConfig,
+        stack: &Option<Stack<Window>>,
+    ) -> Vec<(Window, Rectangle)> {
+        match *stack {
+            Some(ref s) => {
+                let ws = s.integrate();
+                let rects = fibonacci(self.ratio, self.spiral, screen, ws.len() as u32);
+                ws.into_iter().zip(rects).collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    fn apply_message(
+        &mut self,
+        message: LayoutMessage,
+        _: &dyn WindowSystem,
+        _: &Option<Stack<Window>>,
+        _: &GeneralConfig,
+    ) -> bool {
+        resize_ratio(&mut self.ratio, self.increment_ratio, message)
+    }
+
+    fn description(&self) -> String {
+        if self.spiral {
+            "Spiral".to_owned()
+        } else {
+            "Dwindle".to_owned()
+        }
+    }
+
+    fn copy(&self) -> Box<dyn Layout> {
+        Box::new(*self)
+    }
+
+    fn serialize(&self) -> Option<Value> {
+        Some(json!({
+            "ratio": self.ratio,
+            "increment_ratio": self.increment_ratio,
+        }))
+    }
+
+    fn deserialize(&mut self, state: &Value, _: &LayoutRegistry) -> bool {
+        match (state["ratio"].as_f64(), state["increment_ratio"].as_f64()) {
+            (Some(ratio), Some(increment_ratio)) if ratio > 0.0 && ratio < 1.0 => {
+                self.ratio = ratio as f32;
+                self.increment_ratio = increment_ratio as f32;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Declares a layout going through the windows like a `FibonacciLayout`
+/// in one given direction
+macro_rules! fibonacci_layout {
+    ($(#[$attr:meta])* $name:ident, $spiral:expr) => {
+        $(#[$attr])*
+        #[derive(Clone, Copy)]
+        pub struct $name(pub FibonacciLayout);
+
+        impl $name {
+            pub fn new() -> $name {
+                $name(FibonacciLayout {
+                    spiral: $spiral,
+                    ratio: 0.5,
+                    increment_ratio: 0.05,
+                })
+            }
+
+            pub fn boxed_new() -> Box<dyn Layout> {
+                Box::new($name::new())
+            }
+        }
+
+        impl Default for $name {
+            fn default() -> $name {
+                $name::new()
+            }
+        }
+
+        impl Layout for $name {
+            fn apply_layout(
+                &mut self,
+                window_system: &dyn WindowSystem,
+                screen: Rectangle,
+                config: &GeneralConfig,
+                stack: &Option<Stack<Window>>,
+            ) -> Vec<(Window, Rectangle)> {
+                self.0.apply_layout(window_system, screen, config, stack)
+            }
+
+            fn apply_message(
+                &mut self,
+                message: LayoutMessage,
+                window_system: &dyn WindowSystem,
+                stack: &Option<Stack<Window>>,
+                config: &GeneralConfig,
+            ) -> bool {
+                self.0.apply_message(message, window_system, stack, config)
+            }
+
+            fn description(&self) -> String {
+                self.0.description()
+            }
+
+            fn copy(&self) -> Box<dyn Layout> {
+                Box::new(*self)
+            }
+
+            fn serialize(&self) -> Option<Value> {
+                self.0.serialize()
+            }
+
+            fn deserialize(&mut self, state: &Value, layouts: &LayoutRegistry) -> bool {
+                self.0.deserialize(state, layouts)
+            }
+        }
+    };
+}
+
+fibonacci_layout!(
+    /// Gives each window a share of the space left by the ones before,
+    /// going round clockwise like dwm's fibonacci spiral
+    SpiralLayout,
+    true
+);
+
+fibonacci_layout!(
+    /// Gives each window a share of the space left by the ones before,
+    /// dwindling towards the bottom right corner
+    DwindleLayout,
+    false
+);
+
+/// Puts the master windows in one column, and the others in two
+/// columns beside it, or on both sides of it for wide screens
+#[derive(Clone, Copy)]
+pub struct ThreeColumnLayout {
+    pub num_master: u32,
+    pub increment_ratio: f32,
+    /// The share of the screen's width taken by the master column
+    pub ratio: f32,
+    /// Whether the master column is in the middle, or on the left
+    pub middle: bool,
+}
+
+impl ThreeColumnLayout {
+    pub fn boxed_new() -> Box<dyn Layout> {
+        Box::new(ThreeColumnLayout {
+            num_master: 1,
+            increment_ratio: 0.05,
+            ratio: 0.5,
+            middle: true,
+        })
+    }
+}
+
+impl Layout for ThreeColumnLayout {
+    fn apply_layout(
+        &mut self,
+        _: &dyn WindowSystem,
+        screen: Rectangle,
+        _: &GeneralConfig,
+        stack: &Option<Stack<Window>>,
+    ) -> Vec<(Window, Rectangle)> {
+        match *stack {
+            Some(ref s) => {
+                let ws = s.integrate();
+                let rects = three_columns(
+                    self.ratio,
+                    self.num_master,
+                    self.middle,
+                    false,
+                    screen,
+                    ws.len() as u32,
+                );
+                ws.into_iter().zip(rects).collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    fn apply_message(
+        &mut self,
+        message: LayoutMessage,
+        _: &dyn WindowSystem,
+        _: &Option<Stack<Window>>,
+        _: &GeneralConfig,
+    ) -> bool {
+        resize_master(
+            &mut self.num_master,
+            &mut self.ratio,
+            self.increment_ratio,
+            message,
+        )
+    }
+
+    fn description(&self) -> String {
+        "ThreeColumn".to_owned()
+    }
+
+    fn copy(&self) -> Box<dyn Layout> {
+        Box::new(*self)
+    }
+
+    fn serialize(&self) -> Option<Value> {
+        Some(json!({
+            "num_master": self.num_master,
+            "increment_ratio": self.increment_ratio,
+            "ratio": self.ratio,
+            "middle": self.middle,
+        }))
+    }
+
+    fn deserialize(&mut self, state: &Value, _: &LayoutRegistry) -> bool {
+        match (
+            state["num_master"].as_u64(),
+            state["increment_ratio"].as_f64(),
+            state["ratio"].as_f64(),
+            state["middle"].as_bool(),
+        ) {
+            (Some(num_master), Some(increment_ratio), Some(ratio), Some(middle))
+                if ratio > 0.0 && ratio < 1.0 =>
+            {
+                self.num_master = num_master as u32;
+                self.increment_ratio = increment_ratio as f32;
+                self.ratio = ratio as f32;
+                self.middle = middle;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Puts the master windows in the middle, and the others
+/// alternately on its right and left, like dwm's centeredmaster
+#[derive(Clone, Copy)]
+pub struct CenteredMasterLayout {
+    pub num_master: u32,
+    pub increment_ratio: f32,
+    /// The share of the screen's width taken by the master column
+    pub ratio: f32,
+}
+
+impl CenteredMasterLayout {
+    pub fn boxed_new() -> Box<dyn Layout> {
+        Box::new(CenteredMasterLayout {
+            num_master: 1,
+            increment_ratio: 0.05,
+            ratio: 0.5,
+        })
+    }
+}
+
+impl Layout for CenteredMasterLayout {
+    fn apply_layout(
+        &mut self,
+        _: &dyn WindowSystem,
+        screen: Rectangle,
+        _: &GeneralConfig,
+        stack: &Option<Stack<Window>>,
+    ) -> Vec<(Window, Rectangle)> {
+        match *stack {
+            Some(ref s) => {
+                let ws = s.integrate();
+                let rects = three_columns(
+                    self.ratio,
+                    self.num_master,
+                    true,
+                    true,
+                    screen,
+                    ws.len() as u32,
+                );
+                ws.into_iter().zip(rects).collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    fn apply_message(
+        &mut self,
+        message: LayoutMessage,
+        _: &dyn WindowSystem,
+        _: &Option<Stack<Window>>,
+        _: &GeneralConfig,
+    ) -> bool {
+        resize_master(
+            &mut self.num_master,
+            &mut self.ratio,
+            self.increment_ratio,
+            message,
+        )
+    }
+
+    fn description(&self) -> String {
+        "CenteredMaster".to_owned()
+    }
+
+    fn copy(&self) -> Box<dyn Layout> {
+        Box::new(*self)
+    }
+
+    fn serialize(&self) -> Option<Value> {
+        Some(json!({
+            "num_master": self.num_master,
+            "increment_ratio": self.increment_ratio,
+            "ratio": self.ratio,
+        }))
+    }
+
+    fn deserialize(&mut self, state: &Value, _: &LayoutRegistry) -> bool {
+        match (
+            state["num_master"].as_u64(),
+            state["increment_ratio"].as_f64(),
+            state["ratio"].as_f64(),
+        ) {
+            (Some(num_master), Some(increment_ratio), Some(ratio))
+                if ratio > 0.0 && ratio < 1.0 =>
+            {
+                self.num_master = num_master as u32;
+                self.increment_ratio = increment_ratio as f32;
+                self.ratio = ratio as f32;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// The smallest weight of windows in a `ResizableTallLayout`
+const MIN_WEIGHT: f32 = 0.1;
+
+/// Like `TallLayout`, but the windows outside the master column can
+/// be made taller or shorter than the others with `IncreaseSlave` and
+/// `DecreaseSlave`, which resize the focused one
+#[derive(Clone)]
+pub struct ResizableTallLayout {
+    pub num_master: u32,
+    pub increment_ratio: f32,
+    pub ratio: f32,
+    /// The height of the windows outside the master column relative to
+    /// each other, by their position. Missing weights count as 1.
+    pub weights: Vec<f32>,
+    /// How much the weight of a window changes with each message
+    pub increment_weight: f32,
+}
+
+impl ResizableTallLayout {
+    pub fn boxed_new() -> Box<dyn Layout> {
+        Box::new(ResizableTallLayout {
+            num_master: 1,
+            increment_ratio: 0.05,
+            ratio: 0.5,
+            weights: Vec::new(),
+            increment_weight: 0.25,
+        })
+    }
+
+    /// Change the weight of the focused window, if it
+    /// is outside the master column
+    fn resize_slave(&mut self, stack: &Option<Stack<Window>>, increment: f32) -> bool {
+        let position = match *stack {
+            Some(ref s) if s.up.len() >= self.num_master as usize => {
+                s.up.len() - self.num_master as usize
+            }
+            _ => return false,
+        };
+
+        if self.weights.len() <= position {
+            self.weights.resize(position + 1, 1.0);
+        }
+        self.weights[position] = (self.weights[position] + increment).max(MIN_WEIGHT);
+        true
+    }
+}
+
+impl Layout for ResizableTallLayout {
+    fn apply_layout(
+        &mut self,
+        _: &dyn WindowSystem,
+        screen: Rectangle,
+        _: &GeneralConfig,
+        stack: &Option<Stack<Window>>,
+    ) -> Vec<(Window, Rectangle)> {
+        match *stack {
+            Some(ref s) => {
+                let ws = s.integrate();
+                let rects = resizable_tile(
+                    self.ratio,
+                    screen,
+                    self.num_master,
+                    ws.len() as u32,
+                    &self.weights,
+                );
+                ws.into_iter().zip(rects).collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    fn apply_message(
+        &mut self,
+        message: LayoutMessage,
+        _: &dyn WindowSystem,
+        stack: &Option<Stack<Window>>,
+        _: &GeneralConfig,
+    ) -> bool {
+        match message {
+            LayoutMessage::IncreaseSlave => self.resize_slave(stack, self.increment_weight),
+            LayoutMessage::DecreaseSlave => self.resize_slave(stack, -self.increment_weight),
+            _ => resize_master(
+                &mut self.num_master,
+                &mut self.ratio,
+                self.increment_ratio,
+                message,
+            ),
+        }
+    }
+
+    fn description(&self) -> String {
+        "ResizableTall".to_owned()
+    }
+
+    fn copy(&self) -> Box<dyn Layout> {
+        Box::new(self.clone())
+    }
+
+    fn serialize(&self) -> Option<Value> {
+        Some(json!({
+            "num_master": self.num_master,
+            "increment_ratio": self.increment_ratio,
+            "ratio": self.ratio,
+            "weights": self.weights,
+            "increment_weight": self.increment_weight,
+        }))
+    }
+
+    fn deserialize(&mut self, state: &Value, _: &LayoutRegistry) -> bool {
+        let weights: Option<Vec<f32>> = state["weights"].as_array().and_then(|a| {
+            a.iter()
+                .map(|w| w.as_f64().filter(|&w| w > 0.0).map(|w| w as f32))
+                .collect()
+        });
+
+        match (
+            state["num_master"].as_u64(),
+            state["increment_ratio"].as_f64(),
+            state["ratio"].as_f64(),
+            weights,
+            state["increment_weight"].as_f64(),
+        ) {
+            (
+                Some(num_master),
+                Some(increment_ratio),
+                Some(ratio),
+                Some(weights),
+                Some(increment_weight),
+            ) if ratio > 0.0 && ratio < 1.0 => {
+                self.num_master = num_master as u32;
+                self.increment_ratio = increment_ratio as f32;
+                self.ratio = ratio as f32;
+                self.weights = weights;
+                self.increment_weight = increment_weight as f32;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Change the number of master windows of a layout, at least one,
+/// or the share of the screen they take
+fn resize_master(
+    num_master: &mut u32,
+    ratio: &mut f32,
+    increment_ratio: f32,
+    message: LayoutMessage,
+) -> bool {
+    match message {
+        LayoutMessage::IncreaseMaster => {
+            *num_master += 1;
+            true
+        }
+        LayoutMessage::DecreaseMaster => {
+            if *num_master > 1 {
+                *num_master -= 1
+            }
+            true
+        }
+        _ => resize_ratio(ratio, increment_ratio, message),
+    }
+}
+
+/// Change the ratio of a layout, keeping some
+/// space for the windows on both sides of the split
+fn resize_ratio(ratio: &mut f32, increment_ratio: f32, message: LayoutMessage) -> bool {
+    match message {
+        LayoutMessage::Increase => {
+            *ratio = (*ratio + increment_ratio).min(0.9);
+            true
+        }
+        LayoutMessage::Decrease => {
+            *ratio = (*ratio - increment_ratio).max(0.1);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Switches between several layouts with `Next` and `Prev`,
+/// passing every other message to the current one. It is
+/// described by the current layout, and saved as "Choose".
+pub struct ChooseLayout {
+    pub layouts: Vec<Box<dyn Layout>>,
+    pub current: usize,
+}
+
+impl ChooseLayout {
+    pub fn boxed_new(layouts: Vec<Box<dyn Layout>>) -> Box<dyn Layout> {
+        Box::new(ChooseLayout {
+            layouts,
+            current: 0,
+        })
+    }
+}
+
+impl Layout for ChooseLayout {
+    fn apply_layout(
+        &mut self,
+        window_system: &dyn WindowSystem,
+        screen: Rectangle,
+        config: &GeneralConfig,
+        stack: &Option<Stack<Window>>,
+    ) -> Vec<(Window, Rectangle)> {
+        self.layouts[self.current].apply_layout(window_system, screen, config, stack)
+    }
+
+    fn apply_message(
+        &mut self,
+        message: LayoutMessage,
+        window_system: &dyn WindowSystem,
+        stack: &Option<Stack<Window>>,
+        config: &GeneralConfig,
+    ) -> bool {
+        let count = self.layouts.len();
+        match message {
+            LayoutMessage::Next | LayoutMessage::Prev => {
+                self.layouts[self.current].unhook(window_system, stack, config);
+                self.current = match message {
+                    LayoutMessage::Next => (self.current + 1) % count,
+                    _ => (self.current + count - 1) % count,
+                };
+                true
+            }
+            _ => self.layouts[self.current].apply_message(message, window_system, stack, config),
+        }
+    }
+
+    fn description(&self) -> String {
+        self.layouts[self.current].description()
+    }
+
+    fn name(&self) -> String {
+        "Choose".to_owned()
+    }
+
+    fn copy(&self) -> Box<dyn Layout> {
+        Box::new(ChooseLayout {
+            layouts: self.layouts.iter().map(|x| x.copy()).collect(),
+            current: self.current,
+        })
+    }
+
+    fn unhook(
+        &self,
+        window_system: &dyn WindowSystem,
+        stack: &Option<Stack<Window>>,
+        config: &GeneralConfig,
+    ) {
+        self.layouts[self.current].unhook(window_system, stack, config)
+    }
+
+    fn serialize(&self) -> Option<Value> {
+        Some(json!({
+            "layouts": self.layouts.iter().map(|x| save_layout(x.as_ref())).collect::<Vec<_>>(),
+            "current": self.current,
+        }))
+    }
+
+    fn deserialize(&mut self, state: &Value, layouts: &LayoutRegistry) -> bool {
+        let saved = match state["layouts"].as_array() {
+            Some(saved) if !saved.is_empty() => saved,
+            _ => return false,
+        };
+        let restored: Option<Vec<Box<dyn Layout>>> =
+            saved.iter().map(|x| layouts.build(x)).collect();
+        match (restored, state["current"].as_u64()) {
+            (Some(restored), Some(current)) if (current as usize) < restored.len() => {
+                self.layouts = restored;
+                self.current = current as usize;
+                true
+            }
+            _ => false,
+        }
+    }
 }
 
 #[repr(usize)]
-#[derive(Clone, Copy, Ord, Eq, PartialOrd, PartialEq)]
+#[derive(Clone, Copy, Debug, Ord, Eq, PartialOrd, PartialEq)]
 pub enum Direction {
     Up,
     Down,
@@ -186,3 +1289,17 @@ impl Direction {
         }
     }
 }
+
+impl FromStr for Direction {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Direction, Error> {
+        match s {
+            "up" => Ok(Direction::Up),
+            "down" => Ok(Direction::Down),
+            "left" => Ok(Direction::Left),
+            "right" => Ok(Direction::Right),
+            _ => Err(anyhow!("unknown direction {:?}", s)),
+        }
+    }
+}
@@ -6,9 +6,14 @@ extern crate bitflags;
 extern crate serde_json;
 
 pub mod config;
+pub mod config_file;
 pub mod core;
 pub mod handlers;
+pub mod ipc;
 pub mod layout;
+pub mod mock_window_system;
+pub mod state;
+pub mod status;
 pub mod util;
 pub mod window_manager;
 pub mod window_system;
@@ -0,0 +1,679 @@
+use crate::config::GeneralConfig;
+use crate::window_manager::WindowManager;
+use crate::window_system::*;
+use libc::{c_int, c_ulong};
+use std::borrow::ToOwned;
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, VecDeque};
+use std::os::unix::io::RawFd;
+
+const CW_X: u64 = 1 << 0;
+const CW_Y: u64 = 1 << 1;
+const CW_WIDTH: u64 = 1 << 2;
+const CW_HEIGHT: u64 = 1 << 3;
+const CW_BORDER_WIDTH: u64 = 1 << 4;
+
+/// Named keysyms understood by the mock. Single characters
+/// map to their code point, just like in X11.
+const KEYSYMS: &[(&str, u64)] = &[
+    ("BackSpace", 0xff08),
+    ("Tab", 0xff09),
+    ("Return", 0xff0d),
+    ("Escape", 0xff1b),
+    ("Delete", 0xffff),
+    ("Left", 0xff51),
+    ("Up", 0xff52),
+    ("Right", 0xff53),
+    ("Down", 0xff54),
+    ("space", 0x20),
+    ("comma", 0x2c),
+    ("period", 0x2e),
+    ("minus", 0x2d),
+    ("XF86MonBrightnessUp", 0x1008ff02),
+    ("XF86MonBrightnessDown", 0x1008ff03),
+    ("XF86AudioLowerVolume", 0x1008ff11),
+    ("XF86AudioMute", 0x1008ff12),
+    ("XF86AudioRaiseVolume", 0x1008ff13),
+];
+
+/// The state of a single window inside the `MockWindowSystem`.
+/// Construct it with `MockWindow::new` and override the
+/// properties a test cares about with struct update syntax.
+#[derive(Clone)]
+pub struct MockWindow {
+    pub geometry: Rectangle,
+    pub name: String,
+    pub class: String,
+    pub instance: String,
+    pub role: String,
+    pub strut: Option<Vec<u64>>,
+    pub partial_strut: Option<Vec<u64>>,
+    pub size_hint: SizeHint,
+    pub border_width: u32,
+    pub border_color: u32,
+    pub mapped: bool,
+    pub window_types: Vec<WindowType>,
+    pub transient_for: Option<Window>,
+    pub override_redirect: bool,
+    pub urgent: bool,
+}
+
+impl MockWindow {
+    /// Create an unmapped window with the given geometry
+    /// and empty names
+    pub fn new(geometry: Rectangle) -> MockWindow {
+        MockWindow {
+            geometry,
+            name: String::new(),
+            class: String::new(),
+            instance: String::new(),
+            role: String::new(),
+            strut: None,
+            partial_strut: None,
+            size_hint: SizeHint {
+                min_size: None,
+                max_size: None,
+            },
+            border_width: 0,
+            border_color: 0,
+            mapped: false,
+            window_types: Vec::new(),
+            transient_for: None,
+            override_redirect: false,
+            urgent: false,
+        }
+    }
+}
+
+/// An in-memory window system without any connection to
+/// a display server. It keeps track of everything the
+/// window manager asks it to do, so tests can drive the
+/// manager deterministically and inspect the outcome.
+pub struct MockWindowSystem {
+    root: Window,
+    next_window: Cell<Window>,
+    screens: RefCell<Vec<Rectangle>>,
+    windows: RefCell<BTreeMap<Window, MockWindow>>,
+    /// Stacking order, topmost window first
+    stacking: RefCell<Vec<Window>>,
+    focused: Cell<Window>,
+    pointer: Cell<(u32, u32)>,
+    pointer_grabbed: Cell<bool>,
+    keyboard_grabbed: Cell<bool>,
+    events: RefCell<VecDeque<WindowSystemEvent>>,
+    grabbed_keys: RefCell<Vec<KeyCommand>>,
+    grabbed_buttons: RefCell<Vec<MouseCommand>>,
+    closed: RefCell<Vec<Window>>,
+    killed: RefCell<Vec<Window>>,
+    notice: RefCell<Option<String>>,
+    properties: RefCell<BTreeMap<(Window, String), Vec<u64>>>,
+    text_properties: RefCell<BTreeMap<(Window, String), Vec<String>>>,
+    atoms: RefCell<Vec<String>>,
+}
+
+impl MockWindowSystem {
+    /// Create a new mock window system with the given
+    /// physical screens
+    pub fn new(screens: Vec<Rectangle>) -> MockWindowSystem {
+        MockWindowSystem {
+            root: 1,
+            next_window: Cell::new(2),
+            screens: RefCell::new(screens),
+            windows: RefCell::new(BTreeMap::new()),
+            stacking: RefCell::new(Vec::new()),
+            focused: Cell::new(1),
+            pointer: Cell::new((0, 0)),
+            pointer_grabbed: Cell::new(false),
+            keyboard_grabbed: Cell::new(false),
+            events: RefCell::new(VecDeque::new()),
+            grabbed_keys: RefCell::new(Vec::new()),
+            grabbed_buttons: RefCell::new(Vec::new()),
+            closed: RefCell::new(Vec::new()),
+            killed: RefCell::new(Vec::new()),
+            notice: RefCell::new(None),
+            properties: RefCell::new(BTreeMap::new()),
+            text_properties: RefCell::new(BTreeMap::new()),
+            atoms: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Add a new window and return its id. The window is
+    /// placed on top of the stacking order.
+    pub fn add_window(&self, window: MockWindow) -> Window {
+        let id = self.next_window.get();
+        self.next_window.set(id + 1);
+        self.windows.borrow_mut().insert(id, window);
+        self.stacking.borrow_mut().insert(0, id);
+        id
+    }
+
+    /// Add a new window and queue the event announcing it,
+    /// just like a client mapping a fresh window would.
+    pub fn create_window(&self, window: MockWindow) -> Window {
+        let id = self.add_window(window);
+        self.push_event(WindowSystemEvent::WindowCreated(id));
+        id
+    }
+
+    /// Remove the window and queue the corresponding
+    /// destroy notification.
+    pub fn destroy_window(&self, window: Window) {
+        self.windows.borrow_mut().remove(&window);
+        self.stacking.borrow_mut().retain(|&w| w != window);
+        if self.focused.get() == window {
+            self.focused.set(self.root);
+        }
+        self.push_event(WindowSystemEvent::WindowDestroyed(window));
+    }
+
+    /// Replace the screen configuration and queue the
+    /// notification the window manager uses to rescreen.
+    pub fn set_screens(&self, screens: Vec<Rectangle>) {
+        *self.screens.borrow_mut() = screens;
+        self.push_event(WindowSystemEvent::ConfigurationNotification(self.root));
+    }
+
+    /// Modify the given window in place
+    pub fn update_window<F>(&self, window: Window, f: F)
+    where
+        F: FnOnce(&mut MockWindow),
+    {
+        if let Some(w) = self.windows.borrow_mut().get_mut(&window) {
+            f(w);
+        }
+    }
+
+    /// Append an event to the queue returned by `get_event`
+    pub fn push_event(&self, event: WindowSystemEvent) {
+        self.events.borrow_mut().push_back(event);
+    }
+
+    /// Retrieve a snapshot of the given window's state
+    pub fn window(&self, window: Window) -> Option<MockWindow> {
+        self.windows.borrow().get(&window).cloned()
+    }
+
+    pub fn is_mapped(&self, window: Window) -> bool {
+        self.window(window).is_some_and(|w| w.mapped)
+    }
+
+    /// Return all windows, topmost first
+    pub fn stacking_order(&self) -> Vec<Window> {
+        self.stacking.borrow().clone()
+    }
+
+    pub fn grabbed_keys(&self) -> Vec<KeyCommand> {
+        self.grabbed_keys.borrow().clone()
+    }
+
+    pub fn grabbed_buttons(&self) -> Vec<MouseCommand> {
+        self.grabbed_buttons.borrow().clone()
+    }
+
+    pub fn is_pointer_grabbed(&self) -> bool {
+        self.pointer_grabbed.get()
+    }
+
+    pub fn is_keyboard_grabbed(&self) -> bool {
+        self.keyboard_grabbed.get()
+    }
+
+    /// The message shown by `show_notice`, if any
+    pub fn shown_notice(&self) -> Option<String> {
+        self.notice.borrow().clone()
+    }
+
+    /// All windows that were asked to close, in order
+    pub fn closed_windows(&self) -> Vec<Window> {
+        self.closed.borrow().clone()
+    }
+
+    /// All windows that were killed, in order
+    pub fn killed_windows(&self) -> Vec<Window> {
+        self.killed.borrow().clone()
+    }
+
+    /// Set a raw property on the given window
+    pub fn set_property(&self, window: Window, name: &str, value: Vec<u64>) {
+        self.properties
+            .borrow_mut()
+            .insert((window, name.to_owned()), value);
+    }
+
+    /// Set a list of strings as property on the given window
+    pub fn set_text_property(&self, window: Window, name: &str, value: Vec<String>) {
+        self.text_properties
+            .borrow_mut()
+            .insert((window, name.to_owned()), value);
+    }
+
+    /// Read back a string list property that was published on the given window
+    pub fn get_text_property(&self, window: Window, name: &str) -> Option<Vec<String>> {
+        self.text_properties
+            .borrow()
+            .get(&(window, name.to_owned()))
+            .cloned()
+    }
+
+    /// Intern the given atom name, returning a stable id
+    pub fn get_atom(&self, name: &str) -> u64 {
+        let mut atoms = self.atoms.borrow_mut();
+        match atoms.iter().position(|a| a == name) {
+            Some(i) => i as u64 + 1,
+            None => {
+                atoms.push(name.to_owned());
+                atoms.len() as u64
+            }
+        }
+    }
+
+    /// Read back a property that was published on the given window
+    pub fn get_property(&self, window: Window, name: &str) -> Option<Vec<u64>> {
+        self.properties
+            .borrow()
+            .get(&(window, name.to_owned()))
+            .cloned()
+    }
+}
+
+impl WindowSystem for MockWindowSystem {
+    fn get_string_from_keycode(&self, key: u32) -> String {
+        KEYSYMS
+            .iter()
+            .find(|&&(_, k)| k == key as u64)
+            .map(|&(s, _)| s.to_owned())
+            .or_else(|| std::char::from_u32(key).map(|c| c.to_string()))
+            .unwrap_or_default()
+    }
+
+    fn get_keycode_from_string(&self, key: &str) -> u64 {
+        if let Some(&(_, k)) = KEYSYMS.iter().find(|&&(s, _)| s == key) {
+            return k;
+        }
+
+        let mut chars = key.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => c as u64,
+            _ => 0,
+        }
+    }
+
+    fn get_root(&self) -> Window {
+        self.root
+    }
+
+    fn get_screen_infos(&self) -> Vec<Rectangle> {
+        self.screens.borrow().clone()
+    }
+
+    fn get_number_of_screens(&self) -> usize {
+        self.screens.borrow().len()
+    }
+
+    fn get_display_width(&self, _: usize) -> u32 {
+        self.screens
+            .borrow()
+            .iter()
+            .map(|&Rectangle(x, _, w, _)| x + w as i32)
+            .max()
+            .unwrap_or(0) as u32
+    }
+
+    fn get_display_height(&self, _: usize) -> u32 {
+        self.screens
+            .borrow()
+            .iter()
+            .map(|&Rectangle(_, y, _, h)| y + h as i32)
+            .max()
+            .unwrap_or(0) as u32
+    }
+
+    fn get_window_name(&self, window: Window) -> String {
+        if window == self.root {
+            return "root".to_owned();
+        }
+        self.window(window)
+            .map_or_else(|| "Unknown".to_owned(), |w| w.name)
+    }
+
+    fn get_class_name(&self, window: Window) -> String {
+        self.window(window)
+            .map_or_else(|| "unknown".to_owned(), |w| w.class)
+    }
+
+    fn get_role_name(&self, window: Window) -> String {
+        self.window(window)
+            .map_or_else(|| "unknown".to_owned(), |w| w.instance)
+    }
+
+    fn get_window_role(&self, window: Window) -> String {
+        self.window(window).map(|w| w.role).unwrap_or_default()
+    }
+
+    fn get_windows(&self) -> Vec<Window> {
+        self.windows.borrow().keys().copied().collect()
+    }
+
+    fn set_window_border_width(&self, window: Window, border_width: u32) {
+        self.update_window(window, |w| w.border_width = border_width);
+    }
+
+    fn get_window_border_width(&self, window: Window) -> u32 {
+        self.window(window).map_or(0, |w| w.border_width)
+    }
+
+    fn set_window_border_color(&self, window: Window, border_color: u32) {
+        self.update_window(window, |w| w.border_color = border_color);
+    }
+
+    fn resize_window(&self, window: Window, width: u32, height: u32) {
+        self.update_window(window, |w| {
+            let Rectangle(x, y, _, _) = w.geometry;
+            w.geometry = Rectangle(x, y, width, height);
+        });
+    }
+
+    fn move_window(&self, window: Window, x: i32, y: i32) {
+        self.update_window(window, |w| {
+            let Rectangle(_, _, width, height) = w.geometry;
+            w.geometry = Rectangle(x, y, width, height);
+        });
+    }
+
+    fn show_window(&self, window: Window) {
+        self.update_window(window, |w| w.mapped = true);
+    }
+
+    fn hide_window(&self, window: Window) {
+        self.update_window(window, |w| w.mapped = false);
+    }
+
+    fn focus_window(&self, window: Window, _: &WindowManager) {
+        self.focused.set(window);
+    }
+
+    fn get_focused_window(&self) -> Window {
+        self.focused.get()
+    }
+
+    fn configure_window(
+        &self,
+        window: Window,
+        window_changes: WindowChanges,
+        mask: u64,
+        is_floating: bool,
+    ) {
+        if !is_floating {
+            return;
+        }
+
+        self.update_window(window, |w| {
+            let Rectangle(mut x, mut y, mut width, mut height) = w.geometry;
+            if mask & CW_X != 0 {
+                x = window_changes.x as i32;
+            }
+            if mask & CW_Y != 0 {
+                y = window_changes.y as i32;
+            }
+            if mask & CW_WIDTH != 0 {
+                width = window_changes.width;
+            }
+            if mask & CW_HEIGHT != 0 {
+                height = window_changes.height;
+            }
+            if mask & CW_BORDER_WIDTH != 0 {
+                w.border_width = window_changes.border_width;
+            }
+            w.geometry = Rectangle(x, y, width, height);
+        });
+    }
+
+    fn event_pending(&self) -> bool {
+        !self.events.borrow().is_empty()
+    }
+
+    fn event_fd(&self) -> Option<RawFd> {
+        None
+    }
+
+    /// Pop the next queued event. An empty queue
+    /// yields `UnknownEvent` instead of blocking.
+    fn get_event(&self) -> WindowSystemEvent {
+        self.events
+            .borrow_mut()
+            .pop_front()
+            .unwrap_or(WindowSystemEvent::UnknownEvent)
+    }
+
+    fn flush(&self) {}
+
+    fn grab_keys(&self, keys: Vec<KeyCommand>) {
+        self.grabbed_keys.borrow_mut().extend(keys);
+    }
+
+    fn grab_button(&self, button: MouseCommand) {
+        self.grabbed_buttons.borrow_mut().push(button);
+    }
+
+    fn ungrab_keys(&self) {
+        self.grabbed_keys.borrow_mut().clear();
+    }
+
+    fn ungrab_buttons(&self) {
+        self.grabbed_buttons.borrow_mut().clear();
+    }
+
+    fn remove_enter_events(&self) {
+        self.events
+            .borrow_mut()
+            .retain(|e| !matches!(*e, WindowSystemEvent::Enter(_)));
+    }
+
+    fn remove_motion_events(&self) {
+        self.events
+            .borrow_mut()
+            .retain(|e| !matches!(*e, WindowSystemEvent::MouseMotion(_, _)));
+    }
+
+    fn get_partial_strut(&self, window: Window) -> Option<Vec<u64>> {
+        self.window(window).and_then(|w| w.partial_strut)
+    }
+
+    fn get_strut(&self, window: Window) -> Option<Vec<u64>> {
+        self.window(window).and_then(|w| w.strut)
+    }
+
+    fn set_initial_properties(&self, window: Window) {
+        self.set_property(window, "WM_STATE", vec![3, 0]);
+    }
+
+    fn is_dock(&self, window: Window) -> bool {
+        self.window(window).is_some_and(|w| {
+            w.window_types
+                .iter()
+                .any(|&t| t == WindowType::Dock || t == WindowType::Desktop)
+        })
+    }
+
+    fn get_geometry(&self, window: Window) -> Rectangle {
+        self.window(window)
+            .map_or(Rectangle(0, 0, 0, 0), |w| w.geometry)
+    }
+
+    fn get_size_hints(&self, window: Window) -> SizeHint {
+        self.window(window).map_or(
+            SizeHint {
+                min_size: None,
+                max_size: None,
+            },
+            |w| w.size_hint,
+        )
+    }
+
+    fn get_window_types(&self, window: Window) -> Vec<WindowType> {
+        self.window(window).map_or(Vec::new(), |w| w.window_types)
+    }
+
+    fn requests_fullscreen(&self, window: Window) -> bool {
+        let fullscreen = self.get_atom("_NET_WM_STATE_FULLSCREEN");
+        self.get_property(window, "_NET_WM_STATE")
+            .is_some_and(|state| state.contains(&fullscreen))
+    }
+
+    fn get_transient_for(&self, window: Window) -> Option<Window> {
+        self.window(window).and_then(|w| w.transient_for)
+    }
+
+    fn is_urgent(&self, window: Window) -> bool {
+        self.window(window).is_some_and(|w| w.urgent)
+    }
+
+    fn get_atom_name(&self, atom: c_ulong) -> String {
+        let atoms = self.atoms.borrow();
+        (atom as usize)
+            .checked_sub(1)
+            .and_then(|i| atoms.get(i))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Place the given windows on top of all others,
+    /// the first one being the topmost.
+    fn restack_windows(&self, windows: Vec<Window>) {
+        let mut stacking = self.stacking.borrow_mut();
+        stacking.retain(|w| !windows.contains(w));
+        stacking.splice(0..0, windows);
+    }
+
+    fn close_client(&self, window: Window) {
+        self.closed.borrow_mut().push(window);
+    }
+
+    fn kill_client(&self, window: Window) {
+        self.killed.borrow_mut().push(window);
+    }
+
+    fn grab_pointer(&self) {
+        self.pointer_grabbed.set(true);
+    }
+
+    fn ungrab_pointer(&self) {
+        self.pointer_grabbed.set(false);
+    }
+
+    fn grab_keyboard(&self) {
+        self.keyboard_grabbed.set(true);
+    }
+
+    fn ungrab_keyboard(&self) {
+        self.keyboard_grabbed.set(false);
+    }
+
+    fn get_pointer(&self, _: Window) -> (u32, u32) {
+        self.pointer.get()
+    }
+
+    fn warp_pointer(&self, window: Window, x: u32, y: u32) {
+        let Rectangle(wx, wy, _, _) = self.get_geometry(window);
+        self.pointer
+            .set(((wx + x as i32) as u32, (wy + y as i32) as u32));
+    }
+
+    fn overrides_redirect(&self, window: Window) -> bool {
+        self.window(window).is_some_and(|w| w.override_redirect)
+    }
+
+    fn update_server_state(&self, manager: &WindowManager) {
+        self.set_property(
+            self.root,
+            "_NET_CURRENT_DESKTOP",
+            vec![manager.workspaces.current.workspace.id as u64],
+        );
+        self.set_property(
+            self.root,
+            "_NET_NUMBER_OF_DESKTOPS",
+            vec![manager.workspaces.workspaces().len() as u64],
+        );
+        if let Some(window) = manager.workspaces.peek() {
+            self.set_property(self.root, "_NET_ACTIVE_WINDOW", vec![window]);
+        }
+        self.set_property(
+            self.root,
+            "_NET_CLIENT_LIST",
+            manager.workspaces.all_windows(),
+        );
+        self.set_property(
+            self.root,
+            "_NET_CLIENT_LIST_STACKING",
+            manager.workspaces.stacking_order(&self.stacking_order()),
+        );
+
+        let mut workspaces = manager.workspaces.workspaces();
+        workspaces.sort_by_key(|w| w.id);
+        self.set_text_property(
+            self.root,
+            "_NET_DESKTOP_NAMES",
+            workspaces.into_iter().map(|w| w.tag).collect(),
+        );
+
+        let fullscreen = self.get_atom("_NET_WM_STATE_FULLSCREEN");
+        for (window, tag) in manager.workspaces.all_windows_with_workspaces() {
+            self.set_property(window, "_NET_WM_DESKTOP", vec![tag as u64]);
+            let old = self
+                .get_property(window, "_NET_WM_STATE")
+                .unwrap_or_default();
+            let mut state: Vec<u64> = old.iter().copied().filter(|&a| a != fullscreen).collect();
+            if manager.workspaces.is_fullscreen(window) {
+                state.push(fullscreen);
+            }
+            if state != old {
+                self.set_property(window, "_NET_WM_STATE", state);
+            }
+        }
+    }
+
+    fn show_notice(&self, notice: Option<&str>) {
+        *self.notice.borrow_mut() = notice.map(|x| x.to_owned());
+    }
+
+    fn decode_client_message(
+        &self,
+        window: Window,
+        message_type: c_ulong,
+        _: c_int,
+        data: [i32; 5],
+    ) -> Option<ClientMessage> {
+        if message_type == self.get_atom("_NET_ACTIVE_WINDOW") {
+            Some(ClientMessage::ActivateWindow(window))
+        } else if message_type == self.get_atom("_NET_CLOSE_WINDOW") {
+            Some(ClientMessage::CloseWindow(window))
+        } else if message_type == self.get_atom("_NET_WM_DESKTOP") {
+            Some(ClientMessage::MoveToWorkspace(window, data[0] as u32))
+        } else if message_type == self.get_atom("_NET_WM_STATE") {
+            let fullscreen = self.get_atom("_NET_WM_STATE_FULLSCREEN");
+            if data[1] as u32 as u64 == fullscreen || data[2] as u32 as u64 == fullscreen {
+                StateChange::from_action(data[0]).map(|c| ClientMessage::Fullscreen(window, c))
+            } else {
+                None
+            }
+        } else {
+            None
+        }
+    }
+
+    fn process_message(
+        &self,
+        window_manager: &WindowManager,
+        config: &GeneralConfig,
+        window: Window,
+        atom: c_ulong,
+    ) -> WindowManager {
+        let name = "_NET_CURRENT_DESKTOP";
+        match self.get_property(window, name) {
+            Some(prop) if atom == self.get_atom(name) => {
+                window_manager.view(self, prop[0] as u32, config)
+            }
+            _ => window_manager.clone(),
+        }
+    }
+}
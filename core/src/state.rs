@@ -0,0 +1,257 @@
+//! Save the state of the window manager across restarts.
+//!
+//! `restart` writes the workspaces to a state file, which the new
+//! process reads back and lays over freshly created workspaces.
+//! Windows that vanished in between are dropped, screens that are
+//! gone are left out, and new screens get a free workspace.
+use crate::core::rational_rect::RationalRect;
+use crate::core::screen::Screen;
+use crate::core::stack::Stack;
+use crate::core::workspace::Workspace;
+use crate::core::workspaces::Workspaces;
+use crate::layout::{save_layout, Layout, LayoutRegistry};
+use crate::window_system::Window;
+use anyhow::{bail, Result};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::os::unix::fs::{MetadataExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+
+/// Where `restart` saves the state for the next process
+pub fn state_path() -> PathBuf {
+    match dirs::runtime_dir() {
+        Some(dir) => dir.join("wtftw.state"),
+        None => env::temp_dir().join(format!("wtftw-{}.state", unsafe { libc::getuid() })),
+    }
+}
+
+/// Describe the given workspaces as JSON
+pub fn save(workspaces: &Workspaces) -> Value {
+    let mut all = workspaces.workspaces();
+    all.sort_by_key(|w| w.id);
+
+    json!({
+        "current": workspaces.current.screen_id,
+        "screens": workspaces.screens().iter().map(|s| json!({
+            "id": s.screen_id,
+            "workspace": s.workspace.id,
+        })).collect::<Vec<_>>(),
+        "workspaces": all.iter().map(|w| json!({
+            "id": w.id,
+            "tag": w.tag,
+            "layout": save_layout(w.layout.as_ref()),
+            "stack": w.stack.as_ref().map(|s| json!({
+                "focus": s.focus,
+                "up": s.up,
+                "down": s.down,
+            })),
+        })).collect::<Vec<_>>(),
+        "floating": workspaces.floating.iter().map(|(window, &RationalRect(x, y, width, height))| json!({
+            "window": window,
+            "rect": [x, y, width, height],
+        })).collect::<Vec<_>>(),
+        "fullscreen": workspaces.fullscreen,
+        "borders": workspaces.borders.iter().map(|(window, width)| json!({
+            "window": window,
+            "width": width,
+        })).collect::<Vec<_>>(),
+    })
+}
+
+/// Write the given workspaces to the given state file. The file is
+/// created afresh and readable only by us, as it may live in a shared
+/// directory where others could plant a symlink in its place.
+pub fn write(path: &Path, workspaces: &Workspaces) -> Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != ErrorKind::NotFound => return Err(e.into()),
+        _ => (),
+    }
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)?;
+    file.write_all(save(workspaces).to_string().as_bytes())?;
+    Ok(())
+}
+
+/// Read a state file written by `write` and remove it,
+/// so it is never restored twice
+pub fn read(path: &Path) -> Result<Value> {
+    let metadata = fs::symlink_metadata(path)?;
+    if !metadata.is_file() || metadata.uid() != unsafe { libc::getuid() } {
+        bail!("{} is not a state file of ours", path.display());
+    }
+    let state = serde_json::from_str(&fs::read_to_string(path)?)?;
+    fs::remove_file(path)?;
+    Ok(state)
+}
+
+fn windows(value: &Value) -> Vec<Window> {
+    value.as_array().map_or(Vec::new(), |a| {
+        a.iter().filter_map(|w| w.as_u64()).collect()
+    })
+}
+
+fn stack(value: &Value) -> Option<Stack<Window>> {
+    let focus = value["focus"].as_u64()?;
+    Some(Stack::new(
+        focus,
+        windows(&value["up"]),
+        windows(&value["down"]),
+    ))
+}
+
+/// Rebuild the saved layout. Layouts the registry doesn't know are
+/// restored over the configured one if that is what was saved,
+/// otherwise the configured layout starts afresh.
+fn layout(layout: Box<dyn Layout>, saved: &Value, layouts: &LayoutRegistry) -> Box<dyn Layout> {
+    if let Some(restored) = layouts.build(saved) {
+        return restored;
+    }
+    if saved["description"].as_str() != Some(&layout.name()) || saved["state"].is_null() {
+        return layout;
+    }
+
+    let mut restored = layout.copy();
+    if restored.deserialize(&saved["state"], layouts) {
+        restored
+    } else {
+        layout
+    }
+}
+
+/// Lay the saved state over the given workspaces, which are
+/// expected to be freshly created from the configuration and
+/// the current screens. Only windows in `existing` are kept.
+pub fn restore(
+    workspaces: &Workspaces,
+    state: &Value,
+    existing: &[Window],
+    layouts: &LayoutRegistry,
+) -> Workspaces {
+    let alive = |w: &Window| existing.contains(w);
+    let none = Vec::new();
+
+    let saved: BTreeMap<u32, &Value> = state["workspaces"]
+        .as_array()
+        .unwrap_or(&none)
+        .iter()
+        .filter_map(|w| Some((w["id"].as_u64()? as u32, w)))
+        .collect();
+
+    let mut all: Vec<Workspace> = workspaces
+        .workspaces()
+        .into_iter()
+        .map(|w| match saved.get(&w.id) {
+            Some(s) => {
+                let stack = stack(&s["stack"]).and_then(|x| x.filter(alive));
+                Workspace::new(w.id, w.tag, layout(w.layout, &s["layout"], layouts), stack)
+            }
+            None => w,
+        })
+        .collect();
+    all.sort_by_key(|w| w.id);
+
+    // Put every saved workspace back on its screen, if both still exist
+    let screen_workspaces: BTreeMap<u32, u32> = state["screens"]
+        .as_array()
+        .unwrap_or(&none)
+        .iter()
+        .filter_map(|s| Some((s["id"].as_u64()? as u32, s["workspace"].as_u64()? as u32)))
+        .collect();
+    let mut screens: Vec<Screen> = workspaces.screens();
+    screens.sort_by_key(|s| s.screen_id);
+
+    let mut shown: Vec<Option<u32>> = Vec::new();
+    for screen in screens.iter() {
+        let id = screen_workspaces
+            .get(&screen.screen_id)
+            .copied()
+            .filter(|&id| all.iter().any(|w| w.id == id) && !shown.contains(&Some(id)));
+        shown.push(id);
+    }
+    for i in 0..shown.len() {
+        if shown[i].is_none() {
+            shown[i] = all
+                .iter()
+                .map(|w| w.id)
+                .find(|id| !shown.contains(&Some(*id)));
+        }
+    }
+
+    let current_id = state["current"]
+        .as_u64()
+        .map(|id| id as u32)
+        .filter(|id| screens.iter().any(|s| s.screen_id == *id))
+        .unwrap_or(workspaces.current.screen_id);
+
+    let on_screen: Vec<Screen> = screens
+        .iter()
+        .zip(shown.iter())
+        .map(|(s, &id)| {
+            let workspace = all
+                .iter()
+                .find(|w| Some(w.id) == id)
+                .cloned()
+                .unwrap_or_else(|| s.workspace.clone());
+            Screen::new(workspace, s.screen_id, s.screen_detail)
+        })
+        .collect();
+
+    let floating = state["floating"]
+        .as_array()
+        .unwrap_or(&none)
+        .iter()
+        .filter_map(|f| {
+            let window = f["window"].as_u64().filter(alive)?;
+            let rect: Vec<f32> = f["rect"]
+                .as_array()?
+                .iter()
+                .filter_map(|x| x.as_f64().map(|x| x as f32))
+                .collect();
+            match rect[..] {
+                [x, y, width, height] => Some((window, RationalRect(x, y, width, height))),
+                _ => None,
+            }
+        })
+        .collect();
+    let fullscreen: BTreeSet<Window> = windows(&state["fullscreen"])
+        .into_iter()
+        .filter(alive)
+        .collect();
+    let borders = state["borders"]
+        .as_array()
+        .unwrap_or(&none)
+        .iter()
+        .filter_map(|b| {
+            Some((
+                b["window"].as_u64().filter(alive)?,
+                b["width"].as_u64()? as u32,
+            ))
+        })
+        .collect();
+
+    Workspaces {
+        current: on_screen
+            .iter()
+            .find(|s| s.screen_id == current_id)
+            .cloned()
+            .unwrap(),
+        visible: on_screen
+            .iter()
+            .filter(|s| s.screen_id != current_id)
+            .cloned()
+            .collect(),
+        hidden: all
+            .into_iter()
+            .filter(|w| !shown.contains(&Some(w.id)))
+            .collect(),
+        floating,
+        fullscreen,
+        borders,
+    }
+}
@@ -0,0 +1,293 @@
+//! Render the state of the window manager for status bars.
+//!
+//! A `StatusLogger` prints the workspaces, the active key mode, the
+//! current layout, the title of the focused window and notices like
+//! config errors in the markup of the chosen bar and turns into a
+//! `LogHook` writing to a pipe created by `spawn_pipe`:
+//!
+//! ```ignore
+//! let xmobar = spawn_pipe(config, "xmobar", Vec::new());
+//! config.set_log_hook(StatusLogger::new(Bar::Xmobar).log_hook(xmobar));
+//! ```
+use crate::core::workspace::Workspace;
+use crate::handlers::LogHook;
+use crate::window_manager::WindowManager;
+use crate::window_system::WindowSystem;
+use serde_json::Value;
+use std::io::Write;
+use std::process::Child;
+use std::rc::Rc;
+use std::sync::RwLock;
+
+/// The status bars whose markup is supported
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Bar {
+    Xmobar,
+    Dzen,
+    Lemonbar,
+    /// The JSON protocol of i3bar, also understood by swaybar
+    I3bar,
+}
+
+/// How to decorate one part of the status line. Colours are given
+/// as `#rrggbb`, prefix and suffix are inserted without escaping.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Style {
+    pub prefix: String,
+    pub suffix: String,
+    pub foreground: Option<String>,
+    pub background: Option<String>,
+}
+
+impl Style {
+    pub fn new() -> Style {
+        Style::default()
+    }
+
+    pub fn wrap(mut self, prefix: &str, suffix: &str) -> Style {
+        self.prefix = prefix.to_owned();
+        self.suffix = suffix.to_owned();
+        self
+    }
+
+    pub fn foreground(mut self, colour: &str) -> Style {
+        self.foreground = Some(colour.to_owned());
+        self
+    }
+
+    pub fn background(mut self, colour: &str) -> Style {
+        self.background = Some(colour.to_owned());
+        self
+    }
+}
+
+pub struct StatusLogger {
+    pub bar: Bar,
+    /// The workspace on the focused screen
+    pub current: Style,
+    /// Workspaces on the other screens
+    pub visible: Style,
+    /// Hidden workspaces containing windows
+    pub hidden: Style,
+    /// Hidden workspaces without windows, which are left out if `None`
+    pub empty: Option<Style>,
+    /// Workspaces with a window demanding attention
+    pub urgent: Style,
+    /// The active key mode, shown only while there is one
+    pub mode: Style,
+    pub layout: Style,
+    pub title: Style,
+    /// Messages for the user, see `WindowManager::notify`
+    pub notice: Style,
+    /// Put between two workspaces
+    pub separator: String,
+    /// Put between the workspaces, the mode, the layout, the title and the notice
+    pub section_separator: String,
+    /// Titles longer than this are shortened
+    pub title_length: usize,
+}
+
+impl StatusLogger {
+    /// Create a logger with a default look, similar to xmobarPP
+    pub fn new(bar: Bar) -> StatusLogger {
+        StatusLogger {
+            bar,
+            current: Style::new().wrap("[", "]").foreground("#ffff00"),
+            visible: Style::new().wrap("<", ">"),
+            hidden: Style::new(),
+            empty: None,
+            urgent: Style::new().foreground("#ff0000"),
+            mode: Style::new().foreground("#ff8000"),
+            layout: Style::new(),
+            title: Style::new().foreground("#00ff00"),
+            notice: Style::new().foreground("#ff0000"),
+            separator: " ".to_owned(),
+            section_separator: " : ".to_owned(),
+            title_length: 80,
+        }
+    }
+
+    /// The workspaces in the order of their tags, each with its style
+    fn workspaces<'a>(&'a self, window_manager: &WindowManager) -> Vec<(String, &'a Style)> {
+        let workspaces = &window_manager.workspaces;
+        let urgent = &window_manager.urgent;
+        let mut all: Vec<Workspace> = workspaces.workspaces();
+        all.sort_by_key(|w| w.id);
+
+        all.iter()
+            .filter_map(|w| {
+                let style = if w.id == workspaces.current_tag() {
+                    &self.current
+                } else if w.windows().iter().any(|x| urgent.contains(x)) {
+                    &self.urgent
+                } else if workspaces.visible.iter().any(|s| s.workspace.id == w.id) {
+                    &self.visible
+                } else if w.stack.is_some() {
+                    &self.hidden
+                } else {
+                    self.empty.as_ref()?
+                };
+                Some((w.tag.clone(), style))
+            })
+            .collect()
+    }
+
+    /// The parts of the status line: workspaces, mode, layout, title and notice
+    fn sections<'a>(
+        &'a self,
+        window_manager: &WindowManager,
+        window_system: &dyn WindowSystem,
+    ) -> Vec<Vec<(String, &'a Style)>> {
+        let mode = window_manager.mode.clone().unwrap_or_default();
+        let layout = window_manager
+            .workspaces
+            .current
+            .workspace
+            .layout
+            .description();
+        let title = window_manager
+            .workspaces
+            .peek()
+            .map(|w| shorten(&window_system.get_window_name(w), self.title_length))
+            .unwrap_or_default();
+        let notice = window_manager.notice.clone().unwrap_or_default();
+
+        vec![
+            self.workspaces(window_manager),
+            vec![(mode, &self.mode)],
+            vec![(layout, &self.layout)],
+            vec![(title, &self.title)],
+            vec![(notice, &self.notice)],
+        ]
+        .into_iter()
+        .map(|s| {
+            s.into_iter()
+                .map(|(text, style)| (flatten(&text), style))
+                .filter(|(text, _)| !text.is_empty())
+                .collect()
+        })
+        .filter(|s: &Vec<_>| !s.is_empty())
+        .collect()
+    }
+
+    /// Render a single status line, without the trailing newline
+    pub fn render(
+        &self,
+        window_manager: &WindowManager,
+        window_system: &dyn WindowSystem,
+    ) -> String {
+        let sections = self.sections(window_manager, window_system);
+
+        if self.bar == Bar::I3bar {
+            let blocks: Vec<Value> = sections
+                .iter()
+                .flat_map(|s| s.iter().map(|(text, style)| block(text, style)))
+                .collect();
+            return format!("{},", Value::Array(blocks));
+        }
+
+        sections
+            .iter()
+            .map(|s| {
+                s.iter()
+                    .map(|(text, style)| self.format(text, style))
+                    .collect::<Vec<_>>()
+                    .join(&self.separator)
+            })
+            .collect::<Vec<_>>()
+            .join(&self.section_separator)
+    }
+
+    /// Escape the given text and decorate it with the given style
+    fn format(&self, text: &str, style: &Style) -> String {
+        let text = format!("{}{}{}", style.prefix, self.escape(text), style.suffix);
+        let (fg, bg) = (style.foreground.as_ref(), style.background.as_ref());
+
+        match self.bar {
+            Bar::Xmobar => match (fg, bg) {
+                (None, None) => text,
+                (Some(fg), None) => format!("<fc={}>{}</fc>", fg, text),
+                (fg, Some(bg)) => {
+                    format!("<fc={},{}>{}</fc>", fg.map_or("", |x| x.as_str()), bg, text)
+                }
+            },
+            Bar::Dzen => {
+                let text = fg.map_or(text.clone(), |fg| format!("^fg({}){}^fg()", fg, text));
+                bg.map_or(text.clone(), |bg| format!("^bg({}){}^bg()", bg, text))
+            }
+            Bar::Lemonbar => {
+                let text = fg.map_or(text.clone(), |fg| format!("%{{F{}}}{}%{{F-}}", fg, text));
+                bg.map_or(text.clone(), |bg| format!("%{{B{}}}{}%{{B-}}", bg, text))
+            }
+            Bar::I3bar => text,
+        }
+    }
+
+    /// Keep window titles and tags from being taken for markup
+    fn escape(&self, text: &str) -> String {
+        match self.bar {
+            Bar::Xmobar => format!("<raw={}:{}/>", text.chars().count(), text),
+            Bar::Dzen => text.replace('^', "^^"),
+            Bar::Lemonbar => text.replace('%', "%%"),
+            Bar::I3bar => text.to_owned(),
+        }
+    }
+
+    /// Write a status line to the given pipe whenever it changes
+    pub fn log_hook(self, pipe: Rc<RwLock<Child>>) -> LogHook {
+        let mut last = String::new();
+
+        if self.bar == Bar::I3bar {
+            write_line(&pipe, "{\"version\":1}\n[");
+        }
+
+        Box::new(move |window_manager, window_system| {
+            let line = self.render(&window_manager, window_system.as_ref());
+            if line != last {
+                write_line(&pipe, &line);
+                last = line;
+            }
+        })
+    }
+}
+
+fn block(text: &str, style: &Style) -> Value {
+    let mut block = json!({
+        "full_text": format!("{}{}{}", style.prefix, text, style.suffix),
+        "separator": false,
+    });
+    if let Some(ref fg) = style.foreground {
+        block["color"] = json!(fg);
+    }
+    if let Some(ref bg) = style.background {
+        block["background"] = json!(bg);
+    }
+    block
+}
+
+fn shorten(text: &str, length: usize) -> String {
+    if text.chars().count() > length {
+        let shortened: String = text.chars().take(length.saturating_sub(3)).collect();
+        format!("{}...", shortened)
+    } else {
+        text.to_owned()
+    }
+}
+
+/// Put text spanning several lines on a single one, as status
+/// bars read one line per update
+fn flatten(text: &str) -> String {
+    text.lines()
+        .map(|x| x.trim())
+        .filter(|x| !x.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn write_line(pipe: &Rc<RwLock<Child>>, line: &str) {
+    if let Some(stdin) = pipe.write().unwrap().stdin.as_mut() {
+        if let Err(e) = writeln!(stdin, "{}", line) {
+            debug!("unable to write status line: {}", e);
+        }
+    }
+}
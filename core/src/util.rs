@@ -37,6 +37,19 @@ macro_rules! send_layout_message(
     )
 );
 
+/// Tell wtftw which version the configuration module is built
+/// against, so it isn't loaded into a different one. Required
+/// in every configuration module.
+#[macro_export]
+macro_rules! export_abi_version(
+    () => (
+        #[no_mangle]
+        pub extern "C" fn wtftw_abi_version() -> *const std::os::raw::c_char {
+            $crate::config::ABI_VERSION.as_ptr() as *const std::os::raw::c_char
+        }
+    )
+);
+
 #[macro_export]
 macro_rules! run(
     ($command: expr, $options: expr) => (
@@ -5,8 +5,10 @@ use crate::core::rational_rect::RationalRect;
 use crate::core::screen::Screen;
 use crate::core::workspace::Workspace;
 use crate::core::workspaces::Workspaces;
-use crate::layout::LayoutMessage;
+use crate::layout::{save_layout, LayoutMessage};
+use crate::window_system::ClientMessage;
 use crate::window_system::Rectangle;
+use crate::window_system::StateChange;
 use crate::window_system::Window;
 use crate::window_system::WindowSystem;
 
@@ -18,12 +20,41 @@ use std::rc::Rc;
 pub type ScreenDetail = Rectangle;
 pub type MouseDrag = Box<dyn Fn(u32, u32, WindowManager, &dyn WindowSystem) -> WindowManager>;
 
+/// A state transition external tools might be interested in
+#[derive(Clone, Debug, PartialEq)]
+pub enum WindowManagerEvent {
+    /// The workspace with the given id and tag became current
+    WorkspaceSwitched(u32, String),
+    WindowManaged(Window),
+    WindowUnmanaged(Window),
+    FocusChanged(Option<Window>),
+    TitleChanged(Window, String),
+    /// The workspace with the given id switched to the given layout
+    LayoutChanged(u32, String),
+    ScreensChanged(Vec<ScreenDetail>),
+    UrgencySet(Window),
+    /// A key mode was entered, or left if `None`
+    ModeChanged(Option<String>),
+}
+
 #[derive(Clone)]
 pub struct WindowManager {
     pub running: bool,
+    /// Set by `reload`, the main loop then reads the configuration again
+    pub reload_requested: bool,
     pub dragging: Option<Rc<MouseDrag>>,
     pub workspaces: Workspaces,
     pub waiting_unmap: BTreeMap<Window, Window>,
+    /// The key mode currently active, see `Config::add_mode`
+    pub mode: Option<String>,
+    /// A message for the user shown by status bars, such as
+    /// why the config module could not be used
+    pub notice: Option<String>,
+    /// Managed windows asking for attention, as their WM_HINTS
+    /// last said when they were managed or changed them
+    pub urgent: BTreeSet<Window>,
+    /// Events that happened since the main loop last took them
+    pub events: Vec<WindowManagerEvent>,
 }
 
 impl WindowManager {
@@ -31,6 +62,7 @@ impl WindowManager {
     pub fn new(window_system: &dyn WindowSystem, config: &GeneralConfig) -> WindowManager {
         WindowManager {
             running: true,
+            reload_requested: false,
             dragging: None,
             workspaces: Workspaces::new(
                 config.layout.copy(),
@@ -38,6 +70,10 @@ impl WindowManager {
                 window_system.get_screen_infos(),
             ),
             waiting_unmap: BTreeMap::new(),
+            mode: None,
+            notice: None,
+            urgent: BTreeSet::new(),
+            events: Vec::new(),
         }
     }
 
@@ -107,6 +143,42 @@ impl WindowManager {
             r.hidden = hidden.clone();
             r
         })
+        .emit(vec![WindowManagerEvent::ScreensChanged(screens)])
+    }
+
+    /// Switch from the old to the new configuration, keeping all windows
+    /// where they are. If the configured layout changed, every workspace
+    /// gets the new one, otherwise layouts keep their current parameters.
+    /// Borders are redrawn with the new colours and widths.
+    pub fn reconfigure(
+        &self,
+        window_system: &dyn WindowSystem,
+        old: &GeneralConfig,
+        new: &GeneralConfig,
+    ) -> WindowManager {
+        if save_layout(old.layout.as_ref()) == save_layout(new.layout.as_ref()) {
+            return self.windows(window_system, new, &|x| x.clone());
+        }
+
+        let mut all = self.workspaces.workspaces();
+        all.sort_by_key(|w| w.id);
+        for workspace in all.iter() {
+            workspace
+                .layout
+                .unhook(window_system, &workspace.stack, old);
+        }
+        let description = new.layout.description();
+        let events: Vec<WindowManagerEvent> = all
+            .iter()
+            .filter(|w| w.layout.description() != description)
+            .map(|w| WindowManagerEvent::LayoutChanged(w.id, description.clone()))
+            .collect();
+
+        self.modify_workspaces(|x| {
+            x.map_workspaces(|w| Workspace::new(w.id, w.tag, new.layout.copy(), w.stack))
+        })
+        .emit(events)
+        .windows(window_system, new, &|x| x.clone())
     }
 
     pub fn update_layouts(
@@ -129,7 +201,8 @@ impl WindowManager {
                         .current
                         .workspace
                         .stack
-                        .and_then(|x| x.filter(|w| !self.workspaces.floating.contains_key(w))),
+                        .and_then(|x| x.filter(|w| !self.workspaces.floating.contains_key(w)))
+                        .and_then(|x| x.filter(|&w| !self.workspaces.is_fullscreen(w))),
                 );
                 s
             })
@@ -137,12 +210,17 @@ impl WindowManager {
 
         WindowManager {
             running: self.running,
+            reload_requested: self.reload_requested,
             dragging: self.dragging.clone(),
             workspaces: self
                 .workspaces
                 .from_current(screens[0].clone())
                 .from_visible(screens.into_iter().skip(1).collect()),
             waiting_unmap: self.waiting_unmap.clone(),
+            mode: self.mode.clone(),
+            notice: self.notice.clone(),
+            urgent: self.urgent.clone(),
+            events: self.events.clone(),
         }
     }
 
@@ -160,6 +238,21 @@ impl WindowManager {
         window: Window,
         config: &GeneralConfig,
     ) -> WindowManager {
+        self.manage_with(window_system, window, config, &|x| x.clone())
+    }
+
+    /// Manage a new window, applying the given manage hook before
+    /// the window is laid out for the first time.
+    pub fn manage_with<F>(
+        &self,
+        window_system: &dyn WindowSystem,
+        window: Window,
+        config: &GeneralConfig,
+        hook: &F,
+    ) -> WindowManager
+    where
+        F: Fn(&Workspaces) -> Workspaces,
+    {
         fn adjust(RationalRect(x, y, w, h): RationalRect) -> RationalRect {
             if x + w > 1.0 || y + h > 1.0 || x < 0.0 || y < 0.0 {
                 RationalRect(0.5 - w / 2.0, 0.5 - h / 2.0, w, h)
@@ -169,26 +262,58 @@ impl WindowManager {
         }
 
         let size_hints = window_system.get_size_hints(window);
+        let transient_for = window_system.get_transient_for(window);
 
-        let is_transient = false;
+        let is_transient = transient_for.is_some();
+        let is_dialog = window_system
+            .get_window_types(window)
+            .iter()
+            .any(|t| t.floats_by_default());
         let is_fixed_size =
             size_hints.min_size.is_some() && size_hints.min_size == size_hints.max_size;
 
         debug!("setting focus to newly managed window {}", window);
 
-        let result = if is_transient || is_fixed_size {
+        let result = if is_transient || is_dialog {
+            // Float dialogs centred on the screen of their parent, if it is visible
+            let screen = transient_for
+                .and_then(|parent| self.workspaces.find_screen(parent))
+                .unwrap_or_else(|| self.workspaces.current.clone());
+            let tag = screen.workspace.id;
+            let r = WindowManager::center_location(
+                window_system.get_geometry(window),
+                screen.screen_detail,
+            );
+            self.windows(window_system, config, &|x| {
+                let w = x.insert_up(window);
+                let w = if tag == w.current_tag() {
+                    w
+                } else {
+                    w.shift_window(tag, window)
+                };
+                hook(&w.float(window, r).focus_window(window))
+            })
+        } else if is_fixed_size {
             let r = adjust(self.float_location(window_system, window));
             self.windows(window_system, config, &|x| {
-                x.insert_up(window).float(window, r)
+                hook(&x.insert_up(window).float(window, r))
             })
             .focus(window, window_system, config)
         } else {
-            self.windows(window_system, config, &|x| x.insert_up(window))
+            self.windows(window_system, config, &|x| hook(&x.insert_up(window)))
                 .focus(window, window_system, config)
         };
 
         debug!("focus is set to {}", window);
 
+        let mut result = if window_system.requests_fullscreen(window) {
+            result.set_fullscreen(window_system, config, window, true)
+        } else {
+            result
+        };
+        if window_system.is_urgent(window) {
+            result.urgent.insert(window);
+        }
         result
     }
 
@@ -201,7 +326,9 @@ impl WindowManager {
     ) -> WindowManager {
         if self.workspaces.contains(window) {
             debug!("unmanaging window {}", window);
-            self.windows(window_system, config, &|x| x.delete(window))
+            let mut result = self.windows(window_system, config, &|x| x.delete(window));
+            result.urgent.remove(&window);
+            result
         } else {
             self.clone()
         }
@@ -239,9 +366,118 @@ impl WindowManager {
     {
         WindowManager {
             running: self.running,
+            reload_requested: self.reload_requested,
             dragging: self.dragging.clone(),
             workspaces: f(&self.workspaces),
             waiting_unmap: self.waiting_unmap.clone(),
+            mode: self.mode.clone(),
+            notice: self.notice.clone(),
+            urgent: self.urgent.clone(),
+            events: self.events.clone(),
+        }
+    }
+
+    /// Enter the key mode with the given name, grabbing the keyboard
+    /// so that all keys reach the mode's bindings
+    pub fn enter_mode(&self, window_system: &dyn WindowSystem, name: &str) -> WindowManager {
+        if self.mode.as_deref() == Some(name) {
+            return self.clone();
+        }
+        if self.mode.is_none() {
+            window_system.grab_keyboard();
+        }
+
+        let mut w = self.emit(vec![WindowManagerEvent::ModeChanged(Some(name.to_owned()))]);
+        w.mode = Some(name.to_owned());
+        w
+    }
+
+    /// Leave the current key mode, if any, and release the keyboard
+    pub fn exit_mode(&self, window_system: &dyn WindowSystem) -> WindowManager {
+        if self.mode.is_none() {
+            return self.clone();
+        }
+        window_system.ungrab_keyboard();
+
+        let mut w = self.emit(vec![WindowManagerEvent::ModeChanged(None)]);
+        w.mode = None;
+        w
+    }
+
+    /// Show the given message in status bars, or stop showing one if `None`
+    pub fn notify(&self, notice: Option<String>) -> WindowManager {
+        let mut w = self.clone();
+        w.notice = notice;
+        w
+    }
+
+    /// Record the given events for the main loop to pass on
+    pub fn emit<I>(&self, events: I) -> WindowManager
+    where
+        I: IntoIterator<Item = WindowManagerEvent>,
+    {
+        let mut w = self.clone();
+        w.events.extend(events);
+        w
+    }
+
+    /// The events caused by switching from the current workspaces to the given ones
+    fn transition_events(&self, ws: &Workspaces) -> Vec<WindowManagerEvent> {
+        let old_windows = self.workspaces.all_windows();
+        let new_windows = ws.all_windows();
+        let mut events: Vec<WindowManagerEvent> = new_windows
+            .iter()
+            .filter(|w| !old_windows.contains(w))
+            .map(|&w| WindowManagerEvent::WindowManaged(w))
+            .chain(
+                old_windows
+                    .iter()
+                    .filter(|w| !new_windows.contains(w))
+                    .map(|&w| WindowManagerEvent::WindowUnmanaged(w)),
+            )
+            .collect();
+
+        if ws.current_tag() != self.workspaces.current_tag() {
+            events.push(WindowManagerEvent::WorkspaceSwitched(
+                ws.current_tag(),
+                ws.current.workspace.tag.clone(),
+            ));
+        }
+
+        if ws.peek() != self.workspaces.peek() {
+            events.push(WindowManagerEvent::FocusChanged(ws.peek()));
+        }
+
+        events
+    }
+
+    /// React to a changed property of a managed window
+    pub fn property_changed(
+        &self,
+        window_system: &dyn WindowSystem,
+        window: Window,
+        property: &str,
+    ) -> WindowManager {
+        if !self.is_window_managed(window) {
+            return self.clone();
+        }
+
+        match property {
+            "WM_NAME" | "_NET_WM_NAME" => self.emit(vec![WindowManagerEvent::TitleChanged(
+                window,
+                window_system.get_window_name(window),
+            )]),
+            "WM_HINTS" => {
+                let mut result = self.clone();
+                if window_system.is_urgent(window) {
+                    result.urgent.insert(window);
+                    result.emit(vec![WindowManagerEvent::UrgencySet(window)])
+                } else {
+                    result.urgent.remove(&window);
+                    result
+                }
+            }
+            _ => self.clone(),
         }
     }
 
@@ -305,6 +541,7 @@ impl WindowManager {
                     .workspace
                     .stack
                     .and_then(|x| x.filter(|win| !ws.floating.contains_key(win)))
+                    .and_then(|x| x.filter(|win| !ws.is_fullscreen(*win)))
                     .and_then(|x| x.filter(|win| !vis.contains(win)));
                 let view_rect = w.screen_detail;
 
@@ -312,22 +549,27 @@ impl WindowManager {
                     .layout
                     .apply_layout(window_system, view_rect, config, &tiled);
 
+                let full = this
+                    .with(Vec::new(), |x| x.integrate())
+                    .into_iter()
+                    .filter(|&x| ws.is_fullscreen(x))
+                    .map(|x| (x, view_rect))
+                    .collect::<Vec<_>>();
+
                 let flt = this
                     .with(Vec::new(), |x| x.integrate())
                     .into_iter()
-                    .filter(|x| self.workspaces.floating.contains_key(x))
+                    .filter(|x| ws.floating.contains_key(x) && !ws.is_fullscreen(*x))
                     .map(|x| {
                         (
                             x,
-                            WindowManager::scale_rational_rect(
-                                view_rect,
-                                self.workspaces.floating[&x],
-                            ),
+                            WindowManager::scale_rational_rect(view_rect, ws.floating[&x]),
                         )
                     })
                     .collect::<Vec<_>>();
 
-                let vs: Vec<(Window, Rectangle)> = flt.into_iter().chain(rs.into_iter()).collect();
+                let vs: Vec<(Window, Rectangle)> =
+                    full.into_iter().chain(flt).chain(rs.into_iter()).collect();
                 window_system.restack_windows(vs.iter().map(|x| x.0).collect());
 
                 vs.into_iter()
@@ -337,14 +579,15 @@ impl WindowManager {
         let visible = rects.iter().map(|x| x.0).collect::<Vec<_>>();
 
         for &(window, rect) in rects.iter() {
-            WindowManager::tile_window(window_system, config, window, rect);
+            let border_width = ws.border_width(window, config.border_width);
+            WindowManager::tile_window(window_system, border_width, window, rect);
         }
 
         visible.iter().fold((), |_, &x| {
             window_system.set_window_border_color(x, config.border_color)
         });
         visible.iter().fold((), |_, &x| {
-            window_system.set_window_border_width(x, config.border_width)
+            window_system.set_window_border_width(x, ws.border_width(x, config.border_width))
         });
 
         for &win in visible.iter() {
@@ -376,6 +619,7 @@ impl WindowManager {
         }
 
         let modified = self
+            .emit(self.transition_events(&ws))
             .modify_workspaces(|_| ws.clone())
             .update_layouts(window_system, config);
 
@@ -391,7 +635,56 @@ impl WindowManager {
         window_system: &dyn WindowSystem,
         config: &GeneralConfig,
     ) -> WindowManager {
-        self.modify_workspaces(|w| w.send_layout_message(message, window_system, config))
+        let old_layout = self.workspaces.current.workspace.layout.description();
+        let w = self.modify_workspaces(|w| w.send_layout_message(message, window_system, config));
+        let workspace = &w.workspaces.current.workspace;
+
+        if workspace.layout.description() == old_layout {
+            w
+        } else {
+            w.emit(vec![WindowManagerEvent::LayoutChanged(
+                workspace.id,
+                workspace.layout.description(),
+            )])
+        }
+    }
+
+    /// React to a request sent by a client or an external tool
+    pub fn handle_client_message(
+        &self,
+        window_system: &dyn WindowSystem,
+        config: &GeneralConfig,
+        message: ClientMessage,
+    ) -> WindowManager {
+        match message {
+            ClientMessage::ActivateWindow(window) if self.is_window_managed(window) => {
+                debug!("activating window {}", window);
+                self.windows(window_system, config, &|w| w.focus_window(window))
+            }
+            ClientMessage::CloseWindow(window) if self.is_window_managed(window) => {
+                debug!("closing window {}", window);
+                // Only this window, not every window of its client
+                window_system.kill_client(window);
+                self.clone()
+            }
+            ClientMessage::MoveToWorkspace(window, index)
+                if self.is_window_managed(window)
+                    && index < self.workspaces.number_workspaces() =>
+            {
+                debug!("moving window {} to workspace {}", window, index);
+                self.windows(window_system, config, &|w| w.shift_window(index, window))
+            }
+            ClientMessage::Fullscreen(window, change) if self.is_window_managed(window) => {
+                let fullscreen = match change {
+                    StateChange::Remove => false,
+                    StateChange::Add => true,
+                    StateChange::Toggle => !self.workspaces.is_fullscreen(window),
+                };
+                debug!("setting fullscreen of window {} to {}", window, fullscreen);
+                self.set_fullscreen(window_system, config, window, fullscreen)
+            }
+            _ => self.clone(),
+        }
     }
 
     /// Close the currently focused window
@@ -425,23 +718,40 @@ impl WindowManager {
 
     fn tile_window(
         window_system: &dyn WindowSystem,
-        config: &GeneralConfig,
+        border_width: u32,
         window: Window,
         Rectangle(x, y, w, h): Rectangle,
     ) {
-        window_system.resize_window(
-            window,
-            w - 2 * config.border_width,
-            h - 2 * config.border_width,
-        );
+        window_system.resize_window(window, w - 2 * border_width, h - 2 * border_width);
         window_system.move_window(window, x, y);
         window_system.show_window(window);
     }
 
+    /// Calculate the rectangle of a window with the given geometry
+    /// centred on the given screen. The window is shrunk if needed.
+    pub fn center_location(
+        Rectangle(_, _, w, h): Rectangle,
+        Rectangle(_, _, sw, sh): ScreenDetail,
+    ) -> RationalRect {
+        let rw = (w as f32 / sw as f32).min(1.0);
+        let rh = (h as f32 / sh as f32).min(1.0);
+
+        RationalRect((1.0 - rw) / 2.0, (1.0 - rh) / 2.0, rw, rh)
+    }
+
     pub fn float_location(&self, window_system: &dyn WindowSystem, window: Window) -> RationalRect {
-        let Rectangle(sx, sy, sw, sh) = self.workspaces.current.screen_detail;
-        let Rectangle(rx, ry, rw, rh) = window_system.get_geometry(window);
+        WindowManager::relative_location(
+            window_system.get_geometry(window),
+            self.workspaces.current.screen_detail,
+        )
+    }
 
+    /// Calculate the rectangle of a window with the given geometry
+    /// relative to the given screen.
+    pub fn relative_location(
+        Rectangle(rx, ry, rw, rh): Rectangle,
+        Rectangle(sx, sy, sw, sh): ScreenDetail,
+    ) -> RationalRect {
         RationalRect(
             (rx as f32 - sx as f32) / sw as f32,
             (ry as f32 - sy as f32) / sh as f32,
@@ -460,11 +770,33 @@ impl WindowManager {
         self.windows(window_system, config, &|w| w.float(window, rect))
     }
 
-    pub fn mouse_drag(
+    /// Show the given window over its whole screen, without borders
+    /// and above all other windows, or return it to its previous state.
+    pub fn set_fullscreen(
         &self,
         window_system: &dyn WindowSystem,
-        f: Box<dyn Fn(u32, u32, WindowManager, &dyn WindowSystem) -> WindowManager>,
+        config: &GeneralConfig,
+        window: Window,
+        fullscreen: bool,
     ) -> WindowManager {
+        if fullscreen {
+            self.windows(window_system, config, &|w| w.fullscreen(window))
+        } else {
+            self.windows(window_system, config, &|w| w.unfullscreen(window))
+        }
+    }
+
+    pub fn toggle_fullscreen(
+        &self,
+        window_system: &dyn WindowSystem,
+        config: &GeneralConfig,
+        window: Window,
+    ) -> WindowManager {
+        let fullscreen = !self.workspaces.is_fullscreen(window);
+        self.set_fullscreen(window_system, config, window, fullscreen)
+    }
+
+    pub fn mouse_drag(&self, window_system: &dyn WindowSystem, f: MouseDrag) -> WindowManager {
         window_system.grab_pointer();
 
         let motion = Rc::new(Box::new(move |x, y, window_manager, w: &dyn WindowSystem| {
@@ -475,9 +807,14 @@ impl WindowManager {
 
         WindowManager {
             running: self.running,
+            reload_requested: self.reload_requested,
             dragging: Some(motion),
             workspaces: self.workspaces.clone(),
             waiting_unmap: self.waiting_unmap.clone(),
+            mode: self.mode.clone(),
+            notice: self.notice.clone(),
+            urgent: self.urgent.clone(),
+            events: self.events.clone(),
         }
     }
 
@@ -555,9 +892,14 @@ impl WindowManager {
 
         WindowManager {
             running: self.running,
+            reload_requested: self.reload_requested,
             dragging: self.dragging.clone(),
             workspaces: self.workspaces.clone(),
             waiting_unmap: new_map,
+            mode: self.mode.clone(),
+            notice: self.notice.clone(),
+            urgent: self.urgent.clone(),
+            events: self.events.clone(),
         }
     }
 
@@ -568,9 +910,14 @@ impl WindowManager {
 
         WindowManager {
             running: self.running,
+            reload_requested: self.reload_requested,
             dragging: self.dragging.clone(),
             workspaces: self.workspaces.clone(),
             waiting_unmap: new_map,
+            mode: self.mode.clone(),
+            notice: self.notice.clone(),
+            urgent: self.urgent.clone(),
+            events: self.events.clone(),
         }
     }
 
@@ -581,9 +928,14 @@ impl WindowManager {
         }
         WindowManager {
             running: self.running,
+            reload_requested: self.reload_requested,
             dragging: self.dragging.clone(),
             workspaces: self.workspaces.clone(),
             waiting_unmap: new_map,
+            mode: self.mode.clone(),
+            notice: self.notice.clone(),
+            urgent: self.urgent.clone(),
+            events: self.events.clone(),
         }
     }
 }
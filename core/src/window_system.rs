@@ -4,6 +4,8 @@ use self::libc::{c_int, c_ulong};
 use crate::config::GeneralConfig;
 use crate::window_manager::WindowManager;
 use std::fmt::{Debug, Error, Formatter};
+use std::os::unix::io::RawFd;
+use std::str::FromStr;
 
 pub type Window = u64;
 
@@ -50,6 +52,61 @@ impl KeyCommand {
     pub fn new(key: u64, mask: KeyModifiers) -> KeyCommand {
         KeyCommand { key, mask }
     }
+
+    /// Parse a binding like `M-S-Return`, `C-M-j` or `<XF86AudioMute>`.
+    /// `M` stands for the given mod mask, `M1` to `M5` for the modifiers
+    /// themselves, `S` for shift and `C` for control. Keys are given by
+    /// their keysym names, optionally in angle brackets.
+    pub fn parse(
+        binding: &str,
+        mod_mask: KeyModifiers,
+        window_system: &dyn WindowSystem,
+    ) -> anyhow::Result<KeyCommand> {
+        let mut mask = KeyModifiers::NONEMASK;
+        let mut rest = binding.trim();
+
+        // Keysym names contain no dashes, so all but the last part are modifiers
+        while let Some(i) = rest.find('-').filter(|&i| i + 1 < rest.len()) {
+            mask |= match &rest[..i] {
+                "M" => mod_mask,
+                "M1" => KeyModifiers::MOD1MASK,
+                "M2" => KeyModifiers::MOD2MASK,
+                "M3" => KeyModifiers::MOD3MASK,
+                "M4" => KeyModifiers::MOD4MASK,
+                "M5" => KeyModifiers::MOD5MASK,
+                "S" => KeyModifiers::SHIFTMASK,
+                "C" => KeyModifiers::CONTROLMASK,
+                modifier => anyhow::bail!("unknown modifier {:?} in {:?}", modifier, binding),
+            };
+            rest = &rest[i + 1..];
+        }
+
+        let key = rest
+            .strip_prefix('<')
+            .and_then(|x| x.strip_suffix('>'))
+            .unwrap_or(rest);
+        match window_system.get_keycode_from_string(key) {
+            0 => anyhow::bail!("unknown key {:?} in {:?}", key, binding),
+            code => Ok(KeyCommand::new(code, mask)),
+        }
+    }
+
+    /// Parse keys pressed one after another, separated by
+    /// spaces like `M-x w`, see `parse`
+    pub fn parse_sequence(
+        binding: &str,
+        mod_mask: KeyModifiers,
+        window_system: &dyn WindowSystem,
+    ) -> anyhow::Result<Vec<KeyCommand>> {
+        let keys = binding
+            .split_whitespace()
+            .map(|key| KeyCommand::parse(key, mod_mask, window_system))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        if keys.is_empty() {
+            anyhow::bail!("no key given");
+        }
+        Ok(keys)
+    }
 }
 
 impl Debug for KeyCommand {
@@ -105,6 +162,62 @@ impl KeyModifiers {
     }
 }
 
+/// The functional type of a window as announced by
+/// the client through `_NET_WM_WINDOW_TYPE`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WindowType {
+    Desktop,
+    Dock,
+    Toolbar,
+    Menu,
+    Utility,
+    Splash,
+    Dialog,
+    DropdownMenu,
+    PopupMenu,
+    Tooltip,
+    Notification,
+    Combo,
+    Dnd,
+    Normal,
+}
+
+impl FromStr for WindowType {
+    type Err = anyhow::Error;
+
+    /// Parse a type from its kebab-case name, e.g. `dropdown-menu`
+    fn from_str(s: &str) -> anyhow::Result<WindowType> {
+        Ok(match s {
+            "desktop" => WindowType::Desktop,
+            "dock" => WindowType::Dock,
+            "toolbar" => WindowType::Toolbar,
+            "menu" => WindowType::Menu,
+            "utility" => WindowType::Utility,
+            "splash" => WindowType::Splash,
+            "dialog" => WindowType::Dialog,
+            "dropdown-menu" => WindowType::DropdownMenu,
+            "popup-menu" => WindowType::PopupMenu,
+            "tooltip" => WindowType::Tooltip,
+            "notification" => WindowType::Notification,
+            "combo" => WindowType::Combo,
+            "dnd" => WindowType::Dnd,
+            "normal" => WindowType::Normal,
+            _ => anyhow::bail!("unknown window type {:?}", s),
+        })
+    }
+}
+
+impl WindowType {
+    /// Checks if windows of this type should float
+    /// instead of being tiled
+    pub fn floats_by_default(&self) -> bool {
+        matches!(
+            *self,
+            WindowType::Dialog | WindowType::Utility | WindowType::Splash
+        )
+    }
+}
+
 #[derive(Clone, Copy, PartialEq, Eq)]
 pub struct SizeHint {
     pub min_size: Option<(u32, u32)>,
@@ -137,6 +250,40 @@ pub enum WindowSystemEvent {
     UnknownEvent,
 }
 
+/// A request sent by a client or an external tool (pagers,
+/// taskbars, wmctrl) that has been decoded by the window system.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClientMessage {
+    /// Focus the window, switching to its workspace if necessary.
+    ActivateWindow(Window),
+    /// Close the window.
+    CloseWindow(Window),
+    /// Move the window to the workspace with the given index.
+    MoveToWorkspace(Window, u32),
+    /// Change the fullscreen state of the window.
+    Fullscreen(Window, StateChange),
+}
+
+/// How a window state (e.g. fullscreen) should be changed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StateChange {
+    Remove,
+    Add,
+    Toggle,
+}
+
+impl StateChange {
+    /// Decode the action of a `_NET_WM_STATE` client message
+    pub fn from_action(action: i32) -> Option<StateChange> {
+        match action {
+            0 => Some(StateChange::Remove),
+            1 => Some(StateChange::Add),
+            2 => Some(StateChange::Toggle),
+            _ => None,
+        }
+    }
+}
+
 pub trait WindowSystem {
     fn get_string_from_keycode(&self, key: u32) -> String;
     fn get_keycode_from_string(&self, key: &str) -> u64;
@@ -154,6 +301,9 @@ pub trait WindowSystem {
     fn get_class_name(&self, window: Window) -> String;
     // Get 'role' name of window
     fn get_role_name(&self, window: Window) -> String;
+    /// Get the given window's WM_WINDOW_ROLE, or an empty
+    /// string if it doesn't set one
+    fn get_window_role(&self, window: Window) -> String;
     /// Get a list of all windows
     fn get_windows(&self) -> Vec<Window>;
     /// Set the given window's border width
@@ -179,11 +329,18 @@ pub trait WindowSystem {
     );
     /// Check if there are events pending
     fn event_pending(&self) -> bool;
+    /// A file descriptor that becomes readable whenever new
+    /// events arrive, if the window system has one
+    fn event_fd(&self) -> Option<RawFd>;
     /// Get the next event from the queue
     fn get_event(&self) -> WindowSystemEvent;
     fn flush(&self);
     fn grab_keys(&self, keys: Vec<KeyCommand>);
     fn grab_button(&self, button: MouseCommand);
+    /// Release all keys grabbed with `grab_keys`
+    fn ungrab_keys(&self);
+    /// Release all buttons grabbed with `grab_button`
+    fn ungrab_buttons(&self);
     fn remove_enter_events(&self);
     fn remove_motion_events(&self);
     fn get_partial_strut(&self, window: Window) -> Option<Vec<u64>>;
@@ -192,15 +349,44 @@ pub trait WindowSystem {
     fn is_dock(&self, window: Window) -> bool;
     fn get_geometry(&self, window: Window) -> Rectangle;
     fn get_size_hints(&self, window: Window) -> SizeHint;
+    /// Get the types the window announced, in order of preference
+    fn get_window_types(&self, window: Window) -> Vec<WindowType>;
+    /// Whether the window asked to be fullscreen before it was managed
+    fn requests_fullscreen(&self, window: Window) -> bool;
+    /// Get the window the given window is transient for (e.g. the
+    /// main window of a dialog), if any
+    fn get_transient_for(&self, window: Window) -> Option<Window>;
+    /// Check whether the given window wants the user's attention
+    fn is_urgent(&self, window: Window) -> bool;
+    /// Get the name of the given atom
+    fn get_atom_name(&self, atom: c_ulong) -> String;
     fn restack_windows(&self, windows: Vec<Window>);
+    /// Disconnect the client owning the given window, closing all its windows
     fn close_client(&self, window: Window);
+    /// Ask the given window to close, with WM_DELETE_WINDOW if it understands it
     fn kill_client(&self, window: Window);
     fn grab_pointer(&self);
     fn ungrab_pointer(&self);
+    /// Send all key presses to the window manager, e.g. while a key mode is active
+    fn grab_keyboard(&self);
+    fn ungrab_keyboard(&self);
     fn get_pointer(&self, window: Window) -> (u32, u32);
     fn warp_pointer(&self, window: Window, x: u32, y: u32);
     fn overrides_redirect(&self, window: Window) -> bool;
     fn update_server_state(&self, manager: &WindowManager);
+    /// Show the given message in a window of its own until it is
+    /// clicked away, or hide it if `None`. Used when no status bar
+    /// shows the notices of the window manager.
+    fn show_notice(&self, notice: Option<&str>);
+    /// Decode a raw client message event into a request the
+    /// window manager understands. Unknown messages yield None.
+    fn decode_client_message(
+        &self,
+        window: Window,
+        message_type: c_ulong,
+        format: c_int,
+        data: [i32; 5],
+    ) -> Option<ClientMessage>;
     fn process_message(
         &self,
         window_manager: &WindowManager,
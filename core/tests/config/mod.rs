@@ -0,0 +1,197 @@
+extern crate wtftw_core;
+
+use self::wtftw_core::config::{
+    check_abi_version, config_manifest, source_hash, Config, ABI_VERSION, ERROR_LOG,
+};
+use self::wtftw_core::mock_window_system::MockWindowSystem;
+use self::wtftw_core::window_manager::WindowManager;
+use self::wtftw_core::window_system::*;
+use std::cell::Cell;
+use std::env;
+use std::ffi::CStr;
+use std::fs;
+use std::ops::Deref;
+use std::path::Path;
+use std::rc::Rc;
+
+wtftw_core::export_abi_version!();
+
+#[test]
+fn abi_version_is_exported_and_checked() {
+    let exported = unsafe { CStr::from_ptr(wtftw_abi_version()) };
+    let exported = exported.to_str().unwrap();
+
+    assert!(ABI_VERSION.ends_with('\0'));
+    assert!(exported.starts_with("rustc "));
+    assert!(exported.contains(", source "));
+    assert!(check_abi_version(exported).is_ok());
+    assert!(check_abi_version("0.0.1+abi.1").is_err());
+    assert!(check_abi_version(&format!("{}+abi.0", env!("CARGO_PKG_VERSION"))).is_err());
+    assert!(check_abi_version("").is_err());
+}
+
+#[test]
+fn missing_config_module_changes_nothing() {
+    let ws = MockWindowSystem::new(vec![Rectangle(0, 0, 800, 600)]);
+    let dir = env::temp_dir().join(format!("wtftw-config-{}", std::process::id()));
+    fs::create_dir_all(dir.join("target").join("release")).unwrap();
+
+    let mut config = Config::initialize().unwrap();
+    config.internal.wtftw_dir = dir.to_str().unwrap().to_owned();
+    let mut wm = WindowManager::new(&ws, &config.general);
+
+    assert!(config.call(&mut wm, &ws).is_err());
+    assert!(config.internal.library.is_none());
+    assert!(config.internal.key_handlers.is_empty());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn config_manifest_pins_this_wtftw() {
+    let manifest = config_manifest();
+    let parsed: toml::Value = manifest.parse().unwrap();
+    let wtftw = &parsed["dependencies"]["wtftw"];
+
+    assert!(manifest.starts_with("# Generated by wtftw"));
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).parent().unwrap();
+    assert!(wtftw["path"].as_str() == root.to_str());
+    assert!(wtftw.get("package").is_none());
+    assert!(&parsed["patch"]["https://github.com/Kintaro/wtftw.git"]["wtftw"] == wtftw);
+    assert!(parsed["lib"]["crate-type"][0].as_str() == Some("dylib"));
+}
+
+#[test]
+fn config_module_is_rebuilt_when_its_sources_change() {
+    let dir = env::temp_dir().join(format!("wtftw-config-hash-{}", std::process::id()));
+    fs::create_dir_all(dir.join("src").join("layouts")).unwrap();
+    fs::create_dir_all(dir.join("target").join("release")).unwrap();
+    fs::write(dir.join("Cargo.toml"), config_manifest()).unwrap();
+    fs::write(dir.join("src").join("lib.rs"), "mod layouts;").unwrap();
+    fs::write(dir.join("src").join("layouts").join("mod.rs"), "").unwrap();
+
+    let mut config = Config::initialize().unwrap();
+    config.internal.wtftw_dir = dir.to_str().unwrap().to_owned();
+    let hash = source_hash(&dir).unwrap();
+    fs::write(
+        dir.join("target").join("config.hash"),
+        format!("{:x}", hash),
+    )
+    .unwrap();
+
+    // Nothing to load yet
+    assert!(!config.is_compiled());
+
+    fs::write(dir.join("target").join("release").join("libconfig.so"), "").unwrap();
+    assert!(source_hash(&dir).unwrap() == hash);
+    assert!(config.is_compiled());
+    assert!(config.compile().is_ok());
+
+    fs::write(dir.join("src").join("layouts").join("mod.rs"), "// changed").unwrap();
+    assert!(source_hash(&dir).unwrap() != hash);
+    assert!(!config.is_compiled());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn compile_errors_are_kept_and_reported() {
+    let ws = MockWindowSystem::new(vec![Rectangle(0, 0, 800, 600)]);
+    let dir = env::temp_dir().join(format!("wtftw-config-errors-{}", std::process::id()));
+    fs::create_dir_all(dir.join("src")).unwrap();
+    fs::write(
+        dir.join("Cargo.toml"),
+        "[package]\nname = \"config\"\nversion = \"0.0.0\"\n[lib]\ncrate-type = [\"dylib\"]\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("src").join("lib.rs"),
+        "pub fn configure() { missing() }",
+    )
+    .unwrap();
+
+    let mut config = Config::initialize().unwrap();
+    config.internal.wtftw_dir = dir.to_str().unwrap().to_owned();
+
+    let error = format!("{:#}", config.compile().unwrap_err());
+    let log = fs::read_to_string(dir.join(ERROR_LOG)).unwrap();
+    assert!(log.contains("cannot find function `missing`"));
+    assert!(error.contains(ERROR_LOG));
+    assert!(error.contains("error[E0425]: cannot find function `missing`"));
+    assert!(!error.contains("could not compile"));
+    assert!(config.check().is_err());
+
+    // Hand-written manifests are left alone, and the defaults are used
+    let mut wm = WindowManager::new(&ws, &config.general);
+    assert!(config.compile_and_call(&mut wm, &ws).is_err());
+    assert!(!fs::read_to_string(dir.join("Cargo.toml"))
+        .unwrap()
+        .starts_with("# Generated"));
+    assert!(config.internal.library.is_none());
+    assert!(!config.internal.key_handlers.is_empty());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn checking_without_config_module_creates_the_manifest() {
+    let dir = env::temp_dir().join(format!("wtftw-config-check-{}", std::process::id()));
+
+    let mut config = Config::initialize().unwrap();
+    config.internal.wtftw_dir = dir.to_str().unwrap().to_owned();
+
+    assert!(!config.check().unwrap());
+    assert!(fs::read_to_string(dir.join("Cargo.toml")).unwrap() == config_manifest());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn manifests_of_older_versions_are_replaced() {
+    let dir = env::temp_dir().join(format!("wtftw-config-legacy-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(
+        dir.join("Cargo.toml"),
+        "[project]\n\
+         name = \"config\"\n\
+         version = \"0.0.0\"\n\
+         authors = [\"wtftw\"]\n\n\
+         [dependencies.wtftw_contrib]
+         git = \"https://github.com/Kintaro/wtftw-contrib.git\"\n
+         [dependencies.wtftw]\n\
+         git = \"https://github.com/Kintaro/wtftw.git\"\n\n\
+         [lib]\n\
+         name = \"config\"\n\
+         crate-type = [\"dylib\"]",
+    )
+    .unwrap();
+
+    let mut config = Config::initialize().unwrap();
+    config.internal.wtftw_dir = dir.to_str().unwrap().to_owned();
+
+    assert!(!config.check().unwrap());
+    assert!(fs::read_to_string(dir.join("Cargo.toml")).unwrap() == config_manifest());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn notices_are_shown_without_a_log_hook() {
+    let ws = Rc::new(MockWindowSystem::new(vec![Rectangle(0, 0, 800, 600)]));
+    let mut config = Config::initialize().unwrap();
+    let wm = WindowManager::new(ws.deref(), &config.general)
+        .notify(Some("config module failed".to_owned()));
+
+    config.log(&wm, ws.clone());
+    assert!(ws.shown_notice() == Some("config module failed".to_owned()));
+    config.log(&wm.notify(None), ws.clone());
+    assert!(ws.shown_notice().is_none());
+
+    // Status bars show notices themselves
+    let logged = Rc::new(Cell::new(0));
+    let count = logged.clone();
+    config.set_log_hook(Box::new(move |_, _| count.set(count.get() + 1)));
+    config.log(&wm, ws.clone());
+    assert!(logged.get() == 1);
+    assert!(ws.shown_notice().is_none());
+}
@@ -0,0 +1,290 @@
+extern crate wtftw_core;
+
+use self::wtftw_core::config::Config;
+use self::wtftw_core::config_file::{command_line, load};
+use self::wtftw_core::layout::{save_layout, LayoutMessage};
+use self::wtftw_core::mock_window_system::{MockWindow, MockWindowSystem};
+use self::wtftw_core::window_manager::{WindowManager, WindowManagerEvent};
+use self::wtftw_core::window_system::*;
+use std::ops::Deref;
+use std::rc::Rc;
+
+const CONFIG: &str = r##"
+mod_mask = "mod4"
+terminal = "urxvt -e tmux"
+launcher = "rofi"
+focus_follows_mouse = false
+focus_border_color = "#00b6ff"
+border_color = 0x444444
+border_width = 3
+tags = ["web", "code", "chat"]
+layout = { name = "Tall", ratio = 0.6 }
+
+[[keys]]
+key = "M-S-Return"
+action = "spawn-terminal"
+
+[[keys]]
+key = "<XF86AudioMute>"
+modifiers = ["control"]
+action = "spawn amixer -q set Master toggle"
+
+[[keys]]
+key = "j"
+modifiers = ["mod"]
+action = "focus-down"
+
+[[keys]]
+key = "2"
+modifiers = ["mod"]
+action = "view code"
+
+[[keys]]
+key = "M-x 3"
+action = "view chat"
+
+[[keys]]
+key = "M-r"
+action = "mode resize"
+
+[[keys]]
+key = "l"
+mode = "resize"
+action = "layout increase"
+
+[[mouse]]
+button = 1
+modifiers = ["mod"]
+action = "move"
+
+[[rules]]
+class = "Gimp"
+action = "center-float"
+
+[[rules]]
+class = "Steam"
+title = { contains = "Friends" }
+actions = ["shift chat", "border 0"]
+"##;
+
+fn setup() -> (Rc<MockWindowSystem>, Config) {
+    let window_system = Rc::new(MockWindowSystem::new(vec![Rectangle(0, 0, 800, 600)]));
+    let config = Config::initialize().unwrap();
+    (window_system, config)
+}
+
+fn key(config: &Config, key: char, mask: KeyModifiers) -> bool {
+    config
+        .internal
+        .key_handlers
+        .contains_key(&KeyCommand::new(key as u64, mask))
+}
+
+#[test]
+fn configuration_file_sets_options() {
+    let (ws, mut config) = setup();
+    load(&mut config, ws.deref(), CONFIG).unwrap();
+
+    let general = &config.general;
+    assert!(general.mod_mask == KeyModifiers::MOD4MASK);
+    assert!(general.terminal == ("urxvt".to_owned(), "-e tmux".to_owned()));
+    assert!(general.launcher == "rofi");
+    assert!(!general.focus_follows_mouse);
+    assert!(general.focus_border_color == 0x00b6ff);
+    assert!(general.border_color == 0x444444);
+    assert!(general.border_width == 3);
+    assert!(general.tags == vec!["web", "code", "chat"]);
+    assert!(general.layout.description() == "Tall");
+    assert!(general.layout.serialize().unwrap()["ratio"] == 0.6f32 as f64);
+    assert!(general.layout.serialize().unwrap()["num_master"] == 1);
+
+    let shift = KeyModifiers::MOD4MASK | KeyModifiers::SHIFTMASK;
+    assert!(config
+        .internal
+        .key_handlers
+        .contains_key(&KeyCommand::new(0xff0d, shift)));
+    assert!(config
+        .internal
+        .key_handlers
+        .contains_key(&KeyCommand::new(0x1008ff12, KeyModifiers::CONTROLMASK)));
+    assert!(key(&config, 'j', KeyModifiers::MOD4MASK));
+    assert!(key(&config, '2', KeyModifiers::MOD4MASK));
+    assert!(config
+        .internal
+        .mouse_handlers
+        .contains_key(&MouseCommand::new(BUTTON1, KeyModifiers::MOD4MASK)));
+}
+
+#[test]
+fn configuration_file_bindings_and_rules_work() {
+    let (ws, mut config) = setup();
+    load(&mut config, ws.deref(), CONFIG).unwrap();
+    let window_system: Rc<dyn WindowSystem> = ws.clone();
+    let manage = |wm: WindowManager, window: Window| {
+        wm.manage_with(ws.deref(), window, &config.general, &|x| {
+            (config.internal.manage_hook)(x.clone(), window_system.clone(), window)
+        })
+    };
+    let run = |wm: WindowManager, key: char| {
+        let handler =
+            &config.internal.key_handlers[&KeyCommand::new(key as u64, KeyModifiers::MOD4MASK)];
+        handler(wm, window_system.clone(), &config.general)
+    };
+
+    let gimp = ws.add_window(MockWindow {
+        class: "Gimp".to_owned(),
+        ..MockWindow::new(Rectangle(0, 0, 400, 300))
+    });
+    let steam = ws.add_window(MockWindow {
+        class: "Steam".to_owned(),
+        name: "Friends List".to_owned(),
+        ..MockWindow::new(Rectangle(0, 0, 400, 300))
+    });
+    let other = ws.add_window(MockWindow {
+        class: "Steam".to_owned(),
+        name: "Store".to_owned(),
+        ..MockWindow::new(Rectangle(0, 0, 400, 300))
+    });
+
+    let wm = WindowManager::new(ws.deref(), &config.general);
+    let wm = manage(manage(manage(wm, gimp), steam), other);
+    assert!(wm.workspaces.floating.contains_key(&gimp));
+    assert!(wm.workspaces.find_tag(steam) == Some(2));
+    assert!(wm.workspaces.borders.get(&steam) == Some(&0));
+    assert!(wm.workspaces.find_tag(other) == Some(0));
+
+    assert!(wm.workspaces.peek() == Some(other));
+    let wm = run(wm, 'j');
+    assert!(wm.workspaces.peek() == Some(gimp));
+
+    let wm = run(wm, '2');
+    assert!(wm.workspaces.current_tag() == 1);
+
+    let press = |wm, key: char, mask| {
+        config.handle_key(wm, window_system.clone(), KeyCommand::new(key as u64, mask))
+    };
+    let wm = press(wm, 'x', KeyModifiers::MOD4MASK);
+    let wm = press(wm, '3', KeyModifiers::NONEMASK);
+    assert!(wm.workspaces.current_tag() == 2);
+
+    let wm = press(wm, 'r', KeyModifiers::MOD4MASK);
+    assert!(wm.mode == Some("resize".to_owned()));
+    let wm = press(wm, 'l', KeyModifiers::NONEMASK);
+    let wm = press(wm, 'l', KeyModifiers::NONEMASK);
+    let ratio = wm.workspaces.current.workspace.layout.serialize().unwrap()["ratio"]
+        .as_f64()
+        .unwrap();
+    assert!((ratio - 0.7).abs() < 0.001);
+    let wm = config.handle_key(
+        wm,
+        window_system.clone(),
+        KeyCommand::new(0xff1b, KeyModifiers::NONEMASK),
+    );
+    assert!(wm.mode.is_none());
+}
+
+#[test]
+fn invalid_configuration_files_change_nothing() {
+    let (ws, mut config) = setup();
+    let invalid = |source: &str| {
+        let mut config = Config::initialize().unwrap();
+        load(&mut config, ws.deref(), source).is_err()
+    };
+
+    assert!(invalid("border_width = -1"));
+    assert!(invalid("border_colour = 3"));
+    assert!(invalid("focus_border_color = \"blue\""));
+    assert!(invalid("tags = []"));
+    assert!(invalid("mod_mask = \"hyper\""));
+    assert!(invalid("layout = \"Accordion\""));
+    assert!(invalid("layout = { name = \"Tall\", gap = 3 }"));
+    assert!(invalid("layout = { name = \"Tall\", ratio = 1.5 }"));
+    assert!(invalid("layout = []"));
+    assert!(invalid("layout = [\"Tall\", \"Accordion\"]"));
+    assert!(invalid(
+        "layout = { name = \"Tall\", num_master = \"two\" }"
+    ));
+    assert!(invalid("[[keys]]\nkey = \"NoSuchKey\"\naction = \"exit\""));
+    assert!(invalid("[[keys]]\nkey = \"H-j\"\naction = \"exit\""));
+    assert!(invalid("[[keys]]\nkey = \"j\"\naction = \"dance\""));
+    assert!(invalid("[[keys]]\nkey = \"j\"\naction = \"get-tree\""));
+    assert!(invalid("[[keys]]\nkey = \"j\"\naction = \"mode resize\""));
+    assert!(invalid("[[keys]]\nkey = \"j\"\naction = \"spawn 'xterm\""));
+    assert!(invalid(
+        "[[keys]]\nkey = \"M-x j\"\nmodifiers = [\"shift\"]\naction = \"exit\""
+    ));
+    assert!(invalid(
+        "[[keys]]\nkey = \"j\"\nmodifiers = [\"meta\"]\naction = \"exit\""
+    ));
+    assert!(invalid("[[mouse]]\nbutton = 9\naction = \"move\""));
+    assert!(invalid("[[rules]]\nclass = \"Gimp\""));
+    assert!(invalid("[[rules]]\nclass = \"Gimp\"\naction = \"hover\""));
+    assert!(invalid("[[rules]]\ncolour = \"red\"\naction = \"float\""));
+    assert!(invalid("[[rules]]\ntype = \"window\"\naction = \"float\""));
+
+    let source = "border_width = 7\n[[keys]]\nkey = \"j\"\naction = \"dance\"";
+    assert!(load(&mut config, ws.deref(), source).is_err());
+    assert!(config.general.border_width == 2);
+    assert!(config.internal.key_handlers.is_empty());
+}
+
+#[test]
+fn layout_lists_are_switched_with_next_and_prev() {
+    let (ws, mut config) = setup();
+    let source = "layout = [{ name = \"Tall\", ratio = 0.6 }, \"Grid\"]";
+    load(&mut config, ws.deref(), source).unwrap();
+
+    let layout = &mut config.general.layout;
+    assert!(layout.description() == "Tall");
+    assert!(save_layout(layout.as_ref())["description"] == "Choose");
+    let state = layout.serialize().unwrap();
+    assert!(state["current"] == 0);
+    assert!(state["layouts"][0]["description"] == "Tall");
+    assert!(state["layouts"][0]["state"]["ratio"] == 0.6f32 as f64);
+    assert!(state["layouts"][1]["description"] == "Grid");
+
+    let general = Config::initialize().unwrap().general;
+    assert!(layout.apply_message(LayoutMessage::Next, ws.deref(), &None, &general));
+    assert!(layout.serialize().unwrap()["current"] == 1);
+    assert!(layout.description() == "Grid");
+    assert!(layout.apply_message(LayoutMessage::Next, ws.deref(), &None, &general));
+    assert!(layout.serialize().unwrap()["current"] == 0);
+    assert!(layout.apply_message(LayoutMessage::Prev, ws.deref(), &None, &general));
+    assert!(layout.serialize().unwrap()["current"] == 1);
+
+    let registry = &config.internal.layouts;
+    let rebuilt = registry.build(&save_layout(layout.as_ref())).unwrap();
+    assert!(rebuilt.serialize() == layout.serialize());
+}
+
+#[test]
+fn switching_layout_lists_emits_the_new_layout() {
+    let (ws, mut config) = setup();
+    load(&mut config, ws.deref(), "layout = [\"Tall\", \"Grid\"]").unwrap();
+    let wm = WindowManager::new(ws.deref(), &config.general);
+    let id = wm.workspaces.current.workspace.id;
+
+    let wm = wm.send_layout_message(LayoutMessage::Next, ws.deref(), &config.general);
+    assert!(wm.events == vec![WindowManagerEvent::LayoutChanged(id, "Grid".to_owned())]);
+
+    let wm = WindowManager {
+        events: Vec::new(),
+        ..wm
+    }
+    .send_layout_message(LayoutMessage::Prev, ws.deref(), &config.general);
+    assert!(wm.events == vec![WindowManagerEvent::LayoutChanged(id, "Tall".to_owned())]);
+}
+
+#[test]
+fn commands_are_split_like_a_shell_would() {
+    let split = |s: &str| command_line(s).ok();
+    let words = |w: &[&str]| Some(w.iter().map(|x| x.to_string()).collect::<Vec<_>>());
+
+    assert!(split("amixer -q  set Master") == words(&["amixer", "-q", "set", "Master"]));
+    assert!(split("notify-send 'two words'") == words(&["notify-send", "two words"]));
+    assert!(split(r#"echo "say \"hi\"" a\ b ''"#) == words(&["echo", "say \"hi\"", "a b", ""]));
+    assert!(split(r#"echo "\n" 'it'\''s'"#) == words(&["echo", "\\n", "it's"]));
+    assert!(split("echo 'open").is_none());
+    assert!(split("echo \"open").is_none());
+    assert!(split("echo \\").is_none());
+}
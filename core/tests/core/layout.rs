@@ -0,0 +1,587 @@
+extern crate wtftw_core;
+
+use self::wtftw_core::config::{Config, GeneralConfig};
+use self::wtftw_core::core::stack::Stack;
+use self::wtftw_core::layout::*;
+use self::wtftw_core::mock_window_system::MockWindowSystem;
+use self::wtftw_core::window_system::*;
+use serde_json::json;
+
+/// A layout without parameters, to check registering others
+pub struct Column;
+
+impl Layout for Column {
+    fn apply_layout(
+        &mut self,
+        _: &dyn WindowSystem,
+        _: Rectangle,
+        _: &GeneralConfig,
+        _: &Option<Stack<Window>>,
+    ) -> Vec<(Window, Rectangle)> {
+        Vec::new()
+    }
+
+    fn description(&self) -> String {
+        "Column".to_owned()
+    }
+
+    fn copy(&self) -> Box<dyn Layout> {
+        Box::new(Column)
+    }
+}
+
+#[test]
+fn tall_layout_roundtrip() {
+    let tall = TallLayout {
+        num_master: 3,
+        increment_ratio: 0.05,
+        ratio: 0.625,
+    };
+    let saved = save_layout(&tall);
+    assert!(saved["description"] == "Tall");
+
+    let registry = LayoutRegistry::new();
+    let rebuilt = registry.build(&saved).unwrap();
+    assert!(rebuilt.description() == "Tall");
+    assert!(save_layout(rebuilt.as_ref()) == saved);
+
+    let mut default = TallLayout::boxed_new();
+    assert!(default.deserialize(&saved["state"], &registry));
+    assert!(default.serialize() == tall.serialize());
+
+    for &ratio in [0.0, 1.0, 1.5, -0.5].iter() {
+        let mut invalid = saved.clone();
+        invalid["state"]["ratio"] = json!(ratio);
+        assert!(registry.build(&invalid).is_none());
+        assert!(!default.deserialize(&invalid["state"], &registry));
+    }
+    assert!(default.serialize() == tall.serialize());
+}
+
+#[test]
+fn layout_registry_rejects_unknown_layouts() {
+    let mut registry = LayoutRegistry::new();
+    let column = json!({ "description": "Column", "state": null });
+
+    assert!(registry.build(&column).is_none());
+    assert!(registry.build(&json!({ "state": null })).is_none());
+    assert!(registry
+        .build(&json!({ "description": "Tall", "state": { "ratio": "wide" } }))
+        .is_none());
+
+    registry.register("Column", Box::new(|| Box::new(Column)));
+    assert!(registry.contains("Column"));
+    assert!(Column.serialize().is_none());
+    assert!(save_layout(&Column) == column);
+    assert!(registry.build(&column).unwrap().description() == "Column");
+}
+
+const SCREENS: [Rectangle; 3] = [
+    Rectangle(0, 0, 1920, 1080),
+    Rectangle(1920, 0, 1080, 1920),
+    Rectangle(10, 20, 801, 599),
+];
+
+/// Lay out 0 to 20 windows on every screen in `SCREENS`
+fn arrange(layout: &mut dyn Layout) -> Vec<(Rectangle, Vec<(Window, Rectangle)>)> {
+    let ws = MockWindowSystem::new(SCREENS.to_vec());
+    let config = Config::initialize().unwrap();
+    let mut arranged = Vec::new();
+
+    for &screen in SCREENS.iter() {
+        arranged.push((
+            screen,
+            layout.apply_layout(&ws, screen, &config.general, &None),
+        ));
+        for n in 1..=20 {
+            let stack = Some(Stack::new(1, Vec::new(), (2..=n).collect()));
+            arranged.push((
+                screen,
+                layout.apply_layout(&ws, screen, &config.general, &stack),
+            ));
+        }
+    }
+    arranged
+}
+
+/// Check that every window got a place, and that together they fill
+/// the screen without overlapping
+fn assert_covers(screen: Rectangle, windows: &[(Window, Rectangle)]) {
+    let Rectangle(sx, sy, sw, sh) = screen;
+    let area: u64 = windows
+        .iter()
+        .map(|&(_, Rectangle(_, _, w, h))| w as u64 * h as u64)
+        .sum();
+
+    assert!(windows
+        .iter()
+        .map(|&(w, _)| w)
+        .eq(1..=windows.len() as Window));
+    if windows.is_empty() {
+        return;
+    }
+    assert!(area == sw as u64 * sh as u64);
+    for (i, &(_, r)) in windows.iter().enumerate() {
+        let Rectangle(x, y, w, h) = r;
+        assert!(w > 0 && h > 0);
+        assert!(x >= sx && y >= sy);
+        assert!(x + w as i32 <= sx + sw as i32 && y + h as i32 <= sy + sh as i32);
+        assert!(windows[i + 1..]
+            .iter()
+            .all(|&(_, other)| !r.overlaps(&other)));
+    }
+}
+
+#[test]
+fn grid_layout_covers_the_screen() {
+    let ws = MockWindowSystem::new(SCREENS.to_vec());
+    let config = Config::initialize().unwrap();
+
+    for &(master, aspect) in [(false, 1.0), (true, 1.0), (false, 3.0), (true, 0.25)].iter() {
+        let mut grid = GridLayout {
+            aspect,
+            column_bias: 0,
+            master,
+            ratio: 0.6,
+        };
+        for _ in 0..3 {
+            for (screen, windows) in arrange(&mut grid) {
+                assert_covers(screen, &windows);
+            }
+            assert!(grid.apply_message(LayoutMessage::Increase, &ws, &None, &config.general));
+        }
+        for _ in 0..6 {
+            assert!(grid.apply_message(LayoutMessage::Decrease, &ws, &None, &config.general));
+        }
+        for (screen, windows) in arrange(&mut grid) {
+            assert_covers(screen, &windows);
+        }
+    }
+}
+
+#[test]
+fn grid_layout_is_near_square() {
+    let ws = MockWindowSystem::new(SCREENS.to_vec());
+    let config = Config::initialize().unwrap();
+    let screen = Rectangle(0, 0, 1200, 900);
+    let stack = |n| Some(Stack::new(1, Vec::new(), (2..=n).collect()));
+    let mut grid = GridLayout {
+        aspect: 1.0,
+        column_bias: 0,
+        master: false,
+        ratio: 0.5,
+    };
+
+    // Four windows in two columns, five in three with the extra ones on the right
+    let four = grid.apply_layout(&ws, screen, &config.general, &stack(4));
+    assert!(four[0].1 == Rectangle(0, 0, 600, 450));
+    assert!(four[3].1 == Rectangle(600, 450, 600, 450));
+    let five = grid.apply_layout(&ws, screen, &config.general, &stack(5));
+    assert!(five[0].1 == Rectangle(0, 0, 400, 900));
+    assert!(five[2].1 == Rectangle(400, 450, 400, 450));
+
+    // One more column, and the master window beside the grid
+    grid.apply_message(LayoutMessage::Increase, &ws, &None, &config.general);
+    let four = grid.apply_layout(&ws, screen, &config.general, &stack(4));
+    assert!(four[0].1 == Rectangle(0, 0, 400, 900));
+    grid.master = true;
+    let four = grid.apply_layout(&ws, screen, &config.general, &stack(4));
+    assert!(four[0].1 == Rectangle(0, 0, 600, 900));
+    assert!(four[1].1 == Rectangle(600, 0, 300, 900));
+}
+
+#[test]
+fn grid_layout_keeps_its_bias() {
+    let ws = MockWindowSystem::new(SCREENS.to_vec());
+    let config = Config::initialize().unwrap();
+    let screen = Rectangle(0, 0, 1200, 900);
+    let four = Some(Stack::new(1, Vec::new(), vec![2, 3, 4]));
+    let eight = Some(Stack::new(1, Vec::new(), vec![2, 3, 4, 5, 6, 7, 8]));
+    let mut grid = GridLayout {
+        aspect: 1.0,
+        column_bias: 0,
+        master: false,
+        ratio: 0.5,
+    };
+
+    // Four windows fit in two columns, and at most four
+    for _ in 0..5 {
+        grid.apply_message(LayoutMessage::Increase, &ws, &None, &config.general);
+    }
+    let rects = grid.apply_layout(&ws, screen, &config.general, &four);
+    assert!(rects[0].1 == Rectangle(0, 0, 300, 900));
+    assert!(grid.column_bias == 5);
+
+    // Eight fit in three, and still get all the columns asked for
+    let rects = grid.apply_layout(&ws, screen, &config.general, &eight);
+    assert!(rects[0].1 == Rectangle(0, 0, 150, 900));
+
+    for _ in 0..5 {
+        grid.apply_message(LayoutMessage::Decrease, &ws, &None, &config.general);
+    }
+    let rects = grid.apply_layout(&ws, screen, &config.general, &four);
+    assert!(rects[0].1 == Rectangle(0, 0, 600, 450));
+
+    for _ in 0..6 {
+        grid.apply_message(LayoutMessage::Decrease, &ws, &None, &config.general);
+    }
+    let rects = grid.apply_layout(&ws, screen, &config.general, &four);
+    assert!(rects[0].1 == Rectangle(0, 0, 1200, 225));
+    assert!(grid.column_bias == -6);
+}
+
+#[test]
+fn grid_layout_roundtrip() {
+    let grid = GridLayout {
+        aspect: 1.5,
+        column_bias: -1,
+        master: true,
+        ratio: 0.625,
+    };
+    let registry = LayoutRegistry::new();
+    let rebuilt = registry.build(&save_layout(&grid)).unwrap();
+
+    assert!(rebuilt.description() == "Grid");
+    assert!(rebuilt.serialize() == grid.serialize());
+    assert!(registry
+        .build(
+            &json!({ "description": "Grid", "state": { "aspect": 0.0, "column_bias": 0,
+                                                          "master": false, "ratio": 0.5 } })
+        )
+        .is_none());
+    assert!(registry
+        .build(
+            &json!({ "description": "Grid", "state": { "aspect": 1.0, "column_bias": 0,
+                                                          "master": true, "ratio": 1.5 } })
+        )
+        .is_none());
+}
+
+#[test]
+fn fibonacci_layouts_cover_the_screen() {
+    let ws = MockWindowSystem::new(SCREENS.to_vec());
+    let config = Config::initialize().unwrap();
+
+    for mut layout in [SpiralLayout::boxed_new(), DwindleLayout::boxed_new()] {
+        for message in vec![LayoutMessage::Increase; 12]
+            .into_iter()
+            .chain(vec![LayoutMessage::Decrease; 20])
+        {
+            for (screen, windows) in arrange(layout.as_mut()) {
+                assert_covers(screen, &windows);
+            }
+            assert!(layout.apply_message(message, &ws, &None, &config.general));
+        }
+        assert!(!layout.apply_message(LayoutMessage::Next, &ws, &None, &config.general));
+    }
+}
+
+#[test]
+fn spiral_goes_round_and_dwindle_goes_down() {
+    let ws = MockWindowSystem::new(SCREENS.to_vec());
+    let config = Config::initialize().unwrap();
+    let screen = Rectangle(0, 0, 1600, 800);
+    let stack = Some(Stack::new(1, Vec::new(), vec![2, 3, 4]));
+    let rects = |layout: &mut dyn Layout| -> Vec<Rectangle> {
+        layout
+            .apply_layout(&ws, screen, &config.general, &stack)
+            .into_iter()
+            .map(|(_, r)| r)
+            .collect()
+    };
+
+    let mut spiral = SpiralLayout::boxed_new();
+    assert!(
+        rects(spiral.as_mut())
+            == vec![
+                Rectangle(0, 0, 800, 800),
+                Rectangle(800, 0, 800, 400),
+                Rectangle(1200, 400, 400, 400),
+                Rectangle(800, 400, 400, 400),
+            ]
+    );
+
+    let mut dwindle = DwindleLayout::boxed_new();
+    assert!(
+        rects(dwindle.as_mut())
+            == vec![
+                Rectangle(0, 0, 800, 800),
+                Rectangle(800, 0, 800, 400),
+                Rectangle(800, 400, 400, 400),
+                Rectangle(1200, 400, 400, 400),
+            ]
+    );
+
+    dwindle.apply_message(LayoutMessage::Increase, &ws, &None, &config.general);
+    assert!(rects(dwindle.as_mut())[0] == Rectangle(0, 0, 880, 800));
+}
+
+#[test]
+fn fibonacci_layouts_roundtrip() {
+    let registry = LayoutRegistry::new();
+    let spiral = FibonacciLayout {
+        spiral: true,
+        ratio: 0.625,
+        increment_ratio: 0.1,
+    };
+    let rebuilt = registry.build(&save_layout(&spiral)).unwrap();
+    assert!(rebuilt.description() == "Spiral");
+    assert!(rebuilt.serialize() == spiral.serialize());
+    assert!(save_layout(&SpiralLayout(spiral)) == save_layout(&spiral));
+    assert!(save_layout(&DwindleLayout::new())["description"] == "Dwindle");
+
+    let dwindle =
+        json!({ "description": "Dwindle", "state": { "ratio": 0.4, "increment_ratio": 0.05 } });
+    assert!(registry.build(&dwindle).unwrap().description() == "Dwindle");
+    assert!(registry
+        .build(
+            &json!({ "description": "Dwindle", "state": { "ratio": 1.0, "increment_ratio": 0.05 } })
+        )
+        .is_none());
+}
+
+#[test]
+fn column_layouts_cover_the_screen() {
+    let ws = MockWindowSystem::new(SCREENS.to_vec());
+    let config = Config::initialize().unwrap();
+    let left = ThreeColumnLayout {
+        num_master: 1,
+        increment_ratio: 0.05,
+        ratio: 0.4,
+        middle: false,
+    };
+
+    for mut layout in [
+        ThreeColumnLayout::boxed_new(),
+        Box::new(left),
+        CenteredMasterLayout::boxed_new(),
+    ] {
+        for message in [
+            LayoutMessage::DecreaseMaster,
+            LayoutMessage::IncreaseMaster,
+            LayoutMessage::IncreaseMaster,
+            LayoutMessage::Increase,
+            LayoutMessage::DecreaseMaster,
+            LayoutMessage::Decrease,
+            LayoutMessage::Decrease,
+        ] {
+            assert!(layout.apply_message(message, &ws, &None, &config.general));
+            for (screen, windows) in arrange(layout.as_mut()) {
+                assert_covers(screen, &windows);
+            }
+        }
+        assert!(!layout.apply_message(LayoutMessage::Next, &ws, &None, &config.general));
+    }
+}
+
+#[test]
+fn column_layouts_put_the_master_in_the_middle() {
+    let ws = MockWindowSystem::new(SCREENS.to_vec());
+    let config = Config::initialize().unwrap();
+    let screen = Rectangle(0, 0, 1600, 900);
+    let rects = |layout: &mut dyn Layout, n| -> Vec<Rectangle> {
+        let stack = Some(Stack::new(1, Vec::new(), (2..=n).collect()));
+        layout
+            .apply_layout(&ws, screen, &config.general, &stack)
+            .into_iter()
+            .map(|(_, r)| r)
+            .collect()
+    };
+    let (top, bottom) = (
+        |x| Rectangle(x, 0, 400, 450),
+        |x| Rectangle(x, 450, 400, 450),
+    );
+
+    let mut three = ThreeColumnLayout::boxed_new();
+    assert!(
+        rects(three.as_mut(), 5)
+            == vec![
+                Rectangle(400, 0, 800, 900),
+                top(1200),
+                bottom(1200),
+                top(0),
+                bottom(0)
+            ]
+    );
+    assert!(
+        rects(three.as_mut(), 2) == vec![Rectangle(0, 0, 800, 900), Rectangle(800, 0, 800, 900)]
+    );
+
+    let mut left = ThreeColumnLayout {
+        num_master: 1,
+        increment_ratio: 0.05,
+        ratio: 0.5,
+        middle: false,
+    };
+    assert!(
+        rects(&mut left, 5)
+            == vec![
+                Rectangle(0, 0, 800, 900),
+                top(800),
+                bottom(800),
+                top(1200),
+                bottom(1200)
+            ]
+    );
+
+    let mut centered = CenteredMasterLayout::boxed_new();
+    assert!(
+        rects(centered.as_mut(), 5)
+            == vec![
+                Rectangle(400, 0, 800, 900),
+                top(1200),
+                top(0),
+                bottom(1200),
+                bottom(0)
+            ]
+    );
+
+    // Two masters share the middle column, the third window is alone on the right
+    centered.apply_message(LayoutMessage::IncreaseMaster, &ws, &None, &config.general);
+    assert!(
+        rects(centered.as_mut(), 3)
+            == vec![
+                Rectangle(0, 0, 800, 450),
+                Rectangle(0, 450, 800, 450),
+                Rectangle(800, 0, 800, 900)
+            ]
+    );
+}
+
+#[test]
+fn column_layouts_roundtrip() {
+    let registry = LayoutRegistry::new();
+    let three = ThreeColumnLayout {
+        num_master: 2,
+        increment_ratio: 0.1,
+        ratio: 0.625,
+        middle: false,
+    };
+    let centered = CenteredMasterLayout {
+        num_master: 3,
+        increment_ratio: 0.1,
+        ratio: 0.375,
+    };
+
+    for layout in [&three as &dyn Layout, &centered] {
+        let rebuilt = registry.build(&save_layout(layout)).unwrap();
+        assert!(rebuilt.description() == layout.description());
+        assert!(rebuilt.serialize() == layout.serialize());
+
+        for &ratio in [0.0, 1.0, 1.5, -0.5].iter() {
+            let mut invalid = save_layout(layout);
+            invalid["state"]["ratio"] = json!(ratio);
+            assert!(registry.build(&invalid).is_none());
+        }
+    }
+}
+
+#[test]
+fn three_columns_never_exceed_the_screen() {
+    let screen = Rectangle(0, 0, 1200, 900);
+    for &ratio in [1.0, 1.5].iter() {
+        let rects = three_columns(ratio, 1, true, false, screen, 4);
+        assert!(rects.len() == 4);
+        assert!(rects[0] == Rectangle(0, 0, 1200, 900));
+    }
+}
+
+#[test]
+fn resizable_tall_layout_covers_the_screen() {
+    let ws = MockWindowSystem::new(SCREENS.to_vec());
+    let config = Config::initialize().unwrap();
+    let mut layout = ResizableTallLayout::boxed_new();
+
+    for focus in 1..=6 {
+        let stack = Some(Stack::new(
+            focus,
+            (1..focus).rev().collect(),
+            (focus + 1..=6).collect(),
+        ));
+        let message = if focus % 2 == 0 {
+            LayoutMessage::IncreaseSlave
+        } else {
+            LayoutMessage::DecreaseSlave
+        };
+        for _ in 0..(focus * 3) {
+            layout.apply_message(message, &ws, &stack, &config.general);
+        }
+        layout.apply_message(LayoutMessage::IncreaseMaster, &ws, &stack, &config.general);
+        for (screen, windows) in arrange(layout.as_mut()) {
+            assert_covers(screen, &windows);
+        }
+    }
+}
+
+#[test]
+fn resizable_tall_layout_resizes_the_focused_slave() {
+    let ws = MockWindowSystem::new(SCREENS.to_vec());
+    let config = Config::initialize().unwrap();
+    let screen = Rectangle(0, 0, 1000, 900);
+    let on_master = Some(Stack::new(1, Vec::new(), vec![2, 3, 4]));
+    let on_third = Some(Stack::new(3, vec![2, 1], vec![4]));
+    let mut layout = ResizableTallLayout::boxed_new();
+
+    // Slaves start with the same height, like in Tall
+    let rects = layout.apply_layout(&ws, screen, &config.general, &on_third);
+    assert!(rects[2] == (3, Rectangle(500, 300, 500, 300)));
+
+    assert!(!layout.apply_message(
+        LayoutMessage::IncreaseSlave,
+        &ws,
+        &on_master,
+        &config.general
+    ));
+    assert!(!layout.apply_message(LayoutMessage::IncreaseSlave, &ws, &None, &config.general));
+    for _ in 0..4 {
+        assert!(layout.apply_message(
+            LayoutMessage::IncreaseSlave,
+            &ws,
+            &on_third,
+            &config.general
+        ));
+    }
+    assert!(layout.serialize().unwrap()["weights"] == json!([1.0, 2.0]));
+
+    let rects = layout.apply_layout(&ws, screen, &config.general, &on_third);
+    assert!(rects[0] == (1, Rectangle(0, 0, 500, 900)));
+    assert!(rects[1] == (2, Rectangle(500, 0, 500, 225)));
+    assert!(rects[2] == (3, Rectangle(500, 225, 500, 450)));
+    assert!(rects[3] == (4, Rectangle(500, 675, 500, 225)));
+
+    // Weights never reach zero
+    for _ in 0..20 {
+        layout.apply_message(
+            LayoutMessage::DecreaseSlave,
+            &ws,
+            &on_third,
+            &config.general,
+        );
+    }
+    let rects = layout.apply_layout(&ws, screen, &config.general, &on_third);
+    assert_covers(screen, &rects);
+}
+
+#[test]
+fn resizable_tall_layout_roundtrip() {
+    let registry = LayoutRegistry::new();
+    let layout = ResizableTallLayout {
+        num_master: 2,
+        increment_ratio: 0.05,
+        ratio: 0.625,
+        weights: vec![1.0, 0.5, 2.25],
+        increment_weight: 0.25,
+    };
+    let saved = save_layout(&layout);
+    let rebuilt = registry.build(&saved).unwrap();
+
+    assert!(rebuilt.description() == "ResizableTall");
+    assert!(rebuilt.serialize() == layout.serialize());
+
+    let mut invalid = saved.clone();
+    invalid["state"]["weights"] = json!([1.0, 0.0]);
+    assert!(registry.build(&invalid).is_none());
+    let mut invalid = saved.clone();
+    invalid["state"]["ratio"] = json!(1.0);
+    assert!(registry.build(&invalid).is_none());
+}
@@ -1 +1,3 @@
+pub mod layout;
 pub mod stack;
+pub mod workspaces;
@@ -0,0 +1,340 @@
+extern crate quickcheck;
+extern crate wtftw_core;
+
+use self::quickcheck::{quickcheck, Arbitrary, Gen};
+use self::wtftw_core::core::rational_rect::RationalRect;
+use self::wtftw_core::core::stack::Stack;
+use self::wtftw_core::core::workspaces::Workspaces;
+use self::wtftw_core::layout::TallLayout;
+use self::wtftw_core::window_system::{Rectangle, Window};
+use std::collections::BTreeSet;
+use std::fmt::{Debug, Error, Formatter};
+
+/// Wrapper to generate arbitrary, but well-formed workspaces:
+/// between one and nine tags, at most as many screens as tags
+/// and every window contained in exactly one stack.
+#[derive(Clone)]
+struct ArbitraryWorkspaces(Workspaces);
+
+impl Debug for ArbitraryWorkspaces {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        f.write_str(&format!("{:?}", normalise(&self.0)))
+    }
+}
+
+impl Arbitrary for ArbitraryWorkspaces {
+    fn arbitrary(g: &mut Gen) -> ArbitraryWorkspaces {
+        let num_tags = 1 + usize::arbitrary(g) % 9;
+        let num_screens = 1 + usize::arbitrary(g) % num_tags;
+        let tags = (0..num_tags).map(|i| i.to_string()).collect();
+        let screens = (0..num_screens)
+            .map(|i| Rectangle(i as i32 * 100, 0, 100, 100))
+            .collect();
+
+        let mut next_window: Window = 1;
+        let mut stack = |g: &mut Gen| {
+            let len = usize::arbitrary(g) % 6;
+            if len == 0 {
+                return None;
+            }
+            let windows: Vec<Window> = (next_window..next_window + len as Window).collect();
+            next_window += len as Window;
+            let focus = usize::arbitrary(g) % len;
+            Some(Stack::new(
+                windows[focus],
+                windows[..focus].iter().rev().copied().collect(),
+                windows[focus + 1..].to_vec(),
+            ))
+        };
+
+        let mut w = Workspaces::new(TallLayout::boxed_new(), tags, screens);
+        w.current.workspace.stack = stack(g);
+        for s in w.visible.iter_mut() {
+            s.workspace.stack = stack(g);
+        }
+        for h in w.hidden.iter_mut() {
+            h.stack = stack(g);
+        }
+
+        for window in w.all_windows() {
+            if u8::arbitrary(g) % 4 == 0 {
+                w = w.float(window, RationalRect(0.25, 0.25, 0.5, 0.5));
+            }
+        }
+
+        ArbitraryWorkspaces(w.view(u32::arbitrary(g) % num_tags as u32))
+    }
+}
+
+type Normalised = (
+    u32,
+    Vec<(u32, u32, Option<Stack<Window>>)>,
+    Vec<(u32, Option<Stack<Window>>)>,
+    Vec<Window>,
+);
+
+/// Project the workspaces onto a comparable representation.
+/// Visible screens and hidden workspaces are sorted, as their
+/// order carries no meaning.
+fn normalise(w: &Workspaces) -> Normalised {
+    let mut visible: Vec<_> = w
+        .visible
+        .iter()
+        .map(|s| (s.screen_id, s.workspace.id, s.workspace.stack.clone()))
+        .collect();
+    visible.sort_by_key(|x| x.0);
+    let mut hidden: Vec<_> = w.hidden.iter().map(|x| (x.id, x.stack.clone())).collect();
+    hidden.sort_by_key(|x| x.0);
+    let current = (
+        w.current.screen_id,
+        w.current.workspace.id,
+        w.current.workspace.stack.clone(),
+    );
+
+    (
+        w.current_tag(),
+        (vec![current]).into_iter().chain(visible).collect(),
+        hidden,
+        w.floating.keys().copied().collect(),
+    )
+}
+
+fn window_set(w: &Workspaces) -> BTreeSet<Window> {
+    w.all_windows().into_iter().collect()
+}
+
+/// The invariants every operation has to maintain
+fn invariant(w: &Workspaces) -> bool {
+    let windows = w.all_windows();
+    let unique = window_set(w);
+    let tags: BTreeSet<u32> = w.workspaces().iter().map(|x| x.id).collect();
+
+    windows.len() == unique.len()
+        && tags.len() == w.workspaces().len()
+        && w.peek().map_or(true, |f| unique.contains(&f))
+        && w.floating.keys().all(|x| unique.contains(x))
+}
+
+/// Pick a window of the workspaces based on the given seed
+fn pick(w: &Workspaces, seed: usize) -> Option<Window> {
+    let windows = w.all_windows();
+    if windows.is_empty() {
+        None
+    } else {
+        Some(windows[seed % windows.len()])
+    }
+}
+
+fn tag(w: &Workspaces, seed: u32) -> u32 {
+    seed % w.number_workspaces()
+}
+
+#[test]
+fn workspaces_arbitrary_invariant() {
+    fn prop(x: ArbitraryWorkspaces) -> bool {
+        invariant(&x.0)
+    }
+    quickcheck(prop as fn(ArbitraryWorkspaces) -> bool);
+}
+
+#[test]
+fn workspaces_view_invariant() {
+    fn prop(x: ArbitraryWorkspaces, i: u32) -> bool {
+        invariant(&x.0.view(tag(&x.0, i)))
+    }
+    quickcheck(prop as fn(ArbitraryWorkspaces, u32) -> bool);
+}
+
+#[test]
+fn workspaces_view_current() {
+    fn prop(x: ArbitraryWorkspaces, i: u32) -> bool {
+        let i = tag(&x.0, i);
+        x.0.view(i).current_tag() == i
+    }
+    quickcheck(prop as fn(ArbitraryWorkspaces, u32) -> bool);
+}
+
+#[test]
+fn workspaces_view_idempotent() {
+    fn prop(x: ArbitraryWorkspaces, i: u32) -> bool {
+        let v = x.0.view(tag(&x.0, i));
+        normalise(&v.view(tag(&x.0, i))) == normalise(&v)
+    }
+    quickcheck(prop as fn(ArbitraryWorkspaces, u32) -> bool);
+}
+
+#[test]
+fn workspaces_view_reversible() {
+    fn prop(x: ArbitraryWorkspaces, i: u32) -> bool {
+        let n = x.0.current_tag();
+        normalise(&x.0.view(tag(&x.0, i)).view(n)) == normalise(&x.0)
+    }
+    quickcheck(prop as fn(ArbitraryWorkspaces, u32) -> bool);
+}
+
+#[test]
+fn workspaces_view_out_of_range() {
+    fn prop(x: ArbitraryWorkspaces, i: u32) -> bool {
+        let i = x.0.number_workspaces() + i % 10;
+        normalise(&x.0.view(i)) == normalise(&x.0)
+    }
+    quickcheck(prop as fn(ArbitraryWorkspaces, u32) -> bool);
+}
+
+#[test]
+fn workspaces_greedy_view() {
+    fn prop(x: ArbitraryWorkspaces, i: u32) -> bool {
+        let i = tag(&x.0, i);
+        let v = x.0.greedy_view(i);
+        invariant(&v)
+            && v.current_tag() == i
+            && v.current.screen_id == x.0.current.screen_id
+            && window_set(&v) == window_set(&x.0)
+    }
+    quickcheck(prop as fn(ArbitraryWorkspaces, u32) -> bool);
+}
+
+#[test]
+fn workspaces_focus_preserves_windows() {
+    fn prop(x: ArbitraryWorkspaces, seed: usize) -> bool {
+        let w = &x.0;
+        let focused = pick(w, seed).map_or(w.clone(), |win| w.focus_window(win));
+        [
+            w.focus_up(),
+            w.focus_down(),
+            w.swap_up(),
+            w.swap_down(),
+            w.swap_master(),
+            w.shift_master(),
+            focused,
+        ]
+        .iter()
+        .all(|v| invariant(v) && window_set(v) == window_set(w))
+    }
+    quickcheck(prop as fn(ArbitraryWorkspaces, usize) -> bool);
+}
+
+#[test]
+fn workspaces_focus_window() {
+    fn prop(x: ArbitraryWorkspaces, seed: usize) -> bool {
+        match pick(&x.0, seed) {
+            Some(window) => {
+                let v = x.0.focus_window(window);
+                v.peek() == Some(window) && v.find_tag(window) == Some(v.current_tag())
+            }
+            None => true,
+        }
+    }
+    quickcheck(prop as fn(ArbitraryWorkspaces, usize) -> bool);
+}
+
+#[test]
+fn workspaces_focus_up_down_reversible() {
+    fn prop(x: ArbitraryWorkspaces) -> bool {
+        normalise(&x.0.focus_up().focus_down()) == normalise(&x.0)
+            && normalise(&x.0.swap_up().swap_down()) == normalise(&x.0)
+    }
+    quickcheck(prop as fn(ArbitraryWorkspaces) -> bool);
+}
+
+#[test]
+fn workspaces_swap_master_focus() {
+    fn prop(x: ArbitraryWorkspaces) -> bool {
+        let v = x.0.swap_master();
+        v.peek() == x.0.peek() && v.current.workspace.stack.map_or(true, |s| s.up.is_empty())
+    }
+    quickcheck(prop as fn(ArbitraryWorkspaces) -> bool);
+}
+
+#[test]
+fn workspaces_insert_up() {
+    fn prop(x: ArbitraryWorkspaces) -> bool {
+        let window = x.0.all_windows().into_iter().max().unwrap_or(0) + 1;
+        let v = x.0.insert_up(window);
+        invariant(&v)
+            && v.peek() == Some(window)
+            && v.find_tag(window) == Some(x.0.current_tag())
+            && v.all_windows().len() == x.0.all_windows().len() + 1
+    }
+    quickcheck(prop as fn(ArbitraryWorkspaces) -> bool);
+}
+
+#[test]
+fn workspaces_insert_up_existing() {
+    fn prop(x: ArbitraryWorkspaces, seed: usize) -> bool {
+        pick(&x.0, seed).map_or(true, |window| {
+            normalise(&x.0.insert_up(window)) == normalise(&x.0)
+        })
+    }
+    quickcheck(prop as fn(ArbitraryWorkspaces, usize) -> bool);
+}
+
+#[test]
+fn workspaces_insert_delete() {
+    fn prop(x: ArbitraryWorkspaces) -> bool {
+        let window = x.0.all_windows().into_iter().max().unwrap_or(0) + 1;
+        normalise(&x.0.insert_up(window).delete(window)) == normalise(&x.0)
+    }
+    quickcheck(prop as fn(ArbitraryWorkspaces) -> bool);
+}
+
+#[test]
+fn workspaces_delete() {
+    fn prop(x: ArbitraryWorkspaces, seed: usize) -> bool {
+        pick(&x.0, seed).map_or(true, |window| {
+            let v = x.0.delete(window);
+            let mut expected = window_set(&x.0);
+            expected.remove(&window);
+            invariant(&v)
+                && !v.contains(window)
+                && window_set(&v) == expected
+                && v.current_tag() == x.0.current_tag()
+        })
+    }
+    quickcheck(prop as fn(ArbitraryWorkspaces, usize) -> bool);
+}
+
+#[test]
+fn workspaces_shift() {
+    fn prop(x: ArbitraryWorkspaces, i: u32) -> bool {
+        let i = tag(&x.0, i);
+        let v = x.0.shift(i);
+        invariant(&v)
+            && window_set(&v) == window_set(&x.0)
+            && v.current_tag() == x.0.current_tag()
+            && x.0.peek().map_or(true, |w| v.find_tag(w) == Some(i))
+    }
+    quickcheck(prop as fn(ArbitraryWorkspaces, u32) -> bool);
+}
+
+#[test]
+fn workspaces_shift_window() {
+    fn prop(x: ArbitraryWorkspaces, seed: usize, i: u32) -> bool {
+        let i = tag(&x.0, i);
+        pick(&x.0, seed).map_or(true, |window| {
+            let v = x.0.shift_window(i, window);
+            invariant(&v)
+                && window_set(&v) == window_set(&x.0)
+                && v.current_tag() == x.0.current_tag()
+                && v.find_tag(window) == Some(i)
+        })
+    }
+    quickcheck(prop as fn(ArbitraryWorkspaces, usize, u32) -> bool);
+}
+
+#[test]
+fn workspaces_float_sink() {
+    fn prop(x: ArbitraryWorkspaces, seed: usize) -> bool {
+        pick(&x.0, seed).map_or(true, |window| {
+            let floated = x.0.float(window, RationalRect(0.0, 0.0, 0.5, 0.5));
+            let sunk = floated.sink(window);
+            invariant(&floated)
+                && invariant(&sunk)
+                && floated.floating.contains_key(&window)
+                && !sunk.floating.contains_key(&window)
+                && window_set(&floated) == window_set(&x.0)
+                && window_set(&sunk) == window_set(&x.0)
+        })
+    }
+    quickcheck(prop as fn(ArbitraryWorkspaces, usize) -> bool);
+}
@@ -0,0 +1,274 @@
+extern crate wtftw_core;
+
+use self::wtftw_core::config::Config;
+use self::wtftw_core::ipc::{tree, Command, IpcServer};
+use self::wtftw_core::layout::{Direction, LayoutMessage};
+use self::wtftw_core::mock_window_system::{MockWindow, MockWindowSystem};
+use self::wtftw_core::window_manager::{WindowManager, WindowManagerEvent};
+use self::wtftw_core::window_system::*;
+use std::env;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::ops::Deref;
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::UnixStream;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+fn setup() -> (Rc<MockWindowSystem>, Config, WindowManager) {
+    let window_system = Rc::new(MockWindowSystem::new(vec![Rectangle(0, 0, 800, 600)]));
+    let config = Config::initialize().unwrap();
+    let window_manager = WindowManager::new(window_system.deref(), &config.general);
+    (window_system, config, window_manager)
+}
+
+#[test]
+fn commands_are_parsed() {
+    let parse = |s: &str| Command::parse(s).ok();
+
+    assert!(parse("view 2") == Some(Command::View("2".to_owned())));
+    assert!(parse("view  2: web\n") == Some(Command::View("2: web".to_owned())));
+    assert!(parse("move 3") == Some(Command::Move("3".to_owned(), None)));
+    assert!(parse("move 3 0x1a") == Some(Command::Move("3".to_owned(), Some(26))));
+    assert!(parse("move 2: web") == Some(Command::Move("2: web".to_owned(), None)));
+    assert!(parse("move 2: web 0x1a") == Some(Command::Move("2: web".to_owned(), Some(26))));
+    assert!(parse(r#"["view","2:  web"]"#) == Some(Command::View("2:  web".to_owned())));
+    assert!(
+        parse(r#"["move","2:  web","0x1a"]"#)
+            == Some(Command::Move("2:  web".to_owned(), Some(26)))
+    );
+    assert!(
+        parse("layout increase-master") == Some(Command::Layout(LayoutMessage::IncreaseMaster))
+    );
+    assert!(
+        parse("layout tree-expand-towards left")
+            == Some(Command::Layout(LayoutMessage::TreeExpandTowards(
+                Direction::Left
+            )))
+    );
+    assert!(parse("focus 42") == Some(Command::Focus(42)));
+    assert!(parse("float") == Some(Command::Float(None)));
+    assert!(parse("sink 7") == Some(Command::Sink(Some(7))));
+    assert!(parse("close") == Some(Command::Close(None)));
+    assert!(parse("get-tree") == Some(Command::GetTree));
+    assert!(parse("subscribe") == Some(Command::Subscribe(Vec::new())));
+    assert!(
+        parse("subscribe focus title")
+            == Some(Command::Subscribe(vec![
+                "focus".to_owned(),
+                "title".to_owned()
+            ]))
+    );
+    assert!(parse("subscribe dances").is_none());
+    assert!(parse("reload") == Some(Command::Reload));
+    assert!(parse("restart") == Some(Command::Restart));
+    assert!(parse("quit") == Some(Command::Quit));
+
+    assert!(parse("").is_none());
+    assert!(parse("[\"view\"").is_none());
+    assert!(parse("view").is_none());
+    assert!(parse("focus").is_none());
+    assert!(parse("focus window").is_none());
+    assert!(parse("layout sideways").is_none());
+    assert!(parse("layout tree-shrink-from").is_none());
+    assert!(parse("dance").is_none());
+}
+
+#[test]
+fn commands_drive_window_manager() {
+    let (ws, config, wm) = setup();
+    let w1 = ws.add_window(MockWindow::new(Rectangle(0, 0, 100, 100)));
+    let w2 = ws.add_window(MockWindow::new(Rectangle(0, 0, 100, 100)));
+    let wm = wm
+        .manage(ws.deref(), w1, &config.general)
+        .manage(ws.deref(), w2, &config.general);
+    let window_system: Rc<dyn WindowSystem> = ws.clone();
+    let run = |wm: WindowManager, command: Command| {
+        command.execute(wm, window_system.clone(), &config.general)
+    };
+
+    let wm = run(wm, Command::Focus(w1)).unwrap();
+    assert!(wm.workspaces.peek() == Some(w1));
+
+    let wm = run(wm, Command::Float(None)).unwrap();
+    assert!(wm.workspaces.floating.contains_key(&w1));
+    let wm = run(wm, Command::Sink(Some(w1))).unwrap();
+    assert!(!wm.workspaces.floating.contains_key(&w1));
+
+    let wm = run(wm, Command::Move("3: code".to_owned(), Some(w2))).unwrap();
+    assert!(wm.workspaces.find_tag(w2) == Some(2));
+    let wm = run(wm, Command::View("2".to_owned())).unwrap();
+    assert!(wm.workspaces.current_tag() == 2);
+    assert!(ws.is_mapped(w2) && !ws.is_mapped(w1));
+
+    let wm = run(wm, Command::Close(None)).unwrap();
+    assert!(ws.killed_windows() == vec![w2]);
+
+    assert!(run(wm.clone(), Command::View("9000".to_owned())).is_err());
+    assert!(run(wm.clone(), Command::Focus(12345)).is_err());
+    let empty = run(wm.clone(), Command::View("4: media".to_owned())).unwrap();
+    assert!(run(empty, Command::Close(None)).is_err());
+
+    let wm = run(wm, Command::Reload).unwrap();
+    assert!(wm.reload_requested);
+
+    let wm = run(wm, Command::Quit).unwrap();
+    assert!(!wm.running);
+}
+
+#[test]
+fn server_replies_to_clients() {
+    let (ws, config, wm) = setup();
+    let path = env::temp_dir().join(format!("wtftw-test-{}.sock", std::process::id()));
+    let server = IpcServer::new(path.clone()).unwrap();
+
+    let send = |command: &str| {
+        let mut stream = UnixStream::connect(&path).unwrap();
+        writeln!(stream, "{}", command).unwrap();
+        stream
+    };
+    let view = send("view 1");
+    let unknown = send("dance");
+    let get_tree = send("get-tree");
+
+    let wm = server.process(wm, ws.clone(), &config.general);
+    assert!(wm.workspaces.current_tag() == 1);
+
+    let reply = |stream: UnixStream| {
+        let mut line = String::new();
+        BufReader::new(stream).read_line(&mut line).unwrap();
+        line
+    };
+    assert!(reply(view) == "{\"success\":true}\n");
+    assert!(reply(unknown).contains("\"success\":false"));
+    assert!(reply(get_tree).contains("\"tree\":{"));
+
+    drop(server);
+    assert!(!path.exists());
+}
+
+#[test]
+fn server_waits_for_whole_commands() {
+    let (ws, config, wm) = setup();
+    let path = env::temp_dir().join(format!("wtftw-partial-{}.sock", std::process::id()));
+    let server = IpcServer::new(path.clone()).unwrap();
+    assert!(fs::metadata(&path).unwrap().permissions().mode() & 0o777 == 0o600);
+
+    let _silent = UnixStream::connect(&path).unwrap();
+    let mut slow = UnixStream::connect(&path).unwrap();
+    write!(slow, "vie").unwrap();
+
+    let started = Instant::now();
+    let wm = server.process(wm, ws.clone(), &config.general);
+    assert!(started.elapsed() < Duration::from_millis(50));
+    assert!(wm.workspaces.current_tag() == 0);
+
+    writeln!(slow, "w 1").unwrap();
+    let wm = server.process(wm, ws.clone(), &config.general);
+    assert!(wm.workspaces.current_tag() == 1);
+
+    let mut line = String::new();
+    BufReader::new(slow).read_line(&mut line).unwrap();
+    assert!(line == "{\"success\":true}\n");
+}
+
+#[test]
+fn server_replaces_only_stale_sockets() {
+    let path = env::temp_dir().join(format!("wtftw-stale-{}.sock", std::process::id()));
+    drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+    assert!(path.exists());
+
+    let server = IpcServer::new(path.clone()).unwrap();
+    assert!(IpcServer::new(path.clone()).is_err());
+    assert!(UnixStream::connect(&path).is_ok());
+
+    drop(server);
+    assert!(!path.exists());
+}
+
+#[test]
+fn tree_describes_state() {
+    let (ws, config, wm) = setup();
+    let w1 = ws.add_window(MockWindow::new(Rectangle(0, 0, 100, 100)));
+    let w2 = ws.add_window(MockWindow::new(Rectangle(0, 0, 100, 100)));
+    let wm = wm
+        .manage(ws.deref(), w1, &config.general)
+        .manage(ws.deref(), w2, &config.general)
+        .float(ws.deref(), &config.general, w2);
+
+    let tree = tree(&wm);
+    assert!(tree["focused"] == w2);
+    assert!(tree["screens"].as_array().unwrap().len() == 1);
+    assert!(tree["screens"][0]["rect"]["width"] == 800);
+    assert!(tree["screens"][0]["workspace"] == 0);
+    assert!(tree["screens"][0]["current"] == true);
+
+    let workspaces = tree["workspaces"].as_array().unwrap();
+    assert!(workspaces.len() == 4);
+    assert!(workspaces[0]["tag"] == "1: term");
+    assert!(workspaces[0]["layout"] == "Tall");
+    assert!(workspaces[0]["screen"] == 0);
+    assert!(workspaces[0]["stack"]["focus"] == w2);
+    assert!(workspaces[0]["stack"]["down"][0] == w1);
+    assert!(workspaces[1]["screen"].is_null());
+    assert!(workspaces[1]["stack"].is_null());
+
+    assert!(tree["floating"][0]["window"] == w2);
+    let width = tree["floating"][0]["rect"]["width"].as_f64().unwrap();
+    assert!(width as f32 == wm.workspaces.floating[&w2].2);
+}
+
+#[test]
+fn subscribers_receive_events() {
+    let (ws, config, wm) = setup();
+    let path = env::temp_dir().join(format!("wtftw-events-{}.sock", std::process::id()));
+    let server = IpcServer::new(path.clone()).unwrap();
+
+    let subscribe = |command: &str| {
+        let mut stream = UnixStream::connect(&path).unwrap();
+        writeln!(stream, "{}", command).unwrap();
+        BufReader::new(stream)
+    };
+    let mut all = subscribe("subscribe");
+    let mut focus = subscribe("subscribe focus");
+    let closed = subscribe("subscribe");
+
+    server.process(wm, ws.clone(), &config.general);
+    drop(closed);
+
+    server.broadcast(&[
+        WindowManagerEvent::WindowManaged(7),
+        WindowManagerEvent::FocusChanged(Some(7)),
+        WindowManagerEvent::WorkspaceSwitched(1, "2: web".to_owned()),
+    ]);
+    server.broadcast(&[WindowManagerEvent::FocusChanged(None)]);
+
+    let lines = |reader: &mut BufReader<UnixStream>, n: usize| {
+        (0..n)
+            .map(|_| {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                line
+            })
+            .collect::<Vec<_>>()
+    };
+
+    assert!(
+        lines(&mut all, 5)
+            == vec![
+                "{\"success\":true}\n",
+                "{\"event\":\"managed\",\"window\":7}\n",
+                "{\"event\":\"focus\",\"window\":7}\n",
+                "{\"event\":\"workspace\",\"tag\":\"2: web\",\"workspace\":1}\n",
+                "{\"event\":\"focus\",\"window\":null}\n",
+            ]
+    );
+    assert!(
+        lines(&mut focus, 3)
+            == vec![
+                "{\"success\":true}\n",
+                "{\"event\":\"focus\",\"window\":7}\n",
+                "{\"event\":\"focus\",\"window\":null}\n",
+            ]
+    );
+}
@@ -1 +1,8 @@
+pub mod config;
+pub mod config_file;
 pub mod core;
+pub mod ipc;
+pub mod state;
+pub mod status;
+pub mod window_manager;
+pub mod window_system;
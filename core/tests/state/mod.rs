@@ -0,0 +1,214 @@
+extern crate wtftw_core;
+
+use self::wtftw_core::config::Config;
+use self::wtftw_core::core::rational_rect::RationalRect;
+use self::wtftw_core::core::workspace::Workspace;
+use self::wtftw_core::core::workspaces::Workspaces;
+use self::wtftw_core::layout::{LayoutMessage, TallLayout};
+use self::wtftw_core::mock_window_system::{MockWindow, MockWindowSystem};
+use self::wtftw_core::state;
+use self::wtftw_core::window_manager::WindowManager;
+use self::wtftw_core::window_system::*;
+use crate::core::layout::Column;
+use serde_json::json;
+use std::env;
+use std::fs;
+use std::os::unix::fs::{symlink, PermissionsExt};
+
+fn new_window(window_system: &MockWindowSystem) -> Window {
+    window_system.add_window(MockWindow::new(Rectangle(0, 0, 100, 100)))
+}
+
+/// Two screens, with workspace 3 on the focused second screen, a
+/// floating and a fullscreen window, the focus on a slave and
+/// a changed layout
+fn setup() -> (MockWindowSystem, Config, WindowManager, Vec<Window>) {
+    let ws = MockWindowSystem::new(vec![Rectangle(0, 0, 800, 600), Rectangle(800, 0, 800, 600)]);
+    let config = Config::initialize().unwrap();
+    let windows: Vec<Window> = (0..5).map(|_| new_window(&ws)).collect();
+
+    let wm = WindowManager::new(&ws, &config.general)
+        .manage(&ws, windows[0], &config.general)
+        .manage(&ws, windows[1], &config.general)
+        .view(&ws, 1, &config.general)
+        .view(&ws, 3, &config.general)
+        .manage(&ws, windows[2], &config.general)
+        .manage(&ws, windows[3], &config.general)
+        .manage(&ws, windows[4], &config.general);
+    let wm = wm.windows(&ws, &config.general, &|w| {
+        w.float(windows[3], RationalRect(0.25, 0.25, 0.5, 0.5))
+            .fullscreen(windows[4])
+            .set_border_width(windows[2], 4)
+            .focus_window(windows[2])
+    });
+    let wm = wm
+        .send_layout_message(LayoutMessage::IncreaseMaster, &ws, &config.general)
+        .send_layout_message(LayoutMessage::Increase, &ws, &config.general);
+    (ws, config, wm, windows)
+}
+
+fn fresh(ws: &MockWindowSystem, config: &Config) -> Workspaces {
+    WindowManager::new(ws, &config.general).workspaces
+}
+
+#[test]
+fn state_is_restored_exactly() {
+    let (ws, config, wm, _) = setup();
+    let saved = state::save(&wm.workspaces);
+
+    let restored = state::restore(
+        &fresh(&ws, &config),
+        &saved,
+        &ws.get_windows(),
+        &config.internal.layouts,
+    );
+
+    assert!(state::save(&restored) == saved);
+    assert!(restored.current.screen_id == wm.workspaces.current.screen_id);
+    assert!(restored.current.screen_id == 1);
+    assert!(restored.current_tag() == 3);
+    assert!(restored.peek() == wm.workspaces.peek());
+    assert!(restored.current.workspace.stack == wm.workspaces.current.workspace.stack);
+    assert!(
+        restored.current.workspace.layout.serialize()
+            == wm.workspaces.current.workspace.layout.serialize()
+    );
+}
+
+#[test]
+fn layout_parameters_survive_configuration_changes() {
+    let (ws, mut config, wm, _) = setup();
+    let saved = state::save(&wm.workspaces);
+    let saved_layout = wm.workspaces.current.workspace.layout.serialize();
+
+    // The same layout with other parameters is restored as it was
+    config.general.layout = Box::new(TallLayout {
+        num_master: 2,
+        increment_ratio: 0.1,
+        ratio: 0.6,
+    });
+    let restored = state::restore(
+        &fresh(&ws, &config),
+        &saved,
+        &ws.get_windows(),
+        &config.internal.layouts,
+    );
+    assert!(restored.current.workspace.layout.serialize() == saved_layout);
+
+    // A different layout is rebuilt as it was saved
+    config.general.layout = Box::new(Column);
+    let restored = state::restore(
+        &fresh(&ws, &config),
+        &saved,
+        &ws.get_windows(),
+        &config.internal.layouts,
+    );
+    assert!(restored.current.workspace.layout.serialize() == saved_layout);
+
+    // Unknown layouts fall back to the configured one
+    let mut unknown = saved.clone();
+    unknown["workspaces"][3]["layout"]["description"] = "Accordion".into();
+    let restored = state::restore(
+        &fresh(&ws, &config),
+        &unknown,
+        &ws.get_windows(),
+        &config.internal.layouts,
+    );
+    assert!(restored.current.workspace.layout.description() == "Column");
+    assert!(restored.current.workspace.stack == wm.workspaces.current.workspace.stack);
+}
+
+#[test]
+fn state_survives_the_file() {
+    let (ws, config, wm, _) = setup();
+    let path = env::temp_dir().join(format!("wtftw-test-{}.state", std::process::id()));
+
+    state::write(&path, &wm.workspaces).unwrap();
+    let saved = state::read(&path).unwrap();
+
+    assert!(!path.exists());
+    assert!(saved == state::save(&wm.workspaces));
+    let restored = state::restore(
+        &fresh(&ws, &config),
+        &saved,
+        &ws.get_windows(),
+        &config.internal.layouts,
+    );
+    assert!(state::save(&restored) == saved);
+}
+
+#[test]
+fn state_file_is_private_and_never_followed() {
+    let (_, _, wm, _) = setup();
+    let path = env::temp_dir().join(format!("wtftw-private-{}.state", std::process::id()));
+    let target = env::temp_dir().join(format!("wtftw-target-{}", std::process::id()));
+
+    fs::write(&target, "untouched").unwrap();
+    symlink(&target, &path).unwrap();
+    assert!(state::read(&path).is_err());
+
+    state::write(&path, &wm.workspaces).unwrap();
+    assert!(fs::read_to_string(&target).unwrap() == "untouched");
+    assert!(fs::symlink_metadata(&path).unwrap().is_file());
+    assert!(fs::metadata(&path).unwrap().permissions().mode() & 0o777 == 0o600);
+
+    assert!(state::read(&path).is_ok());
+    fs::remove_file(&target).unwrap();
+}
+
+#[test]
+fn vanished_windows_and_screens_are_dropped() {
+    let (ws, config, wm, windows) = setup();
+    let saved = state::save(&wm.workspaces);
+
+    // The second screen is gone and two windows closed during the restart
+    ws.set_screens(vec![Rectangle(0, 0, 800, 600)]);
+    let existing: Vec<Window> = ws
+        .get_windows()
+        .into_iter()
+        .filter(|&w| w != windows[2] && w != windows[3])
+        .collect();
+    let restored = state::restore(
+        &fresh(&ws, &config),
+        &saved,
+        &existing,
+        &config.internal.layouts,
+    );
+
+    assert!(restored.visible.is_empty());
+    assert!(restored.current_tag() == 0);
+    assert!(restored.current.workspace.windows().len() == 2);
+    assert!(restored.find_tag(windows[4]) == Some(3));
+    assert!(restored.find_tag(windows[2]).is_none());
+    assert!(restored.floating.is_empty());
+    assert!(restored.borders.is_empty());
+    assert!(restored.is_fullscreen(windows[4]));
+    assert!(restored.number_workspaces() == wm.workspaces.number_workspaces());
+}
+
+#[test]
+fn workspaces_are_found_by_id() {
+    let (ws, config, wm, windows) = setup();
+    let mut saved = state::save(&wm.workspaces);
+    saved["screens"] = json!([{ "id": 0, "workspace": 99 }, { "id": 1, "workspace": 3 }]);
+    saved["workspaces"][0]["id"] = json!(null);
+
+    // Workspace ids needn't be positions
+    let renumbered = fresh(&ws, &config).map_workspaces(|w| {
+        Workspace::new(w.id + 1, w.tag.clone(), w.layout.copy(), w.stack.clone())
+    });
+    let restored = state::restore(
+        &renumbered,
+        &saved,
+        &ws.get_windows(),
+        &config.internal.layouts,
+    );
+
+    let mut ids: Vec<u32> = restored.workspaces().iter().map(|w| w.id).collect();
+    ids.sort_unstable();
+    assert!(ids == vec![1, 2, 3, 4]);
+    assert!(restored.current_tag() == 3);
+    assert!(restored.find_tag(windows[2]) == Some(3));
+    assert!(restored.visible[0].workspace.id == 1);
+    assert!(restored.visible[0].workspace.windows().is_empty());
+}
@@ -0,0 +1,161 @@
+extern crate wtftw_core;
+
+use self::wtftw_core::config::Config;
+use self::wtftw_core::mock_window_system::{MockWindow, MockWindowSystem};
+use self::wtftw_core::status::{Bar, StatusLogger, Style};
+use self::wtftw_core::window_manager::WindowManager;
+use self::wtftw_core::window_system::*;
+use std::io::Read;
+use std::ops::Deref;
+use std::process::{Command, Stdio};
+use std::rc::Rc;
+use std::sync::RwLock;
+
+/// A window manager with "1: term" hidden, "2: web" current and empty,
+/// and an urgent window on "3: code"
+fn setup() -> (Rc<MockWindowSystem>, Config, WindowManager) {
+    let window_system = Rc::new(MockWindowSystem::new(vec![Rectangle(0, 0, 800, 600)]));
+    let config = Config::initialize().unwrap();
+    let ws = window_system.deref();
+    let w1 = ws.add_window(MockWindow::new(Rectangle(0, 0, 100, 100)));
+    let w2 = ws.add_window(MockWindow {
+        urgent: true,
+        ..MockWindow::new(Rectangle(0, 0, 100, 100))
+    });
+    let window_manager = WindowManager::new(ws, &config.general)
+        .manage(ws, w1, &config.general)
+        .view(ws, 2, &config.general)
+        .manage(ws, w2, &config.general)
+        .view(ws, 1, &config.general);
+    (window_system, config, window_manager)
+}
+
+#[test]
+fn workspaces_are_rendered_by_state() {
+    let (ws, _, wm) = setup();
+
+    let xmobar = StatusLogger::new(Bar::Xmobar);
+    assert!(
+        xmobar.render(&wm, ws.deref())
+            == "<raw=7:1: term/> <fc=#ffff00>[<raw=6:2: web/>]</fc> \
+                <fc=#ff0000><raw=7:3: code/></fc> : <raw=4:Tall/>"
+    );
+
+    let lemonbar = StatusLogger {
+        empty: Some(Style::new().foreground("#555555")),
+        layout: Style::new().wrap("(", ")"),
+        separator: "|".to_owned(),
+        ..StatusLogger::new(Bar::Lemonbar)
+    };
+    assert!(
+        lemonbar.render(&wm, ws.deref())
+            == "1: term|%{F#ffff00}[2: web]%{F-}|%{F#ff0000}3: code%{F-}|\
+                %{F#555555}4: media%{F-} : (Tall)"
+    );
+}
+
+#[test]
+fn urgency_is_taken_from_hint_changes() {
+    let (ws, _, wm) = setup();
+    let xmobar = StatusLogger::new(Bar::Xmobar);
+    let urgent = ws
+        .get_windows()
+        .into_iter()
+        .find(|&w| wm.workspaces.find_tag(w) == Some(2))
+        .unwrap();
+    let normal = "<raw=7:1: term/> <fc=#ffff00>[<raw=6:2: web/>]</fc> \
+                  <raw=7:3: code/> : <raw=4:Tall/>";
+
+    // Rendering doesn't ask the window system
+    ws.update_window(urgent, |w| w.urgent = false);
+    assert!(xmobar.render(&wm, ws.deref()).contains("<fc=#ff0000>"));
+
+    let wm = wm.property_changed(ws.deref(), urgent, "WM_HINTS");
+    assert!(xmobar.render(&wm, ws.deref()) == normal);
+}
+
+#[test]
+fn focused_title_is_rendered_and_escaped() {
+    let (ws, config, wm) = setup();
+    let window = ws.add_window(MockWindow {
+        name: "50% ^done".to_owned(),
+        ..MockWindow::new(Rectangle(0, 0, 100, 100))
+    });
+    let wm = wm.manage(ws.deref(), window, &config.general);
+
+    let dzen = StatusLogger {
+        current: Style::new().background("#222222"),
+        title_length: 8,
+        ..StatusLogger::new(Bar::Dzen)
+    };
+    assert!(
+        dzen.render(&wm, ws.deref())
+            == "1: term ^bg(#222222)2: web^bg() ^fg(#ff0000)3: code^fg() : Tall : \
+                ^fg(#00ff00)50% ^^...^fg()"
+    );
+
+    let lemonbar = StatusLogger::new(Bar::Lemonbar);
+    assert!(lemonbar
+        .render(&wm, ws.deref())
+        .ends_with("%{F#00ff00}50%% ^done%{F-}"));
+
+    let wm = wm.enter_mode(ws.deref(), "resize");
+    assert!(lemonbar
+        .render(&wm, ws.deref())
+        .contains(" : %{F#ff8000}resize%{F-} : Tall : "));
+
+    let i3bar = StatusLogger::new(Bar::I3bar);
+    let line = i3bar.render(&wm, ws.deref());
+    let blocks: serde_json::Value = serde_json::from_str(line.trim_end_matches(',')).unwrap();
+    assert!(blocks.as_array().unwrap().len() == 6);
+    assert!(blocks[1]["full_text"] == "[2: web]");
+    assert!(blocks[1]["color"] == "#ffff00");
+    assert!(blocks[2]["color"] == "#ff0000");
+    assert!(blocks[3]["full_text"] == "resize");
+    assert!(blocks[5]["full_text"] == "50% ^done");
+
+    let wm = wm.notify(Some("config <error>".to_owned()));
+    let xmobar = StatusLogger::new(Bar::Xmobar);
+    assert!(xmobar
+        .render(&wm, ws.deref())
+        .ends_with(" : <fc=#ff0000><raw=14:config <error>/></fc>"));
+    let wm = wm.notify(Some("failed:\n  one\n\ntwo\n".to_owned()));
+    assert!(xmobar
+        .render(&wm, ws.deref())
+        .ends_with(" : <fc=#ff0000><raw=15:failed: one two/></fc>"));
+    assert!(wm.notify(None).notice.is_none());
+}
+
+#[test]
+fn log_hook_writes_changed_lines() {
+    let (ws, config, wm) = setup();
+    let window_system: Rc<dyn WindowSystem> = ws.clone();
+    let child = Command::new("cat")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let pipe = Rc::new(RwLock::new(child));
+
+    let mut hook = StatusLogger::new(Bar::Lemonbar).log_hook(pipe.clone());
+    hook(wm.clone(), window_system.clone());
+    hook(wm.clone(), window_system.clone());
+    let wm = wm.view(ws.deref(), 0, &config.general);
+    hook(wm, window_system);
+
+    let mut child = pipe.write().unwrap();
+    drop(child.stdin.take());
+    let mut output = String::new();
+    child
+        .stdout
+        .take()
+        .unwrap()
+        .read_to_string(&mut output)
+        .unwrap();
+    child.wait().unwrap();
+
+    let lines: Vec<&str> = output.lines().collect();
+    assert!(lines.len() == 2);
+    assert!(lines[0].starts_with("1: term %{F#ffff00}[2: web]"));
+    assert!(lines[1].starts_with("%{F#ffff00}[1: term]%{F-} %{F#ff0000}3: code"));
+}
@@ -0,0 +1,693 @@
+extern crate wtftw_core;
+
+use self::wtftw_core::config::{Config, GeneralConfig};
+use self::wtftw_core::core::rational_rect::RationalRect;
+use self::wtftw_core::core::stack::Stack;
+use self::wtftw_core::handlers::default::toggle_fullscreen;
+use self::wtftw_core::handlers::rules::*;
+use self::wtftw_core::layout::{Layout, LayoutMessage};
+use self::wtftw_core::mock_window_system::{MockWindow, MockWindowSystem};
+use self::wtftw_core::window_manager::{WindowManager, WindowManagerEvent};
+use self::wtftw_core::window_system::*;
+use std::ops::Deref;
+use std::rc::Rc;
+
+fn setup(screens: Vec<Rectangle>) -> (Rc<MockWindowSystem>, Config, WindowManager) {
+    let window_system = Rc::new(MockWindowSystem::new(screens));
+    let config = Config::initialize().unwrap();
+    let window_manager = WindowManager::new(window_system.deref(), &config.general);
+    (window_system, config, window_manager)
+}
+
+fn new_window(window_system: &MockWindowSystem) -> Window {
+    window_system.add_window(MockWindow::new(Rectangle(0, 0, 100, 100)))
+}
+
+#[test]
+fn manage_tiles_and_focuses() {
+    let (ws, config, wm) = setup(vec![Rectangle(0, 0, 800, 600)]);
+    let w1 = new_window(&ws);
+    let w2 = new_window(&ws);
+
+    let wm = wm
+        .manage(ws.deref(), w1, &config.general)
+        .manage(ws.deref(), w2, &config.general);
+
+    assert!(wm.is_window_managed(w1));
+    assert!(wm.is_window_managed(w2));
+    assert!(ws.is_mapped(w1) && ws.is_mapped(w2));
+    assert!(ws.get_focused_window() == w2);
+
+    let border = config.general.border_width;
+    let mut geometries = [ws.get_geometry(w1), ws.get_geometry(w2)];
+    geometries.sort_by_key(|&Rectangle(x, _, _, _)| x);
+    assert!(geometries[0] == Rectangle(0, 0, 400 - 2 * border, 600 - 2 * border));
+    assert!(geometries[1] == Rectangle(400, 0, 400 - 2 * border, 600 - 2 * border));
+    assert!(ws.window(w2).unwrap().border_color == config.general.focus_border_color);
+    assert!(ws.window(w1).unwrap().border_color == config.general.border_color);
+}
+
+#[test]
+fn view_hides_other_workspaces() {
+    let (ws, config, wm) = setup(vec![Rectangle(0, 0, 800, 600)]);
+    let w1 = new_window(&ws);
+
+    let wm = wm.manage(ws.deref(), w1, &config.general);
+    let wm = wm.view(ws.deref(), 1, &config.general);
+
+    assert!(wm.workspaces.current_tag() == 1);
+    assert!(!ws.is_mapped(w1));
+    assert!(wm.is_waiting_unmap(w1));
+
+    let wm = wm.view(ws.deref(), 0, &config.general);
+    assert!(ws.is_mapped(w1));
+    assert!(wm.workspaces.peek() == Some(w1));
+}
+
+#[test]
+fn unmanage_removes_window() {
+    let (ws, config, wm) = setup(vec![Rectangle(0, 0, 800, 600)]);
+    let w1 = new_window(&ws);
+    let w2 = new_window(&ws);
+
+    let wm = wm
+        .manage(ws.deref(), w1, &config.general)
+        .manage(ws.deref(), w2, &config.general)
+        .unmanage(ws.deref(), w2, &config.general);
+
+    assert!(!wm.is_window_managed(w2));
+    assert!(wm.workspaces.peek() == Some(w1));
+    assert!(ws.get_geometry(w1) == Rectangle(0, 0, 796, 596));
+}
+
+#[test]
+fn fixed_size_windows_float() {
+    let (ws, config, wm) = setup(vec![Rectangle(0, 0, 800, 600)]);
+    let w1 = ws.add_window(MockWindow {
+        size_hint: SizeHint {
+            min_size: Some((200, 100)),
+            max_size: Some((200, 100)),
+        },
+        ..MockWindow::new(Rectangle(100, 100, 200, 100))
+    });
+
+    let wm = wm.manage(ws.deref(), w1, &config.general);
+
+    assert!(wm.workspaces.floating.contains_key(&w1));
+}
+
+#[test]
+fn windows_floated_in_a_transition_are_placed_at_once() {
+    let (ws, config, wm) = setup(vec![Rectangle(0, 0, 800, 600)]);
+    let w1 = new_window(&ws);
+    let w2 = new_window(&ws);
+    let wm = wm
+        .manage(ws.deref(), w1, &config.general)
+        .manage(ws.deref(), w2, &config.general);
+
+    // The layout has to see the floating windows of the new state,
+    // not the ones from before the transition
+    let wm = wm.windows(ws.deref(), &config.general, &|w| {
+        w.float(w2, RationalRect(0.25, 0.25, 0.5, 0.5))
+    });
+    let border = config.general.border_width;
+    assert!(ws.get_geometry(w2) == Rectangle(200, 150, 400 - 2 * border, 300 - 2 * border));
+    assert!(ws.get_geometry(w1) == Rectangle(0, 0, 800 - 2 * border, 600 - 2 * border));
+
+    let wm = wm.windows(ws.deref(), &config.general, &|w| w.sink(w2));
+    assert!(ws.get_geometry(w2).2 == 400 - 2 * border);
+    assert!(!wm.workspaces.floating.contains_key(&w2));
+}
+
+#[test]
+fn rescreen_distributes_workspaces() {
+    let (ws, config, wm) = setup(vec![Rectangle(0, 0, 800, 600)]);
+    let w1 = new_window(&ws);
+    let wm = wm.manage(ws.deref(), w1, &config.general);
+
+    ws.set_screens(vec![Rectangle(0, 0, 800, 600), Rectangle(800, 0, 800, 600)]);
+    match ws.get_event() {
+        WindowSystemEvent::ConfigurationNotification(window) => {
+            assert!(window == ws.get_root())
+        }
+        _ => panic!("expected a configuration notification"),
+    }
+
+    let wm = wm.rescreen(ws.deref());
+    assert!(wm.workspaces.visible.len() == 1);
+    assert!(wm.workspaces.visible[0].screen_detail == Rectangle(800, 0, 800, 600));
+    assert!(wm.workspaces.current.contains(w1));
+}
+
+#[test]
+fn key_handlers_drive_manager() {
+    let (ws, mut config, wm) = setup(vec![Rectangle(0, 0, 800, 600)]);
+    config.default_configuration(ws.deref());
+
+    let quit = KeyCommand::new(
+        ws.get_keycode_from_string("q"),
+        config.general.mod_mask | KeyModifiers::SHIFTMASK,
+    );
+    let window_system: Rc<dyn WindowSystem> = ws.clone();
+    let wm = config.internal.key_handlers[&quit](wm, window_system, &config.general);
+
+    assert!(!wm.running);
+}
+
+#[test]
+fn close_window_asks_focused_client() {
+    let (ws, config, wm) = setup(vec![Rectangle(0, 0, 800, 600)]);
+    let w1 = new_window(&ws);
+    let wm = wm.manage(ws.deref(), w1, &config.general);
+
+    wm.close_window(ws.deref());
+    wm.kill_window(ws.deref());
+
+    assert!(ws.closed_windows() == vec![w1]);
+    assert!(ws.killed_windows() == vec![w1]);
+}
+
+#[test]
+fn server_state_is_published() {
+    let (ws, config, wm) = setup(vec![Rectangle(0, 0, 800, 600)]);
+    let w1 = new_window(&ws);
+    let wm = wm
+        .manage(ws.deref(), w1, &config.general)
+        .view(ws.deref(), 2, &config.general);
+
+    ws.update_server_state(&wm);
+
+    let root = ws.get_root();
+    assert!(ws.get_property(root, "_NET_CURRENT_DESKTOP") == Some(vec![2]));
+    assert!(ws.get_property(root, "_NET_NUMBER_OF_DESKTOPS") == Some(vec![4]));
+}
+
+#[test]
+fn client_lists_are_published() {
+    let (ws, config, wm) = setup(vec![Rectangle(0, 0, 800, 600)]);
+    let w1 = new_window(&ws);
+    let w2 = new_window(&ws);
+    let w3 = new_window(&ws);
+    let wm = wm
+        .manage(ws.deref(), w1, &config.general)
+        .view(ws.deref(), 1, &config.general)
+        .manage(ws.deref(), w2, &config.general)
+        .manage(ws.deref(), w3, &config.general)
+        .float(ws.deref(), &config.general, w2);
+
+    ws.update_server_state(&wm);
+
+    let root = ws.get_root();
+    let mut clients = ws.get_property(root, "_NET_CLIENT_LIST").unwrap();
+    let stacking = ws.get_property(root, "_NET_CLIENT_LIST_STACKING").unwrap();
+    assert!(stacking.len() == 3);
+    assert!(stacking[0] == w1);
+    assert!(stacking[2] == w2);
+
+    clients.sort();
+    assert!(clients == vec![w1, w2, w3]);
+}
+
+#[test]
+fn desktop_names_and_window_desktops_are_published() {
+    let (ws, config, wm) = setup(vec![Rectangle(0, 0, 800, 600)]);
+    let w1 = new_window(&ws);
+    let wm = wm.manage(ws.deref(), w1, &config.general);
+
+    ws.update_server_state(&wm);
+    assert!(
+        ws.get_text_property(ws.get_root(), "_NET_DESKTOP_NAMES")
+            == Some(config.general.tags.clone())
+    );
+    assert!(ws.get_property(w1, "_NET_WM_DESKTOP") == Some(vec![0]));
+
+    let wm = wm.move_window_to_workspace(ws.deref(), &config.general, 3);
+    ws.update_server_state(&wm);
+    assert!(ws.get_property(w1, "_NET_WM_DESKTOP") == Some(vec![3]));
+}
+
+fn client_message(
+    ws: &MockWindowSystem,
+    window: Window,
+    name: &str,
+    data: [i32; 5],
+) -> ClientMessage {
+    ws.push_event(WindowSystemEvent::ClientMessageEvent(
+        window,
+        ws.get_atom(name),
+        32,
+        data,
+    ));
+    match ws.get_event() {
+        WindowSystemEvent::ClientMessageEvent(w, message_type, format, data) => ws
+            .decode_client_message(w, message_type, format, data)
+            .unwrap(),
+        _ => panic!("expected a client message"),
+    }
+}
+
+#[test]
+fn client_messages_activate_close_and_move() {
+    let (ws, config, wm) = setup(vec![Rectangle(0, 0, 800, 600)]);
+    let w1 = new_window(&ws);
+    let w2 = new_window(&ws);
+    let wm = wm
+        .manage(ws.deref(), w1, &config.general)
+        .view(ws.deref(), 2, &config.general)
+        .manage(ws.deref(), w2, &config.general);
+
+    let activate = client_message(&ws, w1, "_NET_ACTIVE_WINDOW", [2, 0, 0, 0, 0]);
+    assert!(activate == ClientMessage::ActivateWindow(w1));
+    let wm = wm.handle_client_message(ws.deref(), &config.general, activate);
+    assert!(wm.workspaces.current_tag() == 0);
+    assert!(wm.workspaces.peek() == Some(w1));
+    assert!(ws.get_focused_window() == w1);
+
+    let shift = client_message(&ws, w2, "_NET_WM_DESKTOP", [0, 0, 0, 0, 0]);
+    let wm = wm.handle_client_message(ws.deref(), &config.general, shift);
+    assert!(wm.workspaces.find_tag(w2) == Some(0));
+    assert!(ws.is_mapped(w2));
+
+    let sticky = client_message(&ws, w2, "_NET_WM_DESKTOP", [-1, 0, 0, 0, 0]);
+    let wm = wm.handle_client_message(ws.deref(), &config.general, sticky);
+    assert!(wm.workspaces.find_tag(w2) == Some(0));
+
+    // Panels close a single window, without disconnecting its client
+    let close = client_message(&ws, w1, "_NET_CLOSE_WINDOW", [0; 5]);
+    wm.handle_client_message(ws.deref(), &config.general, close);
+    assert!(ws.killed_windows() == vec![w1]);
+    assert!(ws.closed_windows().is_empty());
+}
+
+#[test]
+fn unknown_client_messages_are_ignored() {
+    let ws = MockWindowSystem::new(vec![Rectangle(0, 0, 800, 600)]);
+    let message_type = ws.get_atom("_NET_WM_MOVERESIZE");
+    assert!(ws
+        .decode_client_message(2, message_type, 32, [0; 5])
+        .is_none());
+}
+
+#[test]
+fn fullscreen_covers_screen_without_borders() {
+    let (ws, config, wm) = setup(vec![Rectangle(0, 0, 800, 600), Rectangle(800, 0, 800, 600)]);
+    let w1 = new_window(&ws);
+    let w2 = new_window(&ws);
+    let wm = wm
+        .manage(ws.deref(), w1, &config.general)
+        .manage(ws.deref(), w2, &config.general)
+        .focus(w1, ws.deref(), &config.general);
+
+    let fullscreen = ws.get_atom("_NET_WM_STATE_FULLSCREEN") as i32;
+    let message = client_message(&ws, w2, "_NET_WM_STATE", [1, fullscreen, 0, 0, 0]);
+    assert!(message == ClientMessage::Fullscreen(w2, StateChange::Add));
+    let wm = wm.handle_client_message(ws.deref(), &config.general, message);
+
+    assert!(wm.workspaces.is_fullscreen(w2));
+    assert!(ws.get_geometry(w2) == Rectangle(0, 0, 800, 600));
+    assert!(ws.get_window_border_width(w2) == 0);
+    assert!(ws.get_window_border_width(w1) == config.general.border_width);
+    assert!(ws.stacking_order()[0] == w2);
+
+    ws.update_server_state(&wm);
+    assert!(ws.get_property(w2, "_NET_WM_STATE") == Some(vec![fullscreen as u64]));
+    assert!(ws.get_property(w1, "_NET_WM_STATE").is_none());
+
+    let window_system: Rc<dyn WindowSystem> = ws.clone();
+    let wm = wm.focus(w2, ws.deref(), &config.general);
+    let wm = toggle_fullscreen(wm, window_system, &config.general);
+    assert!(!wm.workspaces.is_fullscreen(w2));
+    assert!(ws.get_window_border_width(w2) == config.general.border_width);
+    assert!(ws.get_geometry(w2) != Rectangle(0, 0, 800, 600));
+}
+
+#[test]
+fn fullscreen_keeps_other_window_states() {
+    let (ws, config, wm) = setup(vec![Rectangle(0, 0, 800, 600)]);
+    let w1 = new_window(&ws);
+    let above = ws.get_atom("_NET_WM_STATE_ABOVE");
+    let fullscreen = ws.get_atom("_NET_WM_STATE_FULLSCREEN");
+    ws.set_property(w1, "_NET_WM_STATE", vec![above]);
+    let wm = wm.manage(ws.deref(), w1, &config.general);
+
+    ws.update_server_state(&wm);
+    assert!(ws.get_property(w1, "_NET_WM_STATE") == Some(vec![above]));
+
+    let window_system: Rc<dyn WindowSystem> = ws.clone();
+    let wm = toggle_fullscreen(wm, window_system.clone(), &config.general);
+    ws.update_server_state(&wm);
+    assert!(ws.get_property(w1, "_NET_WM_STATE") == Some(vec![above, fullscreen]));
+
+    let wm = toggle_fullscreen(wm, window_system, &config.general);
+    ws.update_server_state(&wm);
+    assert!(ws.get_property(w1, "_NET_WM_STATE") == Some(vec![above]));
+}
+
+#[test]
+fn windows_asking_for_fullscreen_are_managed_fullscreen() {
+    let (ws, config, wm) = setup(vec![Rectangle(0, 0, 800, 600)]);
+    let w1 = new_window(&ws);
+    let w2 = new_window(&ws);
+    let fullscreen = ws.get_atom("_NET_WM_STATE_FULLSCREEN");
+    ws.set_property(w2, "_NET_WM_STATE", vec![fullscreen]);
+    let wm = wm
+        .manage(ws.deref(), w1, &config.general)
+        .manage(ws.deref(), w2, &config.general);
+
+    assert!(!wm.workspaces.is_fullscreen(w1));
+    assert!(wm.workspaces.is_fullscreen(w2));
+    assert!(ws.get_geometry(w2) == Rectangle(0, 0, 800, 600));
+    assert!(ws.get_window_border_width(w2) == 0);
+}
+
+#[test]
+fn dialogs_and_transients_float_centred() {
+    let (ws, config, wm) = setup(vec![Rectangle(0, 0, 800, 600), Rectangle(800, 0, 800, 600)]);
+    let parent = new_window(&ws);
+    let wm = wm
+        .view(ws.deref(), 1, &config.general)
+        .manage(ws.deref(), parent, &config.general)
+        .view(ws.deref(), 0, &config.general);
+    assert!(wm.workspaces.current_tag() == 0);
+
+    let transient = ws.add_window(MockWindow {
+        transient_for: Some(parent),
+        ..MockWindow::new(Rectangle(0, 0, 400, 300))
+    });
+    let wm = wm.manage(ws.deref(), transient, &config.general);
+
+    assert!(wm.workspaces.find_tag(transient) == Some(1));
+    assert!(wm.workspaces.current_tag() == 1);
+    assert!(wm.workspaces.peek() == Some(transient));
+    assert!(wm.workspaces.floating.contains_key(&transient));
+    let border = config.general.border_width;
+    assert!(ws.get_geometry(transient) == Rectangle(1000, 150, 400 - 2 * border, 300 - 2 * border));
+
+    for &window_type in [WindowType::Dialog, WindowType::Utility, WindowType::Splash].iter() {
+        let dialog = ws.add_window(MockWindow {
+            window_types: vec![window_type],
+            ..MockWindow::new(Rectangle(0, 0, 1600, 300))
+        });
+        let wm = wm.manage(ws.deref(), dialog, &config.general);
+        assert!(wm.workspaces.find_tag(dialog) == Some(1));
+        assert!(wm.workspaces.floating.contains_key(&dialog));
+        assert!(ws.get_geometry(dialog) == Rectangle(800, 150, 800 - 2 * border, 300 - 2 * border));
+    }
+
+    let normal = ws.add_window(MockWindow {
+        window_types: vec![WindowType::Normal],
+        ..MockWindow::new(Rectangle(0, 0, 400, 300))
+    });
+    let wm = wm.manage(ws.deref(), normal, &config.general);
+    assert!(!wm.workspaces.floating.contains_key(&normal));
+}
+
+#[test]
+fn rule_queries_match_window_properties() {
+    let (ws, _, _) = setup(vec![Rectangle(0, 0, 800, 600)]);
+    let window = ws.add_window(MockWindow {
+        name: "Steam - News".to_owned(),
+        class: "Firefox".to_owned(),
+        instance: "Navigator".to_owned(),
+        role: "browser".to_owned(),
+        window_types: vec![WindowType::Normal],
+        ..MockWindow::new(Rectangle(0, 0, 100, 100))
+    });
+    let test = |query: Query| query.test(ws.deref(), window);
+
+    assert!(test(always()));
+    assert!(test(class().is("Firefox")));
+    assert!(!test(class().is("Fire")));
+    assert!(test(instance().is("Navigator")));
+    assert!(test(role().is("browser")));
+    assert!(test(title().contains("News")));
+    assert!(!test(title().contains("news")));
+    assert!(test(title().matches(Regex::new("^Steam").unwrap())));
+    assert!(!test(title().matches(Regex::new("News$ | ^News").unwrap())));
+    assert!(test(window_type(WindowType::Normal)));
+    assert!(!test(window_type(WindowType::Dialog)));
+
+    assert!(test(class().is("Firefox") & role().is("browser")));
+    assert!(!test(class().is("Firefox") & !role().is("browser")));
+    assert!(test(class().is("Gimp") | title().contains("Steam")));
+    assert!(test(
+        class()
+            .is("Gimp")
+            .or(class().is("Firefox"))
+            .and(!class().is("Gimp"))
+    ));
+}
+
+#[test]
+fn rules_compile_into_manage_hook() {
+    let (ws, config, wm) = setup(vec![Rectangle(0, 0, 800, 600)]);
+    let hook = manage_hook(vec![
+        class().is("Gimp").then(Action::CenterFloat),
+        class()
+            .is("Steam")
+            .then(Action::Shift("3: code".to_owned()))
+            .then(Action::Border(5)),
+        class().is("mpv").then(Action::Fullscreen),
+        class().is("Steam").then(Action::Float),
+        window_type(WindowType::Notification).then(Action::Ignore),
+    ]);
+    let window_system: Rc<dyn WindowSystem> = ws.clone();
+    let manage = |wm: WindowManager, window: Window| {
+        wm.manage_with(ws.deref(), window, &config.general, &|x| {
+            hook(x.clone(), window_system.clone(), window)
+        })
+    };
+    let border = config.general.border_width;
+
+    let gimp = ws.add_window(MockWindow {
+        class: "Gimp".to_owned(),
+        ..MockWindow::new(Rectangle(0, 0, 400, 300))
+    });
+    let wm = manage(wm, gimp);
+    assert!(wm.workspaces.floating.contains_key(&gimp));
+    assert!(ws.get_geometry(gimp) == Rectangle(200, 150, 400 - 2 * border, 300 - 2 * border));
+
+    let steam = ws.add_window(MockWindow {
+        class: "Steam".to_owned(),
+        ..MockWindow::new(Rectangle(100, 100, 200, 200))
+    });
+    let wm = manage(wm, steam);
+    assert!(wm.workspaces.find_tag(steam) == Some(2));
+    assert!(wm.workspaces.floating.contains_key(&steam));
+    assert!(!ws.is_mapped(steam));
+    let wm = wm.view(ws.deref(), 2, &config.general);
+    assert!(ws.window(steam).unwrap().border_width == 5);
+    assert!(ws.get_geometry(steam) == Rectangle(100, 100, 190, 190));
+
+    let mpv = ws.add_window(MockWindow {
+        class: "mpv".to_owned(),
+        ..MockWindow::new(Rectangle(0, 0, 100, 100))
+    });
+    let wm = manage(wm, mpv);
+    assert!(wm.workspaces.is_fullscreen(mpv));
+    assert!(ws.get_geometry(mpv) == Rectangle(0, 0, 800, 600));
+
+    let notification = ws.add_window(MockWindow {
+        window_types: vec![WindowType::Notification],
+        ..MockWindow::new(Rectangle(0, 0, 100, 100))
+    });
+    let wm = manage(wm, notification);
+    assert!(!wm.is_window_managed(notification));
+    assert!(ws.is_mapped(notification));
+
+    let wm = wm.unmanage(ws.deref(), steam, &config.general);
+    assert!(!wm.workspaces.borders.contains_key(&steam));
+}
+
+/// A layout stacking all windows, which counts the `Next` messages
+/// it receives in its description
+#[derive(Clone, Copy)]
+struct CountingLayout(u32);
+
+impl Layout for CountingLayout {
+    fn apply_layout(
+        &mut self,
+        _: &dyn WindowSystem,
+        screen: Rectangle,
+        _: &GeneralConfig,
+        stack: &Option<Stack<Window>>,
+    ) -> Vec<(Window, Rectangle)> {
+        stack.as_ref().map_or(Vec::new(), |s| {
+            s.integrate().into_iter().map(|w| (w, screen)).collect()
+        })
+    }
+
+    fn apply_message(
+        &mut self,
+        message: LayoutMessage,
+        _: &dyn WindowSystem,
+        _: &Option<Stack<Window>>,
+        _: &GeneralConfig,
+    ) -> bool {
+        if message == LayoutMessage::Next {
+            self.0 += 1;
+        }
+        true
+    }
+
+    fn description(&self) -> String {
+        format!("Counting {}", self.0)
+    }
+
+    fn copy(&self) -> Box<dyn Layout> {
+        Box::new(*self)
+    }
+}
+
+#[test]
+fn state_transitions_emit_events() {
+    let (ws, mut config, _) = setup(vec![Rectangle(0, 0, 800, 600)]);
+    config.general.layout = Box::new(CountingLayout(0));
+    let wm = WindowManager::new(ws.deref(), &config.general);
+    let w1 = new_window(&ws);
+    let w2 = new_window(&ws);
+
+    let wm = wm.manage(ws.deref(), w1, &config.general);
+    assert!(
+        wm.events
+            == vec![
+                WindowManagerEvent::WindowManaged(w1),
+                WindowManagerEvent::FocusChanged(Some(w1)),
+            ]
+    );
+
+    let wm = WindowManager {
+        events: Vec::new(),
+        ..wm
+    }
+    .manage(ws.deref(), w2, &config.general)
+    .view(ws.deref(), 1, &config.general)
+    .view(ws.deref(), 1, &config.general)
+    .unmanage(ws.deref(), w1, &config.general);
+    assert!(
+        wm.events
+            == vec![
+                WindowManagerEvent::WindowManaged(w2),
+                WindowManagerEvent::FocusChanged(Some(w2)),
+                WindowManagerEvent::WorkspaceSwitched(1, "2: web".to_owned()),
+                WindowManagerEvent::FocusChanged(None),
+                WindowManagerEvent::WindowUnmanaged(w1),
+            ]
+    );
+
+    let wm = WindowManager {
+        events: Vec::new(),
+        ..wm
+    }
+    .send_layout_message(LayoutMessage::Next, ws.deref(), &config.general)
+    .send_layout_message(LayoutMessage::Increase, ws.deref(), &config.general);
+    assert!(
+        wm.events
+            == vec![WindowManagerEvent::LayoutChanged(
+                1,
+                "Counting 1".to_owned()
+            )]
+    );
+
+    ws.set_screens(vec![Rectangle(0, 0, 1024, 768)]);
+    let wm = WindowManager {
+        events: Vec::new(),
+        ..wm
+    }
+    .rescreen(ws.deref());
+    assert!(
+        wm.events
+            == vec![WindowManagerEvent::ScreensChanged(vec![Rectangle(
+                0, 0, 1024, 768
+            )])]
+    );
+}
+
+#[test]
+fn property_changes_emit_events() {
+    let (ws, config, wm) = setup(vec![Rectangle(0, 0, 800, 600)]);
+    let window = new_window(&ws);
+    let unmanaged = new_window(&ws);
+    let wm = wm.manage(ws.deref(), window, &config.general);
+    let wm = WindowManager {
+        events: Vec::new(),
+        ..wm
+    };
+
+    ws.update_window(window, |w| {
+        w.name = "vim".to_owned();
+    });
+    let wm = wm
+        .property_changed(ws.deref(), window, "_NET_WM_NAME")
+        .property_changed(ws.deref(), unmanaged, "WM_NAME")
+        .property_changed(ws.deref(), window, "WM_HINTS")
+        .property_changed(ws.deref(), window, "WM_CLASS");
+    assert!(wm.events == vec![WindowManagerEvent::TitleChanged(window, "vim".to_owned())]);
+
+    ws.update_window(window, |w| w.urgent = true);
+    let wm = wm.property_changed(ws.deref(), window, "WM_HINTS");
+    assert!(wm.events.last() == Some(&WindowManagerEvent::UrgencySet(window)));
+
+    let atom = ws.get_atom("WM_HINTS");
+    assert!(ws.get_atom_name(atom) == "WM_HINTS");
+    assert!(ws.get_atom_name(0).is_empty());
+}
+
+#[test]
+fn reconfigure_keeps_windows_and_redraws_borders() {
+    let (ws, config, wm) = setup(vec![Rectangle(0, 0, 800, 600)]);
+    let w1 = new_window(&ws);
+    let w2 = new_window(&ws);
+    let w3 = new_window(&ws);
+    let wm = wm
+        .manage(ws.deref(), w1, &config.general)
+        .manage(ws.deref(), w2, &config.general)
+        .view(ws.deref(), 1, &config.general)
+        .manage(ws.deref(), w3, &config.general)
+        .view(ws.deref(), 0, &config.general)
+        .send_layout_message(LayoutMessage::Increase, ws.deref(), &config.general);
+
+    let mut new = Config::initialize().unwrap();
+    new.general.border_color = 0x123456;
+    new.general.focus_border_color = 0xabcdef;
+    new.general.border_width = 5;
+    let wm = WindowManager {
+        events: Vec::new(),
+        ..wm
+    }
+    .reconfigure(ws.deref(), &config.general, &new.general);
+
+    assert!(wm.workspaces.find_tag(w1) == Some(0));
+    assert!(wm.workspaces.find_tag(w3) == Some(1));
+    assert!(wm.workspaces.peek() == Some(w2));
+    assert!(ws.window(w2).unwrap().border_color == 0xabcdef);
+    assert!(ws.window(w1).unwrap().border_color == 0x123456);
+    assert!(ws.get_window_border_width(w1) == 5);
+    // The layout is still the configured one and keeps its ratio
+    let ratio = wm.workspaces.current.workspace.layout.serialize().unwrap()["ratio"]
+        .as_f64()
+        .unwrap();
+    assert!((ratio - 0.55).abs() < 0.001);
+    assert!(wm.events.is_empty());
+
+    let old = new;
+    let mut new = Config::initialize().unwrap();
+    new.general.layout = Box::new(CountingLayout(0));
+    let wm = wm.reconfigure(ws.deref(), &old.general, &new.general);
+
+    assert!(wm.workspaces.find_tag(w1) == Some(0));
+    assert!(wm.workspaces.find_tag(w3) == Some(1));
+    assert!(wm
+        .workspaces
+        .workspaces()
+        .iter()
+        .all(|w| w.layout.description() == "Counting 0"));
+    assert!(ws.get_geometry(w1) == Rectangle(0, 0, 796, 596));
+    assert!(ws.get_geometry(w2) == Rectangle(0, 0, 796, 596));
+    assert!(
+        wm.events
+            == (0..4)
+                .map(|id| WindowManagerEvent::LayoutChanged(id, "Counting 0".to_owned()))
+                .collect::<Vec<_>>()
+    );
+}
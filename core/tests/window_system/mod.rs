@@ -0,0 +1,224 @@
+extern crate wtftw_core;
+
+use self::wtftw_core::config::Config;
+use self::wtftw_core::handlers::KeyHandler;
+use self::wtftw_core::mock_window_system::MockWindowSystem;
+use self::wtftw_core::window_manager::{WindowManager, WindowManagerEvent};
+use self::wtftw_core::window_system::*;
+use std::cell::Cell;
+use std::ops::Deref;
+use std::rc::Rc;
+
+#[test]
+fn key_bindings_are_parsed() {
+    let ws = MockWindowSystem::new(vec![Rectangle(0, 0, 800, 600)]);
+    let parse = |s: &str| KeyCommand::parse(s, KeyModifiers::MOD4MASK, &ws).ok();
+    let key = |key: u64, mask: KeyModifiers| Some(KeyCommand::new(key, mask));
+
+    assert!(parse("j") == key('j' as u64, KeyModifiers::NONEMASK));
+    assert!(parse("M-j") == key('j' as u64, KeyModifiers::MOD4MASK));
+    assert!(parse("M-S-Return") == key(0xff0d, KeyModifiers::MOD4MASK | KeyModifiers::SHIFTMASK));
+    assert!(
+        parse("C-M1-j")
+            == key(
+                'j' as u64,
+                KeyModifiers::CONTROLMASK | KeyModifiers::MOD1MASK
+            )
+    );
+    assert!(parse("M5-<space>") == key(0x20, KeyModifiers::MOD5MASK));
+    assert!(parse("M-minus") == key(0x2d, KeyModifiers::MOD4MASK));
+    assert!(parse("<XF86AudioMute>") == key(0x1008ff12, KeyModifiers::NONEMASK));
+    assert!(parse("S-XF86AudioRaiseVolume") == key(0x1008ff13, KeyModifiers::SHIFTMASK));
+
+    assert!(parse("").is_none());
+    assert!(parse("M-").is_none());
+    assert!(parse("X-j").is_none());
+    assert!(parse("M-S-Enter").is_none());
+    assert!(parse("<XF86Dance>").is_none());
+}
+
+#[test]
+fn key_bindings_are_added_to_the_config() {
+    let ws = MockWindowSystem::new(vec![Rectangle(0, 0, 800, 600)]);
+    let mut config = Config::initialize().unwrap();
+    let mask = config.general.mod_mask;
+
+    assert!(config
+        .add_key_binding(&ws, "M-S-Return", Box::new(|m, _, _| m))
+        .is_ok());
+    assert!(config
+        .add_key_binding(&ws, "M-Enter", Box::new(|m, _, _| m))
+        .is_err());
+    assert!(config.internal.key_handlers.len() == 1);
+    assert!(config
+        .internal
+        .key_handlers
+        .contains_key(&KeyCommand::new(0xff0d, mask | KeyModifiers::SHIFTMASK)));
+}
+
+#[test]
+fn grabbed_bindings_are_replaced() {
+    let ws = MockWindowSystem::new(vec![Rectangle(0, 0, 800, 600)]);
+    let mut config = Config::initialize().unwrap();
+    config
+        .add_key_binding(&ws, "M-j", Box::new(|m, _, _| m))
+        .unwrap();
+    config.add_mouse_handler(1, KeyModifiers::MOD1MASK, Box::new(|m, _, _, _| m));
+    config.grab_bindings(&ws);
+    assert!(ws.grabbed_keys() == vec![KeyCommand::new('j' as u64, KeyModifiers::MOD1MASK)]);
+    assert!(ws.grabbed_buttons().len() == 1);
+
+    let mut reloaded = Config::initialize().unwrap();
+    reloaded
+        .add_key_binding(&ws, "M-k", Box::new(|m, _, _| m))
+        .unwrap();
+    reloaded.grab_bindings(&ws);
+    assert!(ws.grabbed_keys() == vec![KeyCommand::new('k' as u64, KeyModifiers::MOD1MASK)]);
+    assert!(ws.grabbed_buttons().is_empty());
+}
+
+/// A config with a "resize" mode entered by M-r, a chord M-x M-y w and
+/// a chord x w in the resize mode, whose bindings count how often they ran
+fn setup_modes() -> (Rc<MockWindowSystem>, Config, WindowManager, Rc<Cell<u32>>) {
+    let ws = Rc::new(MockWindowSystem::new(vec![Rectangle(0, 0, 800, 600)]));
+    let mut config = Config::initialize().unwrap();
+    let count = Rc::new(Cell::new(0));
+    let counter = || -> KeyHandler {
+        let count = count.clone();
+        Box::new(move |m, _, _| {
+            count.set(count.get() + 1);
+            m
+        })
+    };
+
+    config.add_mode(ws.deref(), "resize");
+    config
+        .add_key_binding(
+            ws.deref(),
+            "M-r",
+            Box::new(|m, w, _| m.enter_mode(w.deref(), "resize")),
+        )
+        .unwrap();
+    config
+        .add_mode_key_binding(ws.deref(), "resize", "h", counter())
+        .unwrap();
+    config
+        .add_key_binding(ws.deref(), "M-x M-y w", counter())
+        .unwrap();
+    config
+        .add_mode_key_binding(ws.deref(), "resize", "x w", counter())
+        .unwrap();
+
+    let wm = WindowManager::new(ws.deref(), &config.general);
+    (ws, config, wm, count)
+}
+
+#[test]
+fn key_modes_replace_bindings_until_escape() {
+    let (ws, config, wm, count) = setup_modes();
+    let mask = config.general.mod_mask;
+    let press = |wm, key: &str, mask| {
+        let key = KeyCommand::new(ws.get_keycode_from_string(key), mask);
+        config.handle_key(wm, ws.clone(), key)
+    };
+
+    let wm = press(wm, "h", KeyModifiers::NONEMASK);
+    assert!(count.get() == 0);
+
+    let wm = press(wm, "r", mask);
+    assert!(wm.mode == Some("resize".to_owned()));
+    assert!(ws.is_keyboard_grabbed());
+
+    let wm = press(wm, "h", KeyModifiers::NONEMASK);
+    let wm = press(wm, "j", KeyModifiers::NONEMASK);
+    let wm = press(wm, "h", KeyModifiers::NONEMASK);
+    assert!(count.get() == 2);
+    assert!(wm.mode == Some("resize".to_owned()));
+
+    let wm = press(wm, "Escape", KeyModifiers::NONEMASK);
+    assert!(wm.mode.is_none());
+    assert!(!ws.is_keyboard_grabbed());
+    assert!(
+        wm.events
+            == vec![
+                WindowManagerEvent::ModeChanged(Some("resize".to_owned())),
+                WindowManagerEvent::ModeChanged(None),
+            ]
+    );
+
+    let wm = press(wm, "h", KeyModifiers::NONEMASK);
+    assert!(count.get() == 2);
+    assert!(wm.mode.is_none());
+}
+
+#[test]
+fn chords_are_left_after_the_next_key() {
+    let (ws, config, wm, count) = setup_modes();
+    let mask = config.general.mod_mask;
+    let press = |wm, key: &str, mask| {
+        let key = KeyCommand::new(ws.get_keycode_from_string(key), mask);
+        config.handle_key(wm, ws.clone(), key)
+    };
+
+    let wm = press(wm, "x", mask);
+    assert!(wm.mode == Some("M-x".to_owned()));
+    let wm = press(wm, "y", mask);
+    assert!(wm.mode == Some("M-x M-y".to_owned()));
+    assert!(ws.is_keyboard_grabbed());
+    let wm = press(wm, "w", KeyModifiers::NONEMASK);
+    assert!(count.get() == 1);
+    assert!(wm.mode.is_none());
+    assert!(!ws.is_keyboard_grabbed());
+
+    // Any other key aborts the chord
+    let wm = press(wm, "x", mask);
+    let wm = press(wm, "j", KeyModifiers::NONEMASK);
+    assert!(wm.mode.is_none());
+    let wm = press(wm, "w", KeyModifiers::NONEMASK);
+    assert!(count.get() == 1);
+    assert!(wm.mode.is_none());
+    assert!(!ws.is_keyboard_grabbed());
+}
+
+#[test]
+fn chords_in_a_mode_return_to_it() {
+    let (ws, config, wm, count) = setup_modes();
+    let mask = config.general.mod_mask;
+    let press = |wm, key: &str, mask| {
+        let key = KeyCommand::new(ws.get_keycode_from_string(key), mask);
+        config.handle_key(wm, ws.clone(), key)
+    };
+
+    let wm = press(wm, "r", mask);
+    let wm = press(wm, "x", KeyModifiers::NONEMASK);
+    assert!(wm.mode == Some("resize: x".to_owned()));
+    let wm = press(wm, "w", KeyModifiers::NONEMASK);
+    assert!(count.get() == 1);
+    assert!(wm.mode == Some("resize".to_owned()));
+    assert!(ws.is_keyboard_grabbed());
+
+    // Aborting the chord returns to the mode as well
+    let wm = press(wm, "x", KeyModifiers::NONEMASK);
+    let wm = press(wm, "j", KeyModifiers::NONEMASK);
+    assert!(wm.mode == Some("resize".to_owned()));
+    assert!(ws.is_keyboard_grabbed());
+    let wm = press(wm, "h", KeyModifiers::NONEMASK);
+    assert!(count.get() == 2);
+    assert!(wm.mode == Some("resize".to_owned()));
+}
+
+#[test]
+fn key_modes_must_exist() {
+    let (ws, mut config, _, _) = setup_modes();
+
+    assert!(config
+        .add_mode_key_binding(ws.deref(), "move", "h", Box::new(|m, _, _| m))
+        .is_err());
+    assert!(!config.internal.modes.contains_key("move"));
+    assert!(config.internal.modes["resize"].sticky);
+    assert!(!config.internal.modes["M-x"].sticky);
+    assert!(config.internal.modes["M-x"].key_handlers.len() == 1);
+    assert!(config.internal.modes["M-x"].parent.is_none());
+    assert!(config.internal.modes["resize: x"].parent == Some("resize".to_owned()));
+    assert!(!config.internal.modes.contains_key("x"));
+}
@@ -9,10 +9,15 @@ extern crate simplelog;
 
 use anyhow::Result;
 use std::env;
+use std::mem;
 use std::rc::Rc;
 use std::ops::Deref;
+use std::path::Path;
 use getopts::Options;
 use wtftw_core::config::Config;
+use wtftw_core::config_file::{self, config_file_path};
+use wtftw_core::ipc::{socket_path, IpcServer};
+use wtftw_core::state;
 use wtftw_core::window_manager::WindowManager;
 use wtftw_core::window_system::*;
 use wtftw_xlib::XlibWindowSystem;
@@ -31,12 +36,31 @@ fn init_terminal_logger(verbose_mode_enabled: bool) {
     ]).unwrap();
 }
 
+/// Read the configuration file again and run the compiled configuration as
+/// it was loaded at startup. Changes to the compiled configuration itself
+/// take effect on restart, which builds it again.
+fn reload_config(config: &Config, window_manager: &WindowManager,
+                 window_system: &dyn WindowSystem) -> Result<Config> {
+    let mut reloaded = Config::initialize()?;
+    config_file::load_file(&mut reloaded, window_system, &config_file_path())?;
+
+    if config.internal.library.is_some() {
+        reloaded.call(&mut window_manager.clone(), window_system)?;
+    } else {
+        reloaded.default_configuration(window_system);
+    }
+    Ok(reloaded)
+}
+
 fn main() -> Result<()> {
     // Parse command line arguments
     let args : Vec<String> = env::args().collect();
 
     let mut options = Options::new();
     options.optopt("r", "resume", "list of window IDs to capture in resume", "WINDOW");
+    options.optopt("s", "state", "file to restore the state from, written on restart", "FILE");
+    options.optflag("c", "check-config", "build and load the config module, then exit without starting");
+    options.optflag("u", "update-config", "update the dependencies of the config module before building it");
     options.optflag("v", "verbose", "be verbose");
 
     let matches = match options.parse(args.into_iter().skip(1).collect::<Vec<_>>()) {
@@ -48,6 +72,17 @@ fn main() -> Result<()> {
 
     // Create a default config.generaluration
     let mut config = Config::initialize()?;
+    config.internal.update_dependencies = matches.opt_present("u");
+
+    if matches.opt_present("c") {
+        if config.check()? {
+            println!("config module in {} is fine", config.internal.wtftw_dir);
+        } else {
+            println!("no config module in {}", config.internal.wtftw_dir);
+        }
+        return Ok(());
+    }
+
     // Initialize window system. Use xlib here for now
     debug!("initialize window system");
     let xlib = XlibWindowSystem::new();
@@ -56,10 +91,20 @@ fn main() -> Result<()> {
     debug!("create window manager");
     let mut window_manager = WindowManager::new(window_system.deref(), &config.general);
 
+    // Apply the configuration file, if there is one
+    let config_file = config_file_path();
+    match config_file::load_file(&mut config, window_system.deref(), &config_file) {
+        Ok(true) => info!("loaded {}", config_file.display()),
+        Ok(false) => debug!("no configuration file at {}", config_file.display()),
+        Err(e) => error!("unable to load configuration file: {:#}", e)
+    }
+
     // If available, compile the config.general file at ~/.wtftw/config.general.rs
     // and call the config.generalure method
-    config.compile_and_call(&mut window_manager, window_system.deref());
-    window_manager = WindowManager::new(window_system.deref(), &config.general);
+    let notice = config.compile_and_call(&mut window_manager, window_system.deref())
+        .err()
+        .map(|e| format!("config module failed: {}", e));
+    window_manager = WindowManager::new(window_system.deref(), &config.general).notify(notice);
 
     // Output some initial information
     info!("WTFTW - Window Tiling For The Win");
@@ -72,13 +117,7 @@ fn main() -> Result<()> {
 
     debug!("Size of keyhandlers after config.generaluration: {}", config.internal.key_handlers.len());
 
-    for (command, _) in config.internal.key_handlers.iter() {
-        window_system.grab_keys(vec!(*command));
-    }
-
-    for (&command, _) in config.internal.mouse_handlers.iter() {
-        window_system.grab_button(command);
-    }
+    config.grab_bindings(window_system.deref());
 
     let window_ids = if matches.opt_present("r") {
         debug!("trying to manage pre-existing windows");
@@ -91,22 +130,62 @@ fn main() -> Result<()> {
     for (window, workspace) in window_ids {
         debug!("re-inserting window {}", window);
         window_manager = window_manager.view(window_system.deref(), workspace, &config.general)
-            .manage(window_system.deref(), window, &config.general).windows(window_system.deref(), &config.general,
-                                                        &|x| (config.internal.manage_hook)(x.clone(),
-                                                        window_system.clone(), window));
+            .manage_with(window_system.deref(), window, &config.general,
+                         &|x| (config.internal.manage_hook)(x.clone(), window_system.clone(), window));
+    }
+
+    if let Some(path) = matches.opt_str("s") {
+        debug!("restoring state from {}", path);
+        match state::read(Path::new(&path)) {
+            Ok(saved) => {
+                let existing = window_system.get_windows();
+                window_manager = window_manager.windows(window_system.deref(), &config.general,
+                                                        &|x| state::restore(x, &saved, &existing, &config.internal.layouts));
+            },
+            Err(e) => error!("unable to restore state from {}: {}", path, e)
+        }
     }
 
     window_manager = (*config.internal.startup_hook)(window_manager, window_system.clone(), &config);
 
+    // Listen for commands from scripts
+    let ipc = match IpcServer::new(socket_path()) {
+        Ok(server) => Some(server),
+        Err(e) => {
+            error!("unable to listen on {}: {}", socket_path().display(), e);
+            None
+        }
+    };
+
     // Enter the event loop and just listen for events
     while window_manager.running {
-        let event = window_system.clone().get_event();
+        // Execute commands sent over the socket until the next event arrives
+        let event = match ipc {
+            Some(ref server) if !window_system.event_pending() => {
+                server.wait(window_system.event_fd());
+                window_manager = server.process(window_manager, window_system.clone(), &config.general);
+
+                if window_system.event_pending() {
+                    window_system.get_event()
+                } else {
+                    WindowSystemEvent::UnknownEvent
+                }
+            },
+            _ => window_system.clone().get_event()
+        };
+
         match event {
-            WindowSystemEvent::ClientMessageEvent(_, _, _, _) => {
+            WindowSystemEvent::ClientMessageEvent(window, message_type, format, data) => {
+                if let Some(message) = window_system.decode_client_message(window, message_type, format, data) {
+                    window_manager = window_manager.handle_client_message(window_system.deref(), &config.general, message);
+                }
             },
             WindowSystemEvent::PropertyMessageEvent(process, window, atom) => {
                 if process {
                     window_manager = window_system.process_message(&window_manager, &config.general, window, atom);
+                } else {
+                    window_manager = window_manager.property_changed(window_system.deref(), window,
+                                                                     &window_system.get_atom_name(atom));
                 }
             },
             // The X11/Wayland configuration changed, so we need to readjust the
@@ -131,10 +210,9 @@ fn main() -> Result<()> {
                     continue;
                 }
 
-                window_manager = window_manager.manage(window_system.deref(), window, &config.general)
-                                               .windows(window_system.deref(), &config.general,
-                                                        &|x| (config.internal.manage_hook)(x.clone(),
-                                                        window_system.clone(), window));
+                window_manager = window_manager.manage_with(window_system.deref(), window, &config.general,
+                                                            &|x| (config.internal.manage_hook)(x.clone(),
+                                                            window_system.clone(), window));
             },
             WindowSystemEvent::WindowUnmapped(window, synthetic) => {
                 if synthetic && window_manager.is_window_managed(window) {
@@ -192,11 +270,7 @@ fn main() -> Result<()> {
                 }
             }
             WindowSystemEvent::KeyPressed(_, key) => {
-                if config.internal.key_handlers.contains_key(&key) {
-                    let local_window_manager = window_manager.clone();
-                    window_manager = config.internal.key_handlers[&key](local_window_manager,
-                        window_system.clone(), &config.general);
-                }
+                window_manager = config.handle_key(window_manager, window_system.clone(), key);
             },
             WindowSystemEvent::MouseMotion(x, y) => {
                 let local_window_manager = window_manager.clone();
@@ -208,11 +282,36 @@ fn main() -> Result<()> {
             _ => ()
         };
 
-        if let Some(ref mut loghook) = config.internal.loghook {
-            loghook(window_manager.clone(), window_system.clone());
+        if window_manager.reload_requested {
+            window_manager.reload_requested = false;
+            match reload_config(&config, &window_manager, window_system.deref()) {
+                Ok(mut reloaded) => {
+                    info!("configuration reloaded");
+                    // Status bars of the old configuration were started again
+                    for pipe in config.general.pipes.iter() {
+                        let _ = pipe.write().unwrap().kill();
+                    }
+                    // Layouts kept on workspaces may still come from the old library
+                    if reloaded.internal.library.is_none() {
+                        reloaded.internal.library = config.internal.library.take();
+                    }
+                    reloaded.grab_bindings(window_system.deref());
+                    window_manager = window_manager.exit_mode(window_system.deref())
+                        .reconfigure(window_system.deref(), &config.general, &reloaded.general);
+                    config = reloaded;
+                },
+                Err(e) => error!("unable to reload the configuration: {:#}", e)
+            }
         }
 
+        config.log(&window_manager, window_system.clone());
+
         window_system.update_server_state(&window_manager);
+
+        let events = mem::take(&mut window_manager.events);
+        if let Some(ref server) = ipc {
+            server.broadcast(&events);
+        }
     }
 
     Ok(())
@@ -0,0 +1,86 @@
+extern crate getopts;
+extern crate serde_json;
+extern crate wtftw_core;
+
+use anyhow::{anyhow, Result};
+use getopts::Options;
+use serde_json::Value;
+use std::env;
+use std::io::{BufRead, BufReader, Write};
+use std::net::Shutdown;
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::process;
+use wtftw_core::ipc::socket_path;
+
+const COMMANDS: &str = "Commands:
+    view WORKSPACE           switch to the workspace with the given index or tag
+    move WORKSPACE [WINDOW]  move a window to the given workspace
+    layout MESSAGE           send a message to the current layout,
+                             e.g. increase-master or tree-rotate
+    focus WINDOW             focus the given window
+    float [WINDOW]           float a window
+    sink [WINDOW]            tile a floating window again
+    close [WINDOW]           ask a window to close
+    get-tree                 print screens, workspaces and windows as JSON
+    subscribe [EVENT...]     print events as JSON lines as they happen, e.g.
+                             workspace, managed, unmanaged, focus, title,
+                             layout, screens, urgency or mode
+    reload                   read the configuration again
+    restart                  restart wtftw
+    quit                     quit wtftw
+
+Windows default to the focused one.";
+
+/// Send a command to the running window manager
+fn send(path: &PathBuf, command: &str) -> Result<BufReader<UnixStream>> {
+    let mut stream = UnixStream::connect(path)
+        .map_err(|e| anyhow!("unable to connect to {}: {}", path.display(), e))?;
+    writeln!(stream, "{}", command)?;
+    stream.shutdown(Shutdown::Write)?;
+    Ok(BufReader::new(stream))
+}
+
+fn read_reply(reader: &mut BufReader<UnixStream>) -> Result<Value> {
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    Ok(serde_json::from_str(&line)?)
+}
+
+fn main() -> Result<()> {
+    let args: Vec<String> = env::args().collect();
+
+    let mut options = Options::new();
+    options.optopt("s", "socket", "path of the wtftw socket", "PATH");
+    options.optflag("h", "help", "print this help");
+
+    let matches = options.parse(&args[1..])?;
+    if matches.opt_present("h") || matches.free.is_empty() {
+        let brief = format!("Usage: {} [options] COMMAND [ARGUMENTS]", args[0]);
+        println!("{}\n{}", options.usage(&brief), COMMANDS);
+        return Ok(());
+    }
+
+    let path = matches.opt_str("s").map_or_else(socket_path, PathBuf::from);
+    let mut reader = send(&path, &serde_json::to_string(&matches.free)?)?;
+    let reply = read_reply(&mut reader)?;
+
+    if reply["success"] == Value::Bool(true) {
+        if let Some(tree) = reply.get("tree") {
+            println!("{}", serde_json::to_string_pretty(tree)?);
+        }
+        // Subscriptions keep sending one event per line
+        if matches.free[0] == "subscribe" {
+            for line in reader.lines() {
+                println!("{}", line?);
+            }
+        }
+        Ok(())
+    } else {
+        eprintln!(
+            "error: {}",
+            reply["error"].as_str().unwrap_or("unknown error")
+        );
+        process::exit(1);
+    }
+}
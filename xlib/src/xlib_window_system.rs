@@ -5,6 +5,8 @@ extern crate wtftw_core;
 extern crate x11;
 
 use std::borrow::ToOwned;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
 use wtftw_core::config::GeneralConfig;
 use x11::xinerama;
 use x11::xlib;
@@ -14,6 +16,8 @@ use std::ffi::CStr;
 use std::ffi::CString;
 use std::io::Write;
 use std::mem;
+use std::os::raw::c_char;
+use std::os::unix::io::RawFd;
 use std::ptr::null;
 use std::ptr::null_mut;
 use std::slice::from_raw_parts;
@@ -34,9 +38,13 @@ const UNMAPNOTIFY: usize = 18;
 const MAPREQUEST: usize = 20;
 const CONFIGURENOTIFY: usize = 22;
 const CONFIGUREREQUEST: usize = 23;
+const EXPOSE: usize = 12;
 const PROPERTYNOTIFY: usize = 28;
 const CLIENTMESSAGE: usize = 33;
 
+/// The space around the text of notices, in pixels
+const NOTICE_PADDING: i32 = 8;
+
 /// A custom error handler to prevent xlib from crashing the whole WM.
 /// Necessary because a few events may call the error routine.
 unsafe extern "C" fn error_handler(_: *mut xlib::Display, _: *mut xlib::XErrorEvent) -> i32 {
@@ -51,6 +59,16 @@ pub struct XlibWindowSystem {
     display: *mut xlib::Display,
     root: Window,
     ewmh_child: Window,
+    /// The last known stacking order, topmost window first
+    stacking: RefCell<Vec<Window>>,
+    /// The desktop last published on each client, as every write
+    /// wakes the event loop with a PropertyNotify
+    desktops: RefCell<BTreeMap<Window, u64>>,
+    /// Whether each client was last published as fullscreen
+    fullscreen: RefCell<BTreeMap<Window, bool>>,
+    /// The notice last asked for, and the window showing it
+    /// unless it was clicked away
+    notice: RefCell<(Option<String>, Option<Window>)>,
 }
 
 impl Default for XlibWindowSystem {
@@ -91,6 +109,10 @@ impl XlibWindowSystem {
                 display,
                 root: root as u64,
                 ewmh_child: 0,
+                stacking: RefCell::new(Vec::new()),
+                desktops: RefCell::new(BTreeMap::new()),
+                fullscreen: RefCell::new(BTreeMap::new()),
+                notice: RefCell::new((None, None)),
             };
 
             // let name = (*CString::new(&b"wtftw"[..]).unwrap()).as_ptr();
@@ -174,6 +196,13 @@ impl XlibWindowSystem {
                 res.get_atom("_NET_ACTIVE_WINDOW"),
                 res.get_atom("_NET_NUMBER_OF_DESKTOPS"),
                 res.get_atom("_NET_CURRENT_DESKTOP"),
+                res.get_atom("_NET_CLIENT_LIST"),
+                res.get_atom("_NET_CLIENT_LIST_STACKING"),
+                res.get_atom("_NET_DESKTOP_NAMES"),
+                res.get_atom("_NET_WM_DESKTOP"),
+                res.get_atom("_NET_CLOSE_WINDOW"),
+                res.get_atom("_NET_WM_STATE"),
+                res.get_atom("_NET_WM_STATE_FULLSCREEN"),
             ];
             xlib::XChangeProperty(
                 res.display,
@@ -294,6 +323,121 @@ impl XlibWindowSystem {
         }
     }
 
+    /// Set the given property to a list of values of the given type
+    fn set_list(&self, window: Window, property: &str, typ: &str, values: &[u64]) {
+        let property = self.get_atom(property);
+        let typ = self.get_atom(typ);
+        let data: Vec<u64> = values.to_vec();
+        unsafe {
+            xlib::XChangeProperty(
+                self.display,
+                window as u64,
+                property as u64,
+                typ as u64,
+                32,
+                0,
+                data.as_ptr() as *mut u8,
+                data.len() as i32,
+            );
+        }
+    }
+
+    /// Draw the current notice into its window, one line after another
+    fn draw_notice(&self, window: Window) {
+        let text = match self.notice.borrow().0 {
+            Some(ref text) => text.clone(),
+            None => return,
+        };
+
+        unsafe {
+            let font = xlib::XLoadQueryFont(self.display, b"fixed\0".as_ptr() as *const c_char);
+            if font.is_null() {
+                return;
+            }
+            let line_height = (*font).ascent + (*font).descent;
+            let gc = xlib::XCreateGC(self.display, window, 0, null_mut());
+            xlib::XSetForeground(self.display, gc, 0xffffff);
+            xlib::XSetFont(self.display, gc, (*font).fid);
+
+            for (i, line) in text.lines().enumerate() {
+                // Core fonts only know Latin-1
+                let line: Vec<u8> = line
+                    .chars()
+                    .map(|c| if c.is_ascii() { c as u8 } else { b'?' })
+                    .collect();
+                xlib::XDrawString(
+                    self.display,
+                    window,
+                    gc,
+                    NOTICE_PADDING,
+                    NOTICE_PADDING + line_height * i as i32 + (*font).ascent,
+                    line.as_ptr() as *const c_char,
+                    line.len() as i32,
+                );
+            }
+
+            xlib::XFreeGC(self.display, gc);
+            xlib::XFreeFont(self.display, font);
+        }
+    }
+
+    /// Add or remove the fullscreen state of the given window,
+    /// keeping all other states the client set
+    fn set_fullscreen_state(&self, window: Window, fullscreen: bool) {
+        let atom = self.get_atom("_NET_WM_STATE_FULLSCREEN");
+        let old = self
+            .get_property_from_string("_NET_WM_STATE", window)
+            .unwrap_or_default();
+        let mut state: Vec<u64> = old.iter().copied().filter(|&x| x != atom).collect();
+        if fullscreen {
+            state.push(atom);
+        }
+        if state != old {
+            self.set_list(window, "_NET_WM_STATE", "ATOM", &state);
+        }
+    }
+
+    /// Set the given property to a single cardinal value
+    fn set_cardinal(&self, window: Window, property: &str, value: u64) {
+        let property = self.get_atom(property);
+        let cardinal = self.get_atom("CARDINAL");
+        let data: [u64; 1] = [value];
+        unsafe {
+            xlib::XChangeProperty(
+                self.display,
+                window as u64,
+                property as u64,
+                cardinal as u64,
+                32,
+                0,
+                data.as_ptr() as *mut u8,
+                1,
+            );
+        }
+    }
+
+    /// Set the given property to a list of null-terminated UTF-8 strings
+    fn set_utf8_list(&self, window: Window, property: &str, strings: &[String]) {
+        let property = self.get_atom(property);
+        let utf8 = self.get_atom("UTF8_STRING");
+        let data: Vec<u8> = strings
+            .iter()
+            .flat_map(|x| x.bytes().chain(Some(0)))
+            .collect();
+        unsafe {
+            xlib::XChangeProperty(
+                self.display,
+                window as u64,
+                property as u64,
+                utf8 as u64,
+                8,
+                0,
+                data.as_ptr() as *mut u8,
+                data.len() as i32,
+            );
+        }
+    }
+
     fn set_button_grab(&self, grab: bool, window: Window) {
         if grab {
             debug!("grabbing mouse buttons for {}", window);
@@ -505,6 +649,44 @@ impl WindowSystem for XlibWindowSystem {
         }
     }
 
+    fn get_window_role(&self, window: Window) -> String {
+        unsafe {
+            let mut actual_type_return: u64 = 0;
+            let mut actual_format_return: i32 = 0;
+            let mut nitems_return: u64 = 0;
+            let mut bytes_after_return: u64 = 0;
+            let mut prop_return: *mut u8 = null_mut();
+
+            let r = xlib::XGetWindowProperty(
+                self.display,
+                window as u64,
+                self.get_atom("WM_WINDOW_ROLE"),
+                0,
+                i64::MAX,
+                0,
+                0,
+                &mut actual_type_return,
+                &mut actual_format_return,
+                &mut nitems_return,
+                &mut bytes_after_return,
+                &mut prop_return,
+            );
+
+            if r != 0 || prop_return.is_null() {
+                return String::new();
+            }
+
+            let role = if actual_format_return == 8 {
+                String::from_utf8_lossy(from_raw_parts(prop_return, nitems_return as usize))
+                    .into_owned()
+            } else {
+                String::new()
+            };
+            xlib::XFree(prop_return as *mut _);
+            role
+        }
+    }
+
     fn get_windows(&self) -> Vec<Window> {
         unsafe {
             let mut unused: u64 = 0;
@@ -676,6 +858,10 @@ impl WindowSystem for XlibWindowSystem {
         unsafe { xlib::XPending(self.display) != 0 }
     }
 
+    fn event_fd(&self) -> Option<RawFd> {
+        unsafe { Some(xlib::XConnectionNumber(self.display)) }
+    }
+
     fn get_event(&self) -> WindowSystemEvent {
         let mut event = xlib::XEvent { pad: [0; 24] };
         unsafe {
@@ -759,8 +945,23 @@ impl WindowSystem for XlibWindowSystem {
                     WindowSystemEvent::UnknownEvent
                 }
             }
+            EXPOSE => {
+                let event = xlib::XExposeEvent::from(event);
+                if self.notice.borrow().1 == Some(event.window) && event.count == 0 {
+                    self.draw_notice(event.window);
+                }
+                WindowSystemEvent::UnknownEvent
+            }
             BUTTONPRESS => {
                 let event = xlib::XButtonEvent::from(event);
+                // Notices are clicked away
+                if self.notice.borrow().1 == Some(event.window) {
+                    let window = self.notice.borrow_mut().1.take();
+                    unsafe {
+                        xlib::XDestroyWindow(self.display, window.unwrap());
+                    }
+                    return WindowSystemEvent::UnknownEvent;
+                }
                 let button = MouseCommand {
                     button: event.button,
                     mask: KeyModifiers::from_bits(0xEF & event.state as u32).unwrap(),
@@ -780,7 +981,13 @@ impl WindowSystem for XlibWindowSystem {
                     key: xlib::XKeycodeToKeysym(self.display, event.keycode as u8, 0) as u64,
                     mask: KeyModifiers::from_bits(0xEF & event.state as u32).unwrap(),
                 };
-                WindowSystemEvent::KeyPressed(event.window as u64, key)
+                // Modifiers alone only arrive while the keyboard is grabbed
+                // and must not count as keys of a chord
+                if (0xffe1..=0xffee).contains(&key.key) || (0xfe01..=0xfe13).contains(&key.key) {
+                    WindowSystemEvent::UnknownEvent
+                } else {
+                    WindowSystemEvent::KeyPressed(event.window as u64, key)
+                }
             },
             MOTIONOTIFY => {
                 let event = xlib::XMotionEvent::from(event);
@@ -835,6 +1042,18 @@ impl WindowSystem for XlibWindowSystem {
         }
     }
 
+    fn ungrab_keys(&self) {
+        unsafe {
+            xlib::XUngrabKey(self.display, 0, 0x8000, self.root as u64);
+        }
+    }
+
+    fn ungrab_buttons(&self) {
+        unsafe {
+            xlib::XUngrabButton(self.display, 0, 0x8000, self.root as u64);
+        }
+    }
+
     fn grab_pointer(&self) {
         unsafe {
             xlib::XGrabPointer(self.display, self.root as u64, 0, 0x48, 1, 1, 0, 0, 0);
@@ -847,6 +1066,18 @@ impl WindowSystem for XlibWindowSystem {
         }
     }
 
+    fn grab_keyboard(&self) {
+        unsafe {
+            xlib::XGrabKeyboard(self.display, self.root as u64, 0, 1, 1, 0);
+        }
+    }
+
+    fn ungrab_keyboard(&self) {
+        unsafe {
+            xlib::XUngrabKeyboard(self.display, 0);
+        }
+    }
+
     fn remove_enter_events(&self) {
         unsafe {
             let mut event = xlib::XEvent { pad: [0; 24] };
@@ -899,7 +1130,86 @@ impl WindowSystem for XlibWindowSystem {
         }
     }
 
+    fn get_window_types(&self, window: Window) -> Vec<WindowType> {
+        let types = [
+            ("_NET_WM_WINDOW_TYPE_DESKTOP", WindowType::Desktop),
+            ("_NET_WM_WINDOW_TYPE_DOCK", WindowType::Dock),
+            ("_NET_WM_WINDOW_TYPE_TOOLBAR", WindowType::Toolbar),
+            ("_NET_WM_WINDOW_TYPE_MENU", WindowType::Menu),
+            ("_NET_WM_WINDOW_TYPE_UTILITY", WindowType::Utility),
+            ("_NET_WM_WINDOW_TYPE_SPLASH", WindowType::Splash),
+            ("_NET_WM_WINDOW_TYPE_DIALOG", WindowType::Dialog),
+            (
+                "_NET_WM_WINDOW_TYPE_DROPDOWN_MENU",
+                WindowType::DropdownMenu,
+            ),
+            ("_NET_WM_WINDOW_TYPE_POPUP_MENU", WindowType::PopupMenu),
+            ("_NET_WM_WINDOW_TYPE_TOOLTIP", WindowType::Tooltip),
+            ("_NET_WM_WINDOW_TYPE_NOTIFICATION", WindowType::Notification),
+            ("_NET_WM_WINDOW_TYPE_COMBO", WindowType::Combo),
+            ("_NET_WM_WINDOW_TYPE_DND", WindowType::Dnd),
+            ("_NET_WM_WINDOW_TYPE_NORMAL", WindowType::Normal),
+        ];
+
+        self.get_property_from_string("_NET_WM_WINDOW_TYPE", window)
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|atom| {
+                types
+                    .iter()
+                    .find(|&&(name, _)| self.get_atom(name) == atom)
+                    .map(|&(_, t)| t)
+            })
+            .collect()
+    }
+
+    fn requests_fullscreen(&self, window: Window) -> bool {
+        let fullscreen = self.get_atom("_NET_WM_STATE_FULLSCREEN");
+        self.get_property_from_string("_NET_WM_STATE", window)
+            .is_some_and(|state| state.contains(&fullscreen))
+    }
+
+    fn get_transient_for(&self, window: Window) -> Option<Window> {
+        unsafe {
+            let mut parent: u64 = 0;
+            let status = xlib::XGetTransientForHint(self.display, window as u64, &mut parent);
+            if status != 0 && parent != 0 && parent != self.root {
+                Some(parent as Window)
+            } else {
+                None
+            }
+        }
+    }
+
+    fn is_urgent(&self, window: Window) -> bool {
+        unsafe {
+            let hints = xlib::XGetWMHints(self.display, window as u64);
+            if hints.is_null() {
+                return false;
+            }
+            let urgent = (*hints).flags & xlib::XUrgencyHint != 0;
+            xlib::XFree(hints as *mut _);
+            urgent
+        }
+    }
+
+    fn get_atom_name(&self, atom: u64) -> String {
+        unsafe {
+            let name = xlib::XGetAtomName(self.display, atom);
+            if name.is_null() {
+                return String::new();
+            }
+            let result = CStr::from_ptr(name).to_string_lossy().into_owned();
+            xlib::XFree(name as *mut _);
+            result
+        }
+    }
+
     fn restack_windows(&self, w: Vec<Window>) {
+        let mut stacking = self.stacking.borrow_mut();
+        stacking.retain(|x| !w.contains(x));
+        stacking.splice(0..0, w.iter().copied());
+
         unsafe {
             let mut windows = w.iter().map(|&x| x as u64).collect::<Vec<_>>();
             xlib::XRestackWindows(
@@ -984,6 +1294,31 @@ impl WindowSystem for XlibWindowSystem {
                 1,
             );
 
+            let clients = manager.workspaces.all_windows();
+            self.stacking.borrow_mut().retain(|x| clients.contains(x));
+            let stacking = manager.workspaces.stacking_order(&self.stacking.borrow());
+            self.set_list(self.root, "_NET_CLIENT_LIST", "WINDOW", &clients);
+            self.set_list(self.root, "_NET_CLIENT_LIST_STACKING", "WINDOW", &stacking);
+
+            let mut workspaces = manager.workspaces.workspaces();
+            workspaces.sort_by_key(|w| w.id);
+            let names: Vec<String> = workspaces.into_iter().map(|w| w.tag).collect();
+            self.set_utf8_list(self.root, "_NET_DESKTOP_NAMES", &names);
+
+            let mut desktops = self.desktops.borrow_mut();
+            desktops.retain(|x, _| clients.contains(x));
+            let mut published = self.fullscreen.borrow_mut();
+            published.retain(|x, _| clients.contains(x));
+            for (win, tag) in manager.workspaces.all_windows_with_workspaces() {
+                if desktops.insert(win, tag as u64) != Some(tag as u64) {
+                    self.set_cardinal(win, "_NET_WM_DESKTOP", tag as u64);
+                }
+                let is_fullscreen = manager.workspaces.is_fullscreen(win);
+                if published.insert(win, is_fullscreen) != Some(is_fullscreen) {
+                    self.set_fullscreen_state(win, is_fullscreen);
+                }
+            }
+
             if let Some(win) = window {
                 let w: u32 = win as u32;
                 let win_ptr: *const u32 = &w;
@@ -1003,6 +1338,89 @@ impl WindowSystem for XlibWindowSystem {
         }
     }
 
+    fn show_notice(&self, notice: Option<&str>) {
+        if self.notice.borrow().0.as_deref() == notice {
+            return;
+        }
+
+        let mut shown = self.notice.borrow_mut();
+        if let Some(window) = shown.1.take() {
+            unsafe {
+                xlib::XDestroyWindow(self.display, window);
+            }
+        }
+        shown.0 = notice.map(|x| x.to_owned());
+        let lines = match notice {
+            Some(text) => text.lines().count().max(1) as i32,
+            None => return,
+        };
+
+        // Across the top of the first screen, above everything else
+        let Rectangle(x, y, width, _) = self.get_screen_infos()[0];
+        unsafe {
+            let font = xlib::XLoadQueryFont(self.display, b"fixed\0".as_ptr() as *const c_char);
+            let line_height = if font.is_null() {
+                13
+            } else {
+                let height = (*font).ascent + (*font).descent;
+                xlib::XFreeFont(self.display, font);
+                height
+            };
+
+            let window = xlib::XCreateSimpleWindow(
+                self.display,
+                self.root,
+                x,
+                y,
+                width,
+                (lines * line_height + 2 * NOTICE_PADDING) as u32,
+                0,
+                0,
+                0x800000,
+            );
+            let mut attributes: xlib::XSetWindowAttributes = mem::zeroed();
+            attributes.override_redirect = 1;
+            xlib::XChangeWindowAttributes(
+                self.display,
+                window,
+                xlib::CWOverrideRedirect,
+                &mut attributes,
+            );
+            xlib::XSelectInput(
+                self.display,
+                window,
+                xlib::ExposureMask | xlib::ButtonPressMask,
+            );
+            xlib::XMapRaised(self.display, window);
+            shown.1 = Some(window);
+        }
+    }
+
+    fn decode_client_message(
+        &self,
+        window: Window,
+        message_type: u64,
+        _: i32,
+        data: [i32; 5],
+    ) -> Option<ClientMessage> {
+        if message_type == self.get_atom("_NET_ACTIVE_WINDOW") {
+            Some(ClientMessage::ActivateWindow(window))
+        } else if message_type == self.get_atom("_NET_CLOSE_WINDOW") {
+            Some(ClientMessage::CloseWindow(window))
+        } else if message_type == self.get_atom("_NET_WM_DESKTOP") {
+            Some(ClientMessage::MoveToWorkspace(window, data[0] as u32))
+        } else if message_type == self.get_atom("_NET_WM_STATE") {
+            let fullscreen = self.get_atom("_NET_WM_STATE_FULLSCREEN");
+            if data[1] as u32 as u64 == fullscreen || data[2] as u32 as u64 == fullscreen {
+                StateChange::from_action(data[0]).map(|c| ClientMessage::Fullscreen(window, c))
+            } else {
+                None
+            }
+        } else {
+            None
+        }
+    }
+
     fn get_pointer(&self, window: Window) -> (u32, u32) {
         let mut tmp_win: u64 = 0;
         let mut x: i32 = 0;